@@ -0,0 +1,378 @@
+use crate::model::Pair;
+use crate::standings::Standing;
+use anyhow::Result;
+use std::cmp::Ordering;
+
+/// A pair advancing from pool play to the playoff bracket
+#[derive(Debug, Clone)]
+pub struct Qualifier {
+    /// Name of the pool the pair played in
+    pub pool: String,
+    /// One-based place of the pair in its pool
+    pub place: usize,
+    /// The pair
+    pub pair: Pair,
+    /// Standing of the pair in its pool
+    standing: Standing,
+}
+
+impl Qualifier {
+    /// Returns the short name of the qualifier, e.g. `A1` for the winner of pool A
+    ///
+    /// # Returns
+    ///
+    /// * `String` - Pool name followed by the place
+    pub fn label(&self) -> String {
+        format!("{}{}", self.pool, self.place)
+    }
+
+    /// Compares two qualifiers of the same place in different pools
+    ///
+    /// Pools may differ in size, so the share of matches and games won is compared
+    /// before the point differential per match.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Qualifier to compare with
+    ///
+    /// # Returns
+    ///
+    /// * `Ordering` - `Less` if `self` should be seeded higher
+    fn compare(&self, other: &Qualifier) -> Ordering {
+        let key = |qualifier: &Qualifier| {
+            let record = &qualifier.standing.record;
+            let played = record.played.max(1) as f64;
+            [
+                record.won as f64 / played,
+                record.game_win_pct(),
+                record.differential as f64 / played,
+            ]
+        };
+        let (own, other) = (key(self), key(other));
+        own.iter()
+            .zip(&other)
+            .map(|(a, b)| b.total_cmp(a))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Takes the top pairs of each pool and orders them by seed
+///
+/// All pool winners are seeded first, then all runners-up, and so on, so that the
+/// standard seed positions pair winners with runners-up of other pools (A1 vs B2).
+/// Pairs of the same place are ordered by their record.
+///
+/// # Arguments
+///
+/// * `pools` - Standings of each pool, in pool order
+/// * `advance` - Number of pairs advancing from each pool
+///
+/// # Returns
+///
+/// * `Result<Vec<Qualifier>>` - Ok with the qualifiers ordered by seed if successful, Err if a tie at the cut is not broken
+pub fn qualifiers(pools: &[(String, Vec<Standing>)], advance: usize) -> Result<Vec<Qualifier>> {
+    let mut places: Vec<Vec<Qualifier>> = vec![Vec::new(); advance];
+    for (pool, standings) in pools {
+        if let Some(next) = standings.get(advance) {
+            if next.rank <= advance {
+                let tied: Vec<&str> = standings
+                    .iter()
+                    .filter(|standing| standing.rank == next.rank)
+                    .map(|standing| standing.pair.pair_no.as_str())
+                    .collect();
+                anyhow::bail!(
+                    "Pairs {} of pool {} are tied across place {}; add `coin-flip` to --tiebreak to break the tie",
+                    tied.join(", "),
+                    pool,
+                    advance
+                );
+            }
+        }
+        for (index, standing) in standings.iter().take(advance).enumerate() {
+            places[index].push(Qualifier {
+                pool: pool.clone(),
+                place: index + 1,
+                pair: standing.pair.clone(),
+                standing: standing.clone(),
+            });
+        }
+    }
+
+    let mut seeds = Vec::new();
+    for mut place in places {
+        place.sort_by(Qualifier::compare);
+        seeds.extend(place);
+    }
+    Ok(seeds)
+}
+
+/// Swaps qualifiers of the same place so that pairs of the same pool do not meet in the first round
+///
+/// # Arguments
+///
+/// * `seeds` - Qualifiers ordered by seed
+/// * `pairings` - Zero-based seeds meeting in the first round
+///
+/// # Returns
+///
+/// * `Vec<[usize; 2]>` - Pairings still between pairs of the same pool
+pub fn avoid_rematches(seeds: &mut [Qualifier], pairings: &[[usize; 2]]) -> Vec<[usize; 2]> {
+    let rematches = |seeds: &[Qualifier]| -> Vec<[usize; 2]> {
+        pairings
+            .iter()
+            .copied()
+            .filter(|[a, b]| seeds[*a].pool == seeds[*b].pool)
+            .collect()
+    };
+
+    for &[a, b] in pairings {
+        if seeds[a].pool != seeds[b].pool {
+            continue;
+        }
+        // Move the lower seed, trading places with a qualifier of the same place
+        let lower = a.max(b);
+        let count = rematches(seeds).len();
+        let candidates: Vec<usize> = (0..seeds.len())
+            .filter(|&other| other != lower && seeds[other].place == seeds[lower].place)
+            .collect();
+        let swap = candidates.into_iter().find(|&other| {
+            seeds.swap(lower, other);
+            let fewer = rematches(seeds).len() < count;
+            seeds.swap(lower, other);
+            fewer
+        });
+        if let Some(other) = swap {
+            seeds.swap(lower, other);
+        }
+    }
+
+    rematches(seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bracket::{Bracket, Entrant, Format};
+    use crate::standings::Record;
+
+    /// Returns the standing of a pair that won some of its matches
+    ///
+    /// # Arguments
+    ///
+    /// * `pair_no` - Pair number
+    /// * `rank` - Rank in the pool
+    /// * `won` - Matches won, each 2-0 in games
+    /// * `played` - Matches played, the others lost 0-2
+    /// * `differential` - Point differential
+    ///
+    /// # Returns
+    ///
+    /// * `Standing` - The standing
+    fn standing(
+        pair_no: &str,
+        rank: usize,
+        won: usize,
+        played: usize,
+        differential: i64,
+    ) -> Standing {
+        Standing {
+            rank,
+            pair: Pair {
+                pair_no: pair_no.to_string(),
+                players: vec![format!("Player {}", pair_no)],
+            },
+            record: Record {
+                played,
+                won,
+                lost: played - won,
+                games_won: won * 2,
+                games_lost: (played - won) * 2,
+                differential,
+                ..Record::default()
+            },
+            decided_by: None,
+        }
+    }
+
+    /// Returns the labels of qualifiers in seed order
+    ///
+    /// # Arguments
+    ///
+    /// * `seeds` - Qualifiers ordered by seed
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - Label of each qualifier, e.g. `A1`
+    fn labels(seeds: &[Qualifier]) -> Vec<String> {
+        seeds.iter().map(Qualifier::label).collect()
+    }
+
+    /// Returns the seeds meeting in the first round of a single-elimination bracket
+    ///
+    /// # Arguments
+    ///
+    /// * `seed_count` - Number of seeds
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<[usize; 2]>` - Zero-based seeds of each first-round match without a bye
+    fn pairings(seed_count: usize) -> Vec<[usize; 2]> {
+        Bracket::new(Format::Single, seed_count, false)
+            .unwrap()
+            .matches
+            .iter()
+            .filter_map(|bracket_match| match bracket_match.entrants {
+                [Entrant::Seed(a), Entrant::Seed(b)] => Some([a, b]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn winners_meet_runners_up_of_other_pools() {
+        let pools = [
+            (
+                "A".to_string(),
+                vec![
+                    standing("1", 1, 3, 3, 20),
+                    standing("2", 2, 2, 3, 8),
+                    standing("3", 3, 1, 3, -9),
+                    standing("4", 4, 0, 3, -19),
+                ],
+            ),
+            (
+                "B".to_string(),
+                vec![
+                    standing("5", 1, 3, 3, 15),
+                    standing("6", 2, 2, 3, 5),
+                    standing("7", 3, 1, 3, -4),
+                    standing("8", 4, 0, 3, -16),
+                ],
+            ),
+        ];
+
+        let mut seeds = qualifiers(&pools, 2).unwrap();
+        assert_eq!(labels(&seeds), ["A1", "B1", "A2", "B2"]);
+        assert!(avoid_rematches(&mut seeds, &pairings(4)).is_empty());
+        assert_eq!(labels(&seeds), ["A1", "B1", "A2", "B2"]);
+        // Seed 1 meets seed 4 and seed 2 meets seed 3
+        assert_eq!(pairings(4), [[0, 3], [1, 2]]);
+    }
+
+    #[test]
+    fn runners_up_swap_places_to_avoid_a_rematch() {
+        // B2 has the better record, so A1 would meet A2 and B1 would meet B2
+        let pools = [
+            (
+                "A".to_string(),
+                vec![standing("1", 1, 3, 3, 20), standing("2", 2, 2, 3, 2)],
+            ),
+            (
+                "B".to_string(),
+                vec![standing("5", 1, 3, 3, 15), standing("6", 2, 2, 3, 7)],
+            ),
+        ];
+
+        let mut seeds = qualifiers(&pools, 2).unwrap();
+        assert_eq!(labels(&seeds), ["A1", "B1", "B2", "A2"]);
+        assert!(avoid_rematches(&mut seeds, &pairings(4)).is_empty());
+        assert_eq!(labels(&seeds), ["A1", "B1", "A2", "B2"]);
+    }
+
+    #[test]
+    fn rematch_is_reported_when_no_swap_helps() {
+        let pools = [(
+            "A".to_string(),
+            vec![standing("1", 1, 2, 2, 10), standing("2", 2, 1, 2, 0)],
+        )];
+
+        let mut seeds = qualifiers(&pools, 2).unwrap();
+        assert_eq!(avoid_rematches(&mut seeds, &pairings(2)), [[0, 1]]);
+    }
+
+    #[test]
+    fn three_pools_seed_every_winner_before_the_runners_up() {
+        let pools = [
+            (
+                "A".to_string(),
+                vec![standing("1", 1, 3, 3, 12), standing("2", 2, 2, 3, 1)],
+            ),
+            (
+                "B".to_string(),
+                vec![standing("5", 1, 3, 3, 10), standing("6", 2, 2, 3, 9)],
+            ),
+            (
+                "C".to_string(),
+                vec![standing("9", 1, 3, 3, 18), standing("10", 2, 2, 3, 4)],
+            ),
+        ];
+
+        let mut seeds = qualifiers(&pools, 2).unwrap();
+        assert_eq!(labels(&seeds), ["C1", "A1", "B1", "B2", "C2", "A2"]);
+        // The top two seeds get byes; B1 meets A2 and the other runners-up meet each other
+        assert_eq!(pairings(6), [[3, 4], [2, 5]]);
+        assert!(avoid_rematches(&mut seeds, &pairings(6)).is_empty());
+        assert_eq!(seeds[0].pair.pair_no, "9");
+    }
+
+    #[test]
+    fn pools_of_different_sizes_compare_shares_of_wins() {
+        // A1 won fewer matches than B1 but lost no game in a smaller pool
+        let mut b1 = standing("4", 1, 4, 4, 30);
+        b1.record.games_lost = 1;
+        let pools = [
+            (
+                "A".to_string(),
+                vec![
+                    standing("1", 1, 2, 2, 16),
+                    standing("2", 2, 1, 2, 0),
+                    standing("3", 3, 0, 2, -16),
+                ],
+            ),
+            (
+                "B".to_string(),
+                vec![
+                    b1,
+                    standing("5", 2, 3, 4, 10),
+                    standing("6", 3, 2, 4, 0),
+                    standing("7", 4, 1, 4, -10),
+                    standing("8", 5, 0, 4, -30),
+                ],
+            ),
+        ];
+
+        let seeds = qualifiers(&pools, 2).unwrap();
+        assert_eq!(labels(&seeds), ["A1", "B1", "B2", "A2"]);
+    }
+
+    #[test]
+    fn tie_across_the_cut_is_rejected() {
+        let pools = [
+            (
+                "A".to_string(),
+                vec![standing("1", 1, 3, 3, 20), standing("2", 2, 2, 3, 5)],
+            ),
+            (
+                "B".to_string(),
+                vec![
+                    standing("5", 1, 3, 3, 15),
+                    standing("6", 2, 1, 3, 0),
+                    standing("7", 2, 1, 3, 0),
+                    standing("8", 4, 1, 3, -15),
+                ],
+            ),
+        ];
+
+        let error = qualifiers(&pools, 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Pairs 6, 7 of pool B are tied across place 2; add `coin-flip` to --tiebreak to break the tie"
+        );
+        // A tie above the cut does not matter
+        let mut tied_winners = pools[1].1.clone();
+        tied_winners[1].rank = 1;
+        tied_winners[2].rank = 3;
+        assert!(qualifiers(&[("B".to_string(), tied_winners)], 2).is_ok());
+    }
+}
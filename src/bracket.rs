@@ -0,0 +1,1044 @@
+use crate::model::Pair;
+use crate::template::escape_xml;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Part of a bracket a match belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Main draw, or winners bracket of a double elimination
+    Winners,
+    /// Losers bracket of a double elimination
+    Losers,
+    /// Consolation draw for the pairs that lost their first match
+    Consolation,
+    /// Final between the winners of the winners and losers brackets
+    Final,
+}
+
+/// Where a pair playing a bracket match comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entrant {
+    /// Seeded pair, as a zero-based index into the pairs ordered by seed
+    Seed(usize),
+    /// Winner of the match at this index
+    Winner(usize),
+    /// Loser of the match at this index
+    Loser(usize),
+}
+
+/// A match of a bracket
+#[derive(Debug, Clone)]
+pub struct BracketMatch {
+    /// Part of the bracket the match belongs to
+    pub side: Side,
+    /// One-based round number within the side
+    pub round: usize,
+    /// One-based number of the match within its round
+    pub number: usize,
+    /// The two entrants, in the order they are drawn from top to bottom
+    pub entrants: [Entrant; 2],
+    /// Whether the match is only played if the winner of the losers bracket wins the final
+    pub if_necessary: bool,
+}
+
+/// Matches of a playoff bracket, in the order they can be played
+#[derive(Debug, Clone, Default)]
+pub struct Bracket {
+    /// Matches ordered by side, then round, then number
+    pub matches: Vec<BracketMatch>,
+}
+
+/// Kind of bracket to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Single elimination
+    Single,
+    /// Double elimination with a winners and a losers bracket
+    Double,
+    /// Single elimination with a consolation draw for first-match losers
+    Consolation,
+}
+
+/// Which pair of a played match an entrant is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The pair that won the match
+    Winner,
+    /// The pair that lost the match
+    Loser,
+}
+
+/// Paper size of the bracket poster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Paper {
+    /// 420 x 297 mm
+    A3,
+    /// 594 x 420 mm
+    A2,
+}
+
+/// Prefix of the entrant label of a match winner
+const WINNER_OF: &str = "Winner of ";
+/// Prefix of the entrant label of a match loser
+const LOSER_OF: &str = "Loser of ";
+
+/// Margin around the poster, in points
+const MARGIN: f32 = 36.0;
+/// Height of the poster title, in points
+const TITLE_HEIGHT: f32 = 36.0;
+
+impl Side {
+    /// Returns the prefix of the match IDs of the side
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - `W`, `L`, `C` or `F`
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Side::Winners => "W",
+            Side::Losers => "L",
+            Side::Consolation => "C",
+            Side::Final => "F",
+        }
+    }
+
+    /// Returns the name of the side, written to the `Pool` column of the match CSV
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - Human readable name of the side
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Winners => "Main",
+            Side::Losers => "Losers",
+            Side::Consolation => "Consolation",
+            Side::Final => "Final",
+        }
+    }
+}
+
+impl Paper {
+    /// Returns the size of the paper in landscape orientation
+    ///
+    /// # Returns
+    ///
+    /// * `(f32, f32)` - Width and height in points
+    pub fn size(self) -> (f32, f32) {
+        match self {
+            Paper::A3 => (1191.0, 842.0),
+            Paper::A2 => (1684.0, 1191.0),
+        }
+    }
+}
+
+/// Returns the order in which seeds are placed on the lines of a full draw
+///
+/// Seed 1 and 2 can only meet in the final, seeds 1-4 in the semifinals, and so on.
+/// When the number of pairs is not a power of two, the missing seeds are byes, so the
+/// top seeds skip the first round.
+///
+/// # Arguments
+///
+/// * `size` - Number of lines in the draw, a power of two
+///
+/// # Returns
+///
+/// * `Vec<usize>` - One-based seeds from the top line to the bottom line
+pub fn seed_positions(size: usize) -> Vec<usize> {
+    let mut positions = vec![1];
+    while positions.len() < size {
+        let lines = positions.len() * 2;
+        positions = positions
+            .iter()
+            .flat_map(|&seed| [seed, lines + 1 - seed])
+            .collect();
+    }
+    positions
+}
+
+/// Returns the match an entrant label refers to
+///
+/// # Arguments
+///
+/// * `text` - Player name written to the match CSV, e.g. `Winner of W1-2` or `Loser of W1-2`
+///
+/// # Returns
+///
+/// * `Option<(Outcome, &str)>` - Winner or loser, and the ID of the feeding match, or None for a pair name
+pub fn feeder(text: &str) -> Option<(Outcome, &str)> {
+    if let Some(match_id) = text.strip_prefix(WINNER_OF) {
+        return Some((Outcome::Winner, match_id));
+    }
+    text.strip_prefix(LOSER_OF)
+        .map(|match_id| (Outcome::Loser, match_id))
+}
+
+/// Returns whether a player name is an entrant label standing for a pair not known yet
+///
+/// # Arguments
+///
+/// * `text` - Player name written to the match CSV
+///
+/// # Returns
+///
+/// * `bool` - true for `Winner of W1-2`, `Loser of W1-2` and the like
+pub fn is_entrant_label(text: &str) -> bool {
+    feeder(text).is_some()
+}
+
+impl Bracket {
+    /// Seeds pairs into a bracket of the given format
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Kind of bracket
+    /// * `pair_count` - Number of pairs, ordered by seed
+    /// * `if_necessary` - Whether a double elimination ends with a second final when the
+    ///   winner of the losers bracket wins the first one
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Bracket>` - Ok with the bracket if there are at least two pairs, Err otherwise
+    pub fn new(format: Format, pair_count: usize, if_necessary: bool) -> Result<Bracket> {
+        if pair_count < 2 {
+            anyhow::bail!("A bracket needs at least 2 pairs");
+        }
+        if if_necessary && format != Format::Double {
+            anyhow::bail!("An \"if necessary\" final only exists in a double elimination");
+        }
+
+        let mut bracket = Bracket::default();
+        let lines: Vec<Option<Entrant>> = seed_positions(pair_count.next_power_of_two())
+            .into_iter()
+            .map(|seed| (seed <= pair_count).then_some(Entrant::Seed(seed - 1)))
+            .collect();
+        let (winner, losers) = bracket.add_elimination(Side::Winners, lines.clone());
+
+        match format {
+            Format::Single => {}
+            Format::Double => bracket.add_losers_bracket(winner, &losers, if_necessary),
+            Format::Consolation => bracket.add_consolation(&lines, &losers),
+        }
+
+        Ok(bracket)
+    }
+
+    /// Adds the losers bracket and the final of a double elimination
+    ///
+    /// Each round of the losers bracket first pairs its survivors, then pits them against
+    /// the pairs dropping from the next round of the winners bracket. Drop-ins are taken
+    /// in reverse order every other round, so that pairs that met in the winners bracket
+    /// do not meet again right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `winner` - Winner of the winners bracket
+    /// * `losers` - Losers of each round of the winners bracket, by draw position; None is a bye
+    /// * `if_necessary` - Whether to add a second final, played if the first one is lost by the winner of the winners bracket
+    fn add_losers_bracket(
+        &mut self,
+        winner: Option<Entrant>,
+        losers: &[Vec<Option<Entrant>>],
+        if_necessary: bool,
+    ) {
+        let mut round = 0;
+        let mut survivors = losers.first().cloned().unwrap_or_default();
+        if survivors.len() > 1 {
+            survivors = self.add_round(Side::Losers, &mut round, survivors).0;
+        }
+        for (round_index, drops) in losers.iter().enumerate().skip(1) {
+            let mut drops = drops.clone();
+            if round_index % 2 == 1 {
+                drops.reverse();
+            }
+            let lines = survivors
+                .into_iter()
+                .zip(drops)
+                .flat_map(|(survivor, drop)| [survivor, drop])
+                .collect();
+            survivors = self.add_round(Side::Losers, &mut round, lines).0;
+            if survivors.len() > 1 {
+                survivors = self.add_round(Side::Losers, &mut round, survivors).0;
+            }
+        }
+
+        let (Some(winner), Some(Some(losers_winner))) = (winner, survivors.first().copied()) else {
+            return;
+        };
+        let final_index = self.push(Side::Final, 1, 1, [winner, losers_winner]);
+        if if_necessary {
+            let reset_index = self.push(
+                Side::Final,
+                2,
+                1,
+                [Entrant::Winner(final_index), Entrant::Loser(final_index)],
+            );
+            self.matches[reset_index].if_necessary = true;
+        }
+    }
+
+    /// Adds a consolation draw for the pairs that lost their first match
+    ///
+    /// These are the losers of the first round, and the losers of second-round matches
+    /// between two pairs that both had a bye. A seed with a bye that loses to a
+    /// first-round winner is not sent to the consolation draw, as it could meet a pair
+    /// that already lost to its opponent.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - Entrants on the lines of the first round of the main draw; None is a bye
+    /// * `losers` - Losers of each round of the main draw, by draw position; None is a bye
+    fn add_consolation(&mut self, lines: &[Option<Entrant>], losers: &[Vec<Option<Entrant>>]) {
+        let second_round_losers = losers.get(1).cloned().unwrap_or_default();
+        let mut entrants = losers.first().cloned().unwrap_or_default();
+        for (position, entrant) in entrants.iter_mut().enumerate() {
+            let bye = |position: usize| {
+                lines
+                    .get(position * 2..position * 2 + 2)
+                    .is_some_and(|pair| pair.iter().any(Option::is_none))
+            };
+            // Only the first of the two byes takes the loser of their second-round match
+            if position % 2 == 0 && bye(position) && bye(position + 1) {
+                *entrant = second_round_losers.get(position / 2).copied().flatten();
+            }
+        }
+        if entrants.iter().flatten().count() > 1 {
+            self.add_elimination(Side::Consolation, entrants);
+        }
+    }
+
+    /// Adds the rounds of an elimination draw to a side of the bracket
+    ///
+    /// # Arguments
+    ///
+    /// * `side` - Side the matches belong to
+    /// * `lines` - Entrants on the lines of the first round; None is a bye
+    ///
+    /// # Returns
+    ///
+    /// * `(Option<Entrant>, Vec<Vec<Option<Entrant>>>)` - The winner of the draw, and the
+    ///   losers of each round by draw position
+    fn add_elimination(
+        &mut self,
+        side: Side,
+        mut lines: Vec<Option<Entrant>>,
+    ) -> (Option<Entrant>, Vec<Vec<Option<Entrant>>>) {
+        let mut round = 0;
+        let mut losers = Vec::new();
+        while lines.len() > 1 {
+            let (winners, round_losers) = self.add_round(side, &mut round, lines);
+            lines = winners;
+            losers.push(round_losers);
+        }
+        (lines.into_iter().next().flatten(), losers)
+    }
+
+    /// Adds one round, pairing the lines two by two
+    ///
+    /// An entrant facing a bye goes through without a match. The round number is only
+    /// taken when at least one match is played.
+    ///
+    /// # Arguments
+    ///
+    /// * `side` - Side the matches belong to
+    /// * `round` - Last round number of the side, incremented if the round has a match
+    /// * `lines` - Entrants of the round; None is a bye
+    ///
+    /// # Returns
+    ///
+    /// * `(Vec<Option<Entrant>>, Vec<Option<Entrant>>)` - Winners and losers of each pair of lines
+    fn add_round(
+        &mut self,
+        side: Side,
+        round: &mut usize,
+        lines: Vec<Option<Entrant>>,
+    ) -> (Vec<Option<Entrant>>, Vec<Option<Entrant>>) {
+        let mut number = 0;
+        let (winners, losers) = lines
+            .chunks(2)
+            .map(|pair| match pair {
+                [Some(first), Some(second)] => {
+                    number += 1;
+                    let index = self.push(side, *round + 1, number, [*first, *second]);
+                    (Some(Entrant::Winner(index)), Some(Entrant::Loser(index)))
+                }
+                [first, second] => (first.or(*second), None),
+                [single] => (*single, None),
+                _ => unreachable!("chunks of one or two"),
+            })
+            .unzip();
+        if number > 0 {
+            *round += 1;
+        }
+        (winners, losers)
+    }
+
+    /// Appends a match
+    ///
+    /// # Arguments
+    ///
+    /// * `side` - Side the match belongs to
+    /// * `round` - One-based round number within the side
+    /// * `number` - One-based number of the match within its round
+    /// * `entrants` - The two entrants
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Index of the new match
+    fn push(&mut self, side: Side, round: usize, number: usize, entrants: [Entrant; 2]) -> usize {
+        self.matches.push(BracketMatch {
+            side,
+            round,
+            number,
+            entrants,
+            if_necessary: false,
+        });
+        self.matches.len() - 1
+    }
+
+    /// Returns the ID of a match, e.g. `W2-3` for the third match of round 2 of the winners bracket
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the match
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The match ID
+    pub fn match_id(&self, index: usize) -> String {
+        let bracket_match = &self.matches[index];
+        format!(
+            "{}{}-{}",
+            bracket_match.side.prefix(),
+            bracket_match.round,
+            bracket_match.number
+        )
+    }
+
+    /// Returns the text printed next to a match on the poster
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the match
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The match ID, marked when the match is only played if necessary
+    fn match_label(&self, index: usize) -> String {
+        if self.matches[index].if_necessary {
+            format!("{} (if necessary)", self.match_id(index))
+        } else {
+            self.match_id(index)
+        }
+    }
+
+    /// Returns the poster column a match ends in
+    ///
+    /// The final is drawn to the right of the other sides.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the match
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - One-based column of the match
+    fn column(&self, index: usize) -> usize {
+        let bracket_match = &self.matches[index];
+        if bracket_match.side != Side::Final {
+            return bracket_match.round;
+        }
+        let offset = self
+            .matches
+            .iter()
+            .filter(|other| other.side != Side::Final)
+            .map(|other| other.round)
+            .max()
+            .unwrap_or(0);
+        offset + bracket_match.round
+    }
+
+    /// Returns the text printed for an entrant that is not yet known
+    ///
+    /// # Arguments
+    ///
+    /// * `entrant` - Entrant of a match
+    /// * `pairs` - Pairs ordered by seed
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The pair number and names of a seed, or the feeding match otherwise
+    fn entrant_label(&self, entrant: Entrant, pairs: &[Pair]) -> String {
+        match entrant {
+            Entrant::Seed(seed) => {
+                let pair = &pairs[seed];
+                format!(
+                    "({}) {} {}",
+                    seed + 1,
+                    pair.pair_no,
+                    pair.players.join(" / ")
+                )
+            }
+            Entrant::Winner(index) => format!("{}{}", WINNER_OF, self.match_id(index)),
+            Entrant::Loser(index) => format!("{}{}", LOSER_OF, self.match_id(index)),
+        }
+    }
+
+    /// Returns the matches as rows of the bracket match CSV
+    ///
+    /// Entrants decided by an earlier match have a blank pair number and the feeding
+    /// match in place of the first player name, e.g. `Winner of W1-2`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - Pairs ordered by seed
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` - Values in the order of [`crate::model::MATCH_COLUMNS`]
+    pub fn to_records(&self, pairs: &[Pair]) -> Vec<Vec<String>> {
+        self.matches
+            .iter()
+            .enumerate()
+            .map(|(index, bracket_match)| {
+                let pool = if bracket_match.if_necessary {
+                    format!("{} (if necessary)", bracket_match.side.name())
+                } else {
+                    bracket_match.side.name().to_string()
+                };
+                let mut record = vec![self.match_id(index), pool, bracket_match.round.to_string()];
+                for entrant in bracket_match.entrants {
+                    match entrant {
+                        Entrant::Seed(seed) => record.extend(pairs[seed].to_fields()),
+                        _ => {
+                            record.push(String::new());
+                            record.push(self.entrant_label(entrant, pairs));
+                            record.push(String::new());
+                        }
+                    }
+                }
+                record
+            })
+            .collect()
+    }
+
+    /// Draws the bracket as an SVG poster
+    ///
+    /// Each side is drawn as a tree from left to right with one column per round.
+    /// Seeded pairs and entrants coming from another side are written on their own line,
+    /// and the lines of entrants from another side are joined to the match feeding them
+    /// by a dashed connector, such as a winners-bracket loser dropping into the losers
+    /// bracket.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - Pairs ordered by seed
+    /// * `paper` - Paper size of the poster
+    /// * `title` - Title printed at the top of the poster
+    ///
+    /// # Returns
+    ///
+    /// * `String` - SVG of the poster
+    pub fn poster_svg(&self, pairs: &[Pair], paper: Paper, title: &str) -> String {
+        let (width, height) = paper.size();
+
+        let mut sides: Vec<Side> = Vec::new();
+        for bracket_match in &self.matches {
+            if !sides.contains(&bracket_match.side) {
+                sides.push(bracket_match.side);
+            }
+        }
+        let layouts: Vec<(Side, Layout)> = sides
+            .iter()
+            .map(|&side| (side, self.layout(side)))
+            .collect();
+
+        // Every side gets a heading row when there is more than one
+        let heading_rows = if layouts.len() > 1 { 2 } else { 0 };
+        let total_rows: usize = layouts
+            .iter()
+            .map(|(_, layout)| layout.rows + heading_rows)
+            .sum();
+        let columns = (0..self.matches.len())
+            .map(|index| self.column(index))
+            .max()
+            .unwrap_or(1);
+        let row_height = (height - 2.0 * MARGIN - TITLE_HEIGHT) / total_rows.max(1) as f32;
+        let column_width = (width - 2.0 * MARGIN) / (columns as f32 + 0.5);
+        let font_size = (row_height * 0.45).clamp(4.0, 14.0);
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+            w = width,
+            h = height
+        );
+        svg.push_str(&format!(
+            r#"<text x="{}" y="{}" font-size="20" font-weight="bold">{}</text>"#,
+            MARGIN,
+            MARGIN + 20.0,
+            escape_xml(title)
+        ));
+
+        // Where each match ends, and where the lines of entrants from another side start
+        let mut match_points: HashMap<usize, (f32, f32)> = HashMap::new();
+        let mut feed_ins: Vec<(usize, f32, f32)> = Vec::new();
+
+        let mut top = MARGIN + TITLE_HEIGHT;
+        for (side, layout) in &layouts {
+            if heading_rows > 0 {
+                svg.push_str(&format!(
+                    r#"<text x="{}" y="{}" font-size="{}" font-weight="bold">{}</text>"#,
+                    MARGIN,
+                    top + row_height * 1.5,
+                    (row_height * 0.8).min(16.0),
+                    side.name()
+                ));
+                top += row_height * heading_rows as f32;
+            }
+
+            let x_end = |index: usize| MARGIN + self.column(index) as f32 * column_width;
+            let y = |row: f32| top + row * row_height;
+            let leaves: HashMap<(usize, usize), f32> = layout
+                .leaves
+                .iter()
+                .map(|&(index, slot, row)| ((index, slot), row))
+                .collect();
+
+            for (index, bracket_match) in self.matches.iter().enumerate() {
+                if bracket_match.side != *side {
+                    continue;
+                }
+                let x1 = x_end(index);
+                let mut slot_ys = [0.0; 2];
+                for (slot, entrant) in bracket_match.entrants.iter().enumerate() {
+                    let (x0, row) = match leaves.get(&(index, slot)) {
+                        Some(&row) => {
+                            let x0 = x1 - column_width;
+                            let label = self.entrant_label(*entrant, pairs);
+                            // Shrink long names so that they stay within the column
+                            let label_size =
+                                font_size.min(column_width / (label.chars().count() as f32 * 0.55));
+                            svg.push_str(&format!(
+                                r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+                                x0 + 2.0,
+                                y(row) - 3.0,
+                                label_size,
+                                escape_xml(&label)
+                            ));
+                            if let Entrant::Winner(feeder) | Entrant::Loser(feeder) = entrant {
+                                feed_ins.push((*feeder, x0, y(row)));
+                            }
+                            (x0, row)
+                        }
+                        None => {
+                            let Entrant::Winner(feeder) = entrant else {
+                                unreachable!("only winners of the same side are not written out")
+                            };
+                            (x_end(*feeder), layout.rows_of[feeder])
+                        }
+                    };
+                    svg.push_str(&line(x0, y(row), x1, y(row)));
+                    slot_ys[slot] = y(row);
+                }
+                svg.push_str(&line(x1, slot_ys[0], x1, slot_ys[1]));
+                match_points.insert(index, (x1, y(layout.rows_of[&index])));
+                svg.push_str(&format!(
+                    r##"<text x="{}" y="{}" font-size="{}" fill="#555">{}</text>"##,
+                    x1 + 3.0,
+                    y(layout.rows_of[&index]) - 3.0,
+                    font_size * 0.8,
+                    self.match_label(index)
+                ));
+                if layout.roots.contains(&index) {
+                    let row = layout.rows_of[&index];
+                    svg.push_str(&line(x1, y(row), x1 + column_width * 0.5, y(row)));
+                }
+            }
+
+            top += layout.rows as f32 * row_height;
+        }
+
+        for (feeder, x, y) in feed_ins {
+            let Some(&(feeder_x, feeder_y)) = match_points.get(&feeder) else {
+                continue;
+            };
+            svg.push_str(&connector(feeder_x, feeder_y, x, y, column_width * 0.25));
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Places the matches of one side on rows, each match centered between its entrants
+    ///
+    /// # Arguments
+    ///
+    /// * `side` - Side to lay out
+    ///
+    /// # Returns
+    ///
+    /// * `Layout` - Rows of the matches and of the entrant lines
+    fn layout(&self, side: Side) -> Layout {
+        let fed: HashSet<usize> = self
+            .matches
+            .iter()
+            .filter(|bracket_match| bracket_match.side == side)
+            .flat_map(|bracket_match| bracket_match.entrants)
+            .filter_map(|entrant| match entrant {
+                Entrant::Winner(index) if self.matches[index].side == side => Some(index),
+                _ => None,
+            })
+            .collect();
+
+        let mut layout = Layout::default();
+        for (index, bracket_match) in self.matches.iter().enumerate() {
+            if bracket_match.side == side && !fed.contains(&index) {
+                layout.roots.push(index);
+                self.place(index, &mut layout);
+            }
+        }
+        layout
+    }
+
+    /// Places a match and, recursively, the matches feeding it
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the match
+    /// * `layout` - Layout being built
+    ///
+    /// # Returns
+    ///
+    /// * `f32` - Row of the match
+    fn place(&self, index: usize, layout: &mut Layout) -> f32 {
+        let side = self.matches[index].side;
+        let mut rows = [0.0; 2];
+        for (slot, entrant) in self.matches[index].entrants.into_iter().enumerate() {
+            rows[slot] = match entrant {
+                Entrant::Winner(feeder) if self.matches[feeder].side == side => {
+                    self.place(feeder, layout)
+                }
+                _ => {
+                    let row = layout.rows as f32 + 0.5;
+                    layout.rows += 1;
+                    layout.leaves.push((index, slot, row));
+                    row
+                }
+            };
+        }
+        let row = (rows[0] + rows[1]) / 2.0;
+        layout.rows_of.insert(index, row);
+        row
+    }
+}
+
+/// Rows of the matches of one side of a bracket poster
+#[derive(Debug, Default)]
+struct Layout {
+    /// Number of entrant lines, each one row high
+    rows: usize,
+    /// Row of each match, keyed by match index
+    rows_of: HashMap<usize, f32>,
+    /// Entrant lines written out: match index, entrant slot and row
+    leaves: Vec<(usize, usize, f32)>,
+    /// Matches whose winner leaves the side
+    roots: Vec<usize>,
+}
+
+/// Draws a straight black line
+///
+/// # Arguments
+///
+/// * `x1`, `y1` - Start of the line
+/// * `x2`, `y2` - End of the line
+///
+/// # Returns
+///
+/// * `String` - SVG `<line>` element
+fn line(x1: f32, y1: f32, x2: f32, y2: f32) -> String {
+    format!(
+        r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#000" stroke-width="1"/>"##,
+        x1, y1, x2, y2
+    )
+}
+
+/// Draws a dashed curve from the match feeding an entrant to the entrant's line
+///
+/// # Arguments
+///
+/// * `x1`, `y1` - End of the feeding match
+/// * `x2`, `y2` - Start of the entrant's line
+/// * `bend` - Horizontal distance the curve leaves and reaches its ends by
+///
+/// # Returns
+///
+/// * `String` - SVG `<path>` element with a dot at each end
+fn connector(x1: f32, y1: f32, x2: f32, y2: f32, bend: f32) -> String {
+    format!(
+        r##"<path d="M {} {} C {} {} {} {} {} {}" fill="none" stroke="#888" stroke-width="0.75" stroke-dasharray="4 3"/><circle cx="{}" cy="{}" r="1.5" fill="#888"/><circle cx="{}" cy="{}" r="1.5" fill="#888"/>"##,
+        x1,
+        y1,
+        x1 + bend,
+        y1,
+        x2 - bend,
+        y2,
+        x2,
+        y2,
+        x1,
+        y1,
+        x2,
+        y2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Describes the entrants of every match whose ID starts with one of the prefixes
+    ///
+    /// # Arguments
+    ///
+    /// * `bracket` - Bracket to describe
+    /// * `prefixes` - Prefixes of the match IDs to keep, e.g. `["L", "F"]`
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - One `ID: entrant, entrant` line per match, e.g. `L2-1: W L1-1, L W2-1`
+    fn feeders(bracket: &Bracket, prefixes: &[&str]) -> Vec<String> {
+        let entrant = |entrant: &Entrant| match entrant {
+            Entrant::Seed(seed) => format!("S{}", seed + 1),
+            Entrant::Winner(index) => format!("W {}", bracket.match_id(*index)),
+            Entrant::Loser(index) => format!("L {}", bracket.match_id(*index)),
+        };
+        bracket
+            .matches
+            .iter()
+            .enumerate()
+            .map(|(index, bracket_match)| (bracket.match_id(index), bracket_match))
+            .filter(|(id, _)| prefixes.iter().any(|prefix| id.starts_with(prefix)))
+            .map(|(id, bracket_match)| {
+                format!(
+                    "{}: {}, {}",
+                    id,
+                    entrant(&bracket_match.entrants[0]),
+                    entrant(&bracket_match.entrants[1])
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn losers_bracket_feeders() {
+        let cases: [(usize, &[&str]); 3] = [
+            (
+                4,
+                &[
+                    "L1-1: L W1-1, L W1-2",
+                    "L2-1: W L1-1, L W2-1",
+                    "F1-1: W W2-1, W L2-1",
+                ],
+            ),
+            (
+                6,
+                &[
+                    "L1-1: L W1-1, L W2-2",
+                    "L1-2: L W1-2, L W2-1",
+                    "L2-1: W L1-1, W L1-2",
+                    "L3-1: W L2-1, L W3-1",
+                    "F1-1: W W3-1, W L3-1",
+                ],
+            ),
+            (
+                8,
+                &[
+                    "L1-1: L W1-1, L W1-2",
+                    "L1-2: L W1-3, L W1-4",
+                    "L2-1: W L1-1, L W2-2",
+                    "L2-2: W L1-2, L W2-1",
+                    "L3-1: W L2-1, W L2-2",
+                    "L4-1: W L3-1, L W3-1",
+                    "F1-1: W W3-1, W L4-1",
+                ],
+            ),
+        ];
+        for (pair_count, expected) in cases {
+            let bracket = Bracket::new(Format::Double, pair_count, false).unwrap();
+            assert_eq!(
+                feeders(&bracket, &["L", "F"]),
+                expected,
+                "{} pairs",
+                pair_count
+            );
+        }
+    }
+
+    #[test]
+    fn if_necessary_final() {
+        let bracket = Bracket::new(Format::Double, 4, true).unwrap();
+        assert_eq!(
+            feeders(&bracket, &["F"]),
+            ["F1-1: W W2-1, W L2-1", "F2-1: W F1-1, L F1-1"]
+        );
+        let finals: Vec<bool> = bracket
+            .matches
+            .iter()
+            .filter(|bracket_match| bracket_match.side == Side::Final)
+            .map(|bracket_match| bracket_match.if_necessary)
+            .collect();
+        assert_eq!(finals, [false, true]);
+
+        assert!(Bracket::new(Format::Single, 4, true).is_err());
+    }
+
+    #[test]
+    fn consolation_feeders() {
+        let cases: [(usize, &[&str]); 3] = [
+            (4, &["C1-1: L W1-1, L W1-2"]),
+            (6, &["C1-1: L W1-1, L W1-2"]),
+            (
+                8,
+                &[
+                    "C1-1: L W1-1, L W1-2",
+                    "C1-2: L W1-3, L W1-4",
+                    "C2-1: W C1-1, W C1-2",
+                ],
+            ),
+        ];
+        for (pair_count, expected) in cases {
+            let bracket = Bracket::new(Format::Consolation, pair_count, false).unwrap();
+            assert_eq!(feeders(&bracket, &["C"]), expected, "{} pairs", pair_count);
+        }
+    }
+
+    #[test]
+    fn seed_positions_keep_top_seeds_apart() {
+        assert_eq!(seed_positions(8), [1, 8, 4, 5, 2, 7, 3, 6]);
+        assert_eq!(
+            seed_positions(16),
+            [1, 16, 8, 9, 4, 13, 5, 12, 2, 15, 7, 10, 3, 14, 6, 11]
+        );
+    }
+
+    #[test]
+    fn byes_go_to_top_seeds() {
+        let cases: [(usize, &[&str]); 2] = [
+            (
+                5,
+                &[
+                    "W1-1: S4, S5",
+                    "W2-1: S1, W W1-1",
+                    "W2-2: S2, S3",
+                    "W3-1: W W2-1, W W2-2",
+                ],
+            ),
+            (
+                12,
+                &[
+                    "W1-1: S8, S9",
+                    "W1-2: S5, S12",
+                    "W1-3: S7, S10",
+                    "W1-4: S6, S11",
+                    "W2-1: S1, W W1-1",
+                    "W2-2: S4, W W1-2",
+                    "W2-3: S2, W W1-3",
+                    "W2-4: S3, W W1-4",
+                    "W3-1: W W2-1, W W2-2",
+                    "W3-2: W W2-3, W W2-4",
+                    "W4-1: W W3-1, W W3-2",
+                ],
+            ),
+        ];
+        for (pair_count, expected) in cases {
+            let bracket = Bracket::new(Format::Single, pair_count, false).unwrap();
+            assert_eq!(feeders(&bracket, &["W"]), expected, "{} pairs", pair_count);
+        }
+    }
+
+    /// Returns doubles pairs numbered from 1
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Pair>` - The pairs, strongest first
+    fn pairs(count: usize) -> Vec<Pair> {
+        (1..=count)
+            .map(|pair_no| Pair {
+                pair_no: pair_no.to_string(),
+                players: vec![
+                    format!("Player {}a", pair_no),
+                    format!("Player {}b", pair_no),
+                ],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_label_pairs_not_known_yet_and_pass_validation() {
+        let bracket = Bracket::new(Format::Single, 6, false).unwrap();
+        let records = bracket.to_records(&pairs(6));
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(crate::model::MATCH_COLUMNS).unwrap();
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let csv = writer.into_inner().unwrap();
+        let required_columns: Vec<String> = crate::model::PAIR_COLUMNS
+            .iter()
+            .flatten()
+            .map(|column| column.to_string())
+            .collect();
+        let validated = crate::validate::read_rows(
+            &mut csv::Reader::from_reader(csv.as_slice()),
+            &crate::validate::ValidateOptions {
+                required_columns: &required_columns,
+                rename_columns: &[],
+                tolerant: false,
+                is_pending_pair: Some(is_entrant_label),
+            },
+        )
+        .unwrap();
+        assert!(validated.warnings.is_empty());
+
+        // Seeds 1 and 2 have byes and wait for the winners of the first round
+        let final_record = records.last().unwrap();
+        assert_eq!(final_record[0], "W3-1");
+        assert_eq!(
+            final_record[3..],
+            ["", "Winner of W2-1", "", "", "Winner of W2-2", ""]
+        );
+        assert!(
+            records
+                .iter()
+                .any(|record| record[3..]
+                    == ["1", "Player 1a", "Player 1b", "", "Winner of W1-1", ""])
+        );
+    }
+
+    #[test]
+    fn poster_is_valid_svg_with_every_pair() {
+        let bracket = Bracket::new(Format::Single, 5, false).unwrap();
+
+        let svg = bracket.poster_svg(&pairs(5), Paper::A3, "KINTO CUP & Friends");
+        let document = svg2pdf::usvg::roxmltree::Document::parse(&svg).unwrap();
+        let texts: Vec<&str> = document
+            .descendants()
+            .filter_map(|node| node.text())
+            .collect();
+        assert!(texts.contains(&"KINTO CUP & Friends"));
+        for pair_no in 1..=5 {
+            assert!(
+                texts
+                    .iter()
+                    .any(|text| text.contains(&format!("Player {}a", pair_no))),
+                "pair {}",
+                pair_no
+            );
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use super::results::rank_pools;
+use super::{
+    csv_output, load_players, open_matches, read_matches, read_pairs, write_table, FontArgs,
+    TiebreakArgs,
+};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use encoding_rs::Encoding;
+use pickleball_result::advance;
+use pickleball_result::bracket::{self, Bracket, Entrant, Format, Paper};
+use pickleball_result::fonts;
+use pickleball_result::model::{Pair, MATCH_COLUMNS, PAIR_COLUMNS};
+use pickleball_result::pdf::{self, Page};
+use pickleball_result::project::{Project, Stage, Table};
+use pickleball_result::round_robin;
+use pickleball_result::schedule::{self, ScheduleOptions};
+use pickleball_result::timings::Timings;
+use pickleball_result::validate::Validated;
+use std::collections::HashMap;
+
+/// Subcommands of `generate`
+#[derive(Debug, Subcommand)]
+pub enum GenerateCommand {
+    /// Split registered pairs into pools and pair everyone within each pool
+    RoundRobin(RoundRobinArgs),
+    /// Seed pairs into a playoff bracket and draw it as a poster
+    Bracket(BracketArgs),
+    /// Assign a court and a start time to every match of a match CSV
+    Schedule(ScheduleArgs),
+}
+
+/// Arguments of `generate round-robin`
+#[derive(Debug, clap::Args)]
+pub struct RoundRobinArgs {
+    /// Path to the CSV file of registered pairs, with `Pair No`, `Player1` and `Player2` columns, strongest first
+    /// [default: the pairs of --project]
+    #[arg(short, long)]
+    pairs_path: Option<String>,
+    /// Maximum number of pairs in a pool
+    #[arg(long)]
+    pool_size: usize,
+    /// Path for the output match CSV, or `-` for stdout [default: stdout without --project]
+    #[arg(short, long)]
+    output_path: Option<String>,
+}
+
+/// Arguments of `generate bracket`
+#[derive(Debug, clap::Args)]
+pub struct BracketArgs {
+    /// Path to the CSV file of qualified pairs, with `Pair No`, `Player1` and `Player2` columns, strongest first
+    #[arg(short, long)]
+    pairs_path: Option<String>,
+    /// Path to the round-robin match CSV whose top pairs advance, or `-` for stdin
+    /// [default: the pool matches of --project]
+    #[arg(short, long, conflicts_with = "pairs_path", requires = "advance")]
+    matches_path: Option<String>,
+    /// Number of pairs advancing from each pool, ranked by the standings
+    #[arg(long, conflicts_with = "pairs_path")]
+    advance: Option<usize>,
+    /// Path to the JSON file storing the pool results; unused with --project
+    #[arg(long, default_value = "results.json")]
+    store_path: String,
+    #[command(flatten)]
+    tiebreak_args: TiebreakArgs,
+    /// Path for the output bracket match CSV, or `-` for stdout [default: stdout without --project]
+    #[arg(short, long)]
+    output_path: Option<String>,
+    /// Kind of bracket
+    #[arg(long, value_enum, default_value_t = Format::Single)]
+    format: Format,
+    /// End a double elimination with a second final, played if the winner of the losers bracket wins the first one
+    #[arg(long)]
+    if_necessary: bool,
+    /// Path for the PDF of the bracket poster
+    #[arg(long)]
+    poster_path: Option<String>,
+    /// Paper size of the bracket poster
+    #[arg(long, value_enum, default_value_t = Paper::A3)]
+    paper: Paper,
+    /// Title printed at the top of the bracket poster
+    #[arg(short, long, default_value = "Playoff")]
+    tournament_name: String,
+    /// Path to the JSON player registry whose names replace player IDs on the poster
+    /// [default: the players of --project]
+    #[arg(long)]
+    players_path: Option<String>,
+    #[command(flatten)]
+    font_args: FontArgs,
+}
+
+/// Arguments of `generate schedule`
+#[derive(Debug, clap::Args)]
+pub struct ScheduleArgs {
+    /// Path to the match CSV, with `Pair No1` and `Pair No2` columns, or `-` for stdin
+    /// [default: the pool matches of --project]
+    #[arg(short, long)]
+    matches_path: Option<String>,
+    /// Schedule the playoff matches of --project instead of the pool matches
+    #[arg(long, conflicts_with = "matches_path")]
+    playoff: bool,
+    /// Number of courts played on at the same time [default: from --project]
+    #[arg(long)]
+    courts: Option<usize>,
+    /// Length of a match in minutes, including changeover [default: from --project]
+    #[arg(long)]
+    match_duration: Option<u32>,
+    /// Start time of the first matches, as `HH:MM` [default: from --project]
+    #[arg(long, value_parser = schedule::parse_time)]
+    start_time: Option<u32>,
+    /// Minimum rest in minutes between two matches of the same pair [default: from --project, or 0]
+    #[arg(long)]
+    min_rest: Option<u32>,
+    /// Path for the match CSV with `Court` and `Time` columns, or `-` for stdout
+    /// [default: stdout without --project]
+    #[arg(short, long)]
+    output_path: Option<String>,
+    /// Base path of the schedule grids, one PDF per court: `{grid_path}_court{N}.pdf`
+    #[arg(long)]
+    grid_path: Option<String>,
+    /// Title printed above the schedule grid
+    #[arg(short, long, default_value = "Schedule")]
+    tournament_name: String,
+    #[command(flatten)]
+    font_args: FontArgs,
+}
+
+/// Runs a `generate` subcommand
+///
+/// # Arguments
+///
+/// * `command` - Subcommand and its arguments
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the subcommand succeeds, Err otherwise
+pub fn run(
+    command: &GenerateCommand,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    match command {
+        GenerateCommand::RoundRobin(args) => generate_round_robin(args, project, encoding),
+        GenerateCommand::Bracket(args) => generate_bracket(args, project, encoding),
+        GenerateCommand::Schedule(args) => generate_schedule(args, project, encoding),
+    }
+}
+
+/// Generates round-robin matches and writes them as a match CSV
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `generate round-robin`
+/// * `project` - Tournament project whose pairs are used and whose pool matches are replaced, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if generation succeeds, Err otherwise
+pub fn generate_round_robin(
+    args: &RoundRobinArgs,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    if args.pool_size < 2 {
+        anyhow::bail!("Pool size must be at least 2");
+    }
+    let pairs = match (&args.pairs_path, project.as_deref()) {
+        (Some(pairs_path), _) => read_pairs(pairs_path, encoding)?,
+        (None, Some(project)) if !project.store.pairs.is_empty() => project.store.pairs.clone(),
+        (None, Some(_)) => {
+            anyhow::bail!("The project has no pairs yet; give them with --pairs-path")
+        }
+        (None, None) => anyhow::bail!("--pairs-path is required without --project"),
+    };
+    if let Some(project) = project.as_deref() {
+        let played = project
+            .store
+            .pool_matches
+            .rows
+            .iter()
+            .filter(|row| project.store.results.results.contains_key(&row[0]))
+            .count();
+        if played > 0 {
+            anyhow::bail!(
+                "The project already has results for {} pool matches; start a new project to draw the pools again",
+                played
+            );
+        }
+    }
+
+    let mut records = Vec::new();
+    for (pool, pool_pairs) in round_robin::assign_pools(&pairs, args.pool_size) {
+        for round_match in round_robin::round_robin(&pool, &pool_pairs) {
+            records.push(round_match.to_record());
+        }
+    }
+    let table = Table::new(&MATCH_COLUMNS, records);
+    if let Some(output_path) = csv_output(&args.output_path, project.as_deref()) {
+        write_table(output_path, &table)?;
+    }
+
+    if let Some(project) = project {
+        project.store.pairs = pairs;
+        project.set_matches(Stage::Pool, table);
+    }
+
+    Ok(())
+}
+
+/// Seeds the top pairs of each pool into a playoff bracket
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `generate bracket`
+/// * `advance` - Number of pairs advancing from each pool
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<(Vec<Pair>, Bracket)>` - Ok with the pairs ordered by seed and the bracket if successful, Err otherwise
+fn advance_from_pools(
+    args: &BracketArgs,
+    advance: usize,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<(Vec<Pair>, Bracket)> {
+    if advance == 0 {
+        anyhow::bail!("At least one pair must advance from each pool");
+    }
+    let standings = rank_pools(
+        args.matches_path.as_deref(),
+        &args.store_path,
+        project,
+        &args.tiebreak_args,
+        true,
+        encoding,
+    )?;
+    let mut seeds = advance::qualifiers(&standings, advance)?;
+    let bracket = Bracket::new(args.format, seeds.len(), args.if_necessary)?;
+
+    let pairings: Vec<[usize; 2]> = bracket
+        .matches
+        .iter()
+        .filter_map(|bracket_match| match bracket_match.entrants {
+            [Entrant::Seed(a), Entrant::Seed(b)] => Some([a, b]),
+            _ => None,
+        })
+        .collect();
+    for [a, b] in advance::avoid_rematches(&mut seeds, &pairings) {
+        eprintln!(
+            "warning: {} and {} played each other in pool {} and meet again in the first round",
+            seeds[a].label(),
+            seeds[b].label(),
+            seeds[a].pool
+        );
+    }
+    eprintln!(
+        "Seeds: {}",
+        seeds
+            .iter()
+            .enumerate()
+            .map(|(index, seed)| format!("{} {}", index + 1, seed.label()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let pairs = seeds.into_iter().map(|seed| seed.pair).collect();
+    Ok((pairs, bracket))
+}
+
+/// Generates a playoff bracket, writes its matches as a bracket match CSV and draws the poster
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `generate bracket`
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if generation succeeds, Err otherwise
+pub fn generate_bracket(
+    args: &BracketArgs,
+    mut project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let (pairs, bracket) = match (&args.pairs_path, args.advance) {
+        (Some(pairs_path), _) => {
+            let pairs = read_pairs(pairs_path, encoding)?;
+            let bracket = Bracket::new(args.format, pairs.len(), args.if_necessary)?;
+            (pairs, bracket)
+        }
+        (None, Some(advance)) => {
+            advance_from_pools(args, advance, project.as_deref_mut(), encoding)?
+        }
+        (None, None) => {
+            let project = project
+                .as_deref()
+                .context("--pairs-path or --advance is required without --project")?;
+            let pairs = project.store.pairs.clone();
+            if pairs.is_empty() {
+                anyhow::bail!("The project has no pairs; give --pairs-path or --advance");
+            }
+            let bracket = Bracket::new(args.format, pairs.len(), args.if_necessary)?;
+            (pairs, bracket)
+        }
+    };
+
+    let table = Table::new(&MATCH_COLUMNS, bracket.to_records(&pairs));
+    if let Some(output_path) = csv_output(&args.output_path, project.as_deref()) {
+        write_table(output_path, &table)?;
+    }
+    if let Some(project) = project.as_deref_mut() {
+        project.set_matches(Stage::Playoff, table);
+    }
+
+    if let Some(poster_path) = &args.poster_path {
+        let pairs: Vec<Pair> = match load_players(args.players_path.as_deref(), project.as_deref())?
+        {
+            Some(registry) => pairs
+                .iter()
+                .map(|pair| registry.resolve_pair(pair))
+                .collect(),
+            None => pairs,
+        };
+        let font_options = args.font_args.font_options();
+        let options = fonts::load_options(&font_options)?;
+        fonts::check_families(&options.fontdb, &font_options.fallbacks, ["sans-serif"])?;
+        let texts = pairs
+            .iter()
+            .flat_map(|pair| pair.players.iter().chain([&pair.pair_no]))
+            .map(String::as_str)
+            .chain([args.tournament_name.as_str()]);
+        fonts::check_glyphs(
+            &options.fontdb,
+            &font_options.fallbacks,
+            texts.map(|text| ("sans-serif", text)),
+        )?;
+
+        let svg = bracket.poster_svg(&pairs, args.paper, &args.tournament_name);
+        let pdf = pdf::svg_to_pdf(&svg, &options, &Timings::default())?;
+        std::fs::write(poster_path, pdf)
+            .with_context(|| format!("Failed to write {}", poster_path))?;
+        if let Some(project) = project {
+            project.record_document("Bracket poster", poster_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Assigns courts and start times to a match CSV and draws the schedule grid of each court
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `generate schedule`
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if scheduling succeeds, Err otherwise
+pub fn generate_schedule(
+    args: &ScheduleArgs,
+    mut project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let stage = if args.playoff {
+        Stage::Playoff
+    } else {
+        Stage::Pool
+    };
+    let input = open_matches(
+        args.matches_path.as_deref(),
+        project.as_deref(),
+        stage,
+        encoding,
+    )?;
+    let (mut columns, Validated { rows, lines, .. }) = read_matches(input, &[])?;
+    for column in ["Court", "Time"] {
+        if !columns.iter().any(|existing| existing == column) {
+            columns.push(column.to_string());
+        }
+    }
+
+    let field =
+        |row: &HashMap<String, String>, column: &str| row.get(column).cloned().unwrap_or_default();
+    let match_indices: HashMap<&str, usize> = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(index, row)| Some((row.get("Match ID")?.as_str(), index)))
+        .filter(|(match_id, _)| !match_id.is_empty())
+        .collect();
+    let mut matches = Vec::new();
+    for (row, line) in rows.iter().zip(&lines) {
+        let round = match row.get("Round").filter(|round| !round.is_empty()) {
+            Some(round) => round
+                .parse()
+                .with_context(|| format!("line {}: invalid Round `{}`", line, round))?,
+            None => 0,
+        };
+
+        // A pair not yet known is identified by its entrant label, e.g. `Winner of W1-2`
+        let mut pairs = [""; 2];
+        let mut after = Vec::new();
+        for (pair, [pair_no_column, player_column, _]) in pairs.iter_mut().zip(PAIR_COLUMNS) {
+            let player = row.get(player_column).map_or("", String::as_str);
+            *pair = match row[pair_no_column].as_str() {
+                "" if player.is_empty() => {
+                    anyhow::bail!("line {}: blank `{}`", line, pair_no_column)
+                }
+                "" => player,
+                pair_no => pair_no,
+            };
+            if let Some((_, feeder)) = bracket::feeder(player) {
+                let feeder_index = match_indices.get(feeder).with_context(|| {
+                    format!("line {}: unknown match `{}` in `{}`", line, feeder, player)
+                })?;
+                after.push(*feeder_index);
+            }
+        }
+        matches.push(schedule::Pending {
+            pairs,
+            round,
+            after,
+        });
+    }
+
+    let config = project
+        .as_deref()
+        .and_then(|project| project.config.schedule.as_ref());
+    let required = |name: &str| format!("--{} is required without a [schedule] in --project", name);
+    let start_time = match (args.start_time, config) {
+        (Some(start_time), _) => start_time,
+        (None, Some(config)) => schedule::parse_time(&config.start_time)
+            .context("Invalid start_time in the [schedule] of the project")?,
+        (None, None) => anyhow::bail!(required("start-time")),
+    };
+    let options = ScheduleOptions {
+        courts: args
+            .courts
+            .or(config.map(|config| config.courts))
+            .with_context(|| required("courts"))?,
+        start_time,
+        match_duration: args
+            .match_duration
+            .or(config.map(|config| config.match_duration))
+            .with_context(|| required("match-duration"))?,
+        min_rest: args
+            .min_rest
+            .or(config.map(|config| config.min_rest))
+            .unwrap_or(0),
+    };
+    let slots = schedule::schedule(&matches, &options)?;
+
+    let records = rows
+        .iter()
+        .zip(&slots)
+        .map(|(row, slot)| {
+            columns
+                .iter()
+                .map(|column| match column.as_str() {
+                    "Court" => slot.court.to_string(),
+                    "Time" => {
+                        schedule::format_time(schedule::slot_start(&options, slot.time_index))
+                    }
+                    _ => field(row, column),
+                })
+                .collect()
+        })
+        .collect();
+    let table = Table::new(&columns, records);
+    if let Some(output_path) = csv_output(&args.output_path, project.as_deref()) {
+        write_table(output_path, &table)?;
+    }
+
+    if let Some(last) = slots.iter().map(|slot| slot.time_index).max() {
+        eprintln!(
+            "Scheduled {} matches on {} courts, last matches start at {}",
+            slots.len(),
+            options.courts,
+            schedule::format_time(schedule::slot_start(&options, last))
+        );
+    }
+
+    if let Some(grid_path) = &args.grid_path {
+        let labels: Vec<[String; 2]> = rows.iter().map(schedule::grid_label).collect();
+
+        let font_options = args.font_args.font_options();
+        let usvg_options = fonts::load_options(&font_options)?;
+        fonts::check_families(
+            &usvg_options.fontdb,
+            &font_options.fallbacks,
+            ["sans-serif"],
+        )?;
+        let texts = labels
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .chain([args.tournament_name.as_str()]);
+        fonts::check_glyphs(
+            &usvg_options.fontdb,
+            &font_options.fallbacks,
+            texts.map(|text| ("sans-serif", text)),
+        )?;
+
+        let timings = Timings::default();
+        for court in 1..=options.courts {
+            let (titles, pages): (Vec<String>, Vec<Page>) =
+                schedule::grid_pages(&labels, &slots, &options, &args.tournament_name, court)
+                    .into_iter()
+                    .map(|(title, svg)| {
+                        Ok((title, pdf::svg_to_page(&svg, &usvg_options, &timings)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .unzip();
+            let path = format!("{}_court{}.pdf", grid_path, court);
+            std::fs::write(&path, pdf::merge_pages(pages, &titles, true))
+                .with_context(|| format!("Failed to write {}", path))?;
+            if let Some(project) = project.as_deref_mut() {
+                project.record_document("Schedule grid", &path);
+            }
+        }
+    }
+
+    if let (Some(project), None) = (project, &args.matches_path) {
+        project.set_matches(stage, table);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use csv::{ReaderBuilder, Writer};
+use encoding_rs::Encoding;
+use pickleball_result::bracket;
+use pickleball_result::fonts::FontOptions;
+use pickleball_result::input;
+use pickleball_result::model::{Pair, Row};
+use pickleball_result::players::Registry;
+use pickleball_result::project::{Project, Stage, Table};
+use pickleball_result::results::ResultStore;
+use pickleball_result::rules::RulesConfig;
+use pickleball_result::standings::Tiebreak;
+use pickleball_result::validate::{self, ValidateOptions, Validated};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+pub mod generate;
+pub mod players;
+pub mod project;
+pub mod render;
+pub mod results;
+
+/// Tiebreak arguments shared by every command that ranks pools
+#[derive(Debug, clap::Args)]
+pub struct TiebreakArgs {
+    /// Tiebreaks applied in order to pairs level on everything before
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [
+            Tiebreak::MatchWins,
+            Tiebreak::HeadToHead,
+            Tiebreak::GameWinPct,
+            Tiebreak::PointDifferential,
+            Tiebreak::PointsAllowed,
+            Tiebreak::CoinFlip,
+        ]
+    )]
+    tiebreak: Vec<Tiebreak>,
+    /// Largest point differential counted for a single game
+    #[arg(long, default_value_t = 11)]
+    differential_cap: u32,
+}
+
+/// Font arguments shared by every command that renders a PDF
+#[derive(Debug, clap::Args)]
+pub struct FontArgs {
+    /// Font file, or directory scanned recursively for font files, to load (may be repeated)
+    #[arg(long = "font", value_name = "PATH")]
+    fonts: Vec<String>,
+    /// Only use the fonts given with --font or in the manifest
+    #[arg(long)]
+    no_system_fonts: bool,
+    /// Families to try when a font family is missing or lacks a glyph, e.g. `--font-fallback "Inter=Noto Sans JP"`
+    #[arg(long = "font-fallback", value_name = "FAMILY=FALLBACK[,FALLBACK...]", value_parser = parse_font_fallback)]
+    font_fallbacks: Vec<(String, Vec<String>)>,
+}
+
+/// Parses a `COLUMN=FIELD` pair given to `--rename-column`
+///
+/// # Arguments
+///
+/// * `value` - Value given on the command line
+///
+/// # Returns
+///
+/// * `Result<(String, String)>` - Ok with the column and field names if successful, Err otherwise
+pub fn parse_rename(value: &str) -> Result<(String, String)> {
+    let (column, field) = value.split_once('=').context("Expected COLUMN=FIELD")?;
+
+    Ok((column.trim().to_string(), field.trim().to_string()))
+}
+
+/// Parses a `FAMILY=FALLBACK[,FALLBACK...]` value given to `--font-fallback`
+///
+/// # Arguments
+///
+/// * `value` - Value given on the command line
+///
+/// # Returns
+///
+/// * `Result<(String, Vec<String>)>` - Ok with the family and its fallbacks if successful, Err otherwise
+pub fn parse_font_fallback(value: &str) -> Result<(String, Vec<String>)> {
+    let (family, fallbacks) = value
+        .split_once('=')
+        .context("Expected FAMILY=FALLBACK[,FALLBACK...]")?;
+    let fallbacks = fallbacks
+        .split(',')
+        .map(|fallback| fallback.trim().to_string())
+        .filter(|fallback| !fallback.is_empty())
+        .collect();
+
+    Ok((family.trim().to_string(), fallbacks))
+}
+
+impl FontArgs {
+    /// Returns the fonts given on the command line
+    ///
+    /// # Returns
+    ///
+    /// * `FontOptions` - Fonts to load and family fallbacks
+    pub fn font_options(&self) -> FontOptions {
+        FontOptions {
+            system_fonts: !self.no_system_fonts,
+            font_paths: self.fonts.clone(),
+            fallbacks: self.font_fallbacks.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Opens a file for reading, or stdin for `-`, decoded to UTF-8
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<Box<dyn Read>>` - Ok with the reader if successful, Err otherwise
+pub fn open_input(path: &str, encoding: Option<&'static Encoding>) -> Result<Box<dyn Read>> {
+    input::open(path, encoding)
+}
+
+/// Creates a file for writing, or stdout for `-`
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+///
+/// # Returns
+///
+/// * `Result<Box<dyn Write>>` - Ok with the writer if successful, Err otherwise
+pub fn create_output(path: &str) -> Result<Box<dyn Write>> {
+    if path == "-" {
+        return Ok(Box::new(std::io::stdout().lock()));
+    }
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+
+    Ok(Box::new(file))
+}
+
+/// Opens a match CSV given on the command line, or the stored matches of the project
+///
+/// # Arguments
+///
+/// * `path` - Path to the match CSV, or `-` for stdin
+/// * `project` - Tournament project used when no path is given
+/// * `stage` - Stage whose stored matches are read
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<Box<dyn Read>>` - Ok with the reader if successful, Err if neither a path nor a project is given
+pub fn open_matches(
+    path: Option<&str>,
+    project: Option<&Project>,
+    stage: Stage,
+    encoding: Option<&'static Encoding>,
+) -> Result<Box<dyn Read>> {
+    match (path, project) {
+        (Some(path), _) => open_input(path, encoding).context("Failed to open match CSV"),
+        (None, Some(project)) => Ok(Box::new(Cursor::new(project.matches(stage)?.to_csv()?))),
+        (None, None) => anyhow::bail!("--matches-path is required without --project"),
+    }
+}
+
+/// Returns where a CSV is written: the given path, or stdout without a project
+///
+/// With a project, the CSV is stored in the project and only written when a path is given.
+///
+/// # Arguments
+///
+/// * `path` - Path given on the command line
+/// * `project` - Tournament project, if any
+///
+/// # Returns
+///
+/// * `Option<&str>` - Path to write to, `-` for stdout, or None
+pub fn csv_output<'a>(path: &'a Option<String>, project: Option<&Project>) -> Option<&'a str> {
+    path.as_deref().or(project.is_none().then_some("-"))
+}
+
+/// Writes a CSV file
+///
+/// # Arguments
+///
+/// * `path` - Path of the file, or `-` for stdout
+/// * `table` - Header and rows to write
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the file was written, Err otherwise
+pub fn write_table(path: &str, table: &Table) -> Result<()> {
+    let mut writer = Writer::from_writer(create_output(path)?);
+    writer.write_record(&table.columns)?;
+    for row in &table.rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Loads the scoring rules given on the command line, or the rules of the project
+///
+/// # Arguments
+///
+/// * `rules_path` - Path to the TOML rules file
+/// * `project` - Tournament project used when no path is given
+///
+/// # Returns
+///
+/// * `Result<RulesConfig>` - Ok with the rules if successful, Err otherwise
+pub fn load_rules(rules_path: Option<&str>, project: Option<&Project>) -> Result<RulesConfig> {
+    match (rules_path, project) {
+        (None, Some(project)) => Ok(project.config.rules.clone()),
+        (rules_path, _) => RulesConfig::load(rules_path),
+    }
+}
+
+/// Builds a table from rows keyed by column name
+///
+/// # Arguments
+///
+/// * `columns` - Header of the table
+/// * `rows` - Rows keyed by column name; missing values are blank
+///
+/// # Returns
+///
+/// * `Table` - The table
+pub fn to_table(columns: &[String], rows: &[Row]) -> Table {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| row.get(column).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Table::new(columns, rows)
+}
+
+/// Loads the results of the project, or the result store at `store_path` without a project
+///
+/// # Arguments
+///
+/// * `project` - Tournament project, if any
+/// * `store_path` - Path to the JSON result store
+///
+/// # Returns
+///
+/// * `Result<ResultStore>` - Ok with the results if successful, Err otherwise
+pub fn load_results(project: Option<&Project>, store_path: &str) -> Result<ResultStore> {
+    match project {
+        Some(project) => Ok(project.store.results.clone()),
+        None => ResultStore::load(store_path),
+    }
+}
+
+/// Saves results to the project, or to the result store at `store_path` without a project
+///
+/// # Arguments
+///
+/// * `project` - Tournament project, if any
+/// * `store` - Results to save
+/// * `store_path` - Path to the JSON result store
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the results were saved, Err otherwise
+pub fn save_results(
+    project: Option<&mut Project>,
+    store: ResultStore,
+    store_path: &str,
+) -> Result<()> {
+    match project {
+        Some(project) => {
+            project.store.results = store;
+            Ok(())
+        }
+        None => store.save(store_path),
+    }
+}
+
+/// Loads the player registry given on the command line, or the players of the project
+///
+/// # Arguments
+///
+/// * `players_path` - Path to the JSON player registry
+/// * `project` - Tournament project used when no path is given
+///
+/// # Returns
+///
+/// * `Result<Option<Registry>>` - Ok with the registry, or None without a path or a project, Err if it cannot be read
+pub fn load_players(
+    players_path: Option<&str>,
+    project: Option<&Project>,
+) -> Result<Option<Registry>> {
+    match (players_path, project) {
+        (Some(players_path), _) => {
+            if !Path::new(players_path).exists() {
+                anyhow::bail!("Player registry {} does not exist", players_path);
+            }
+            Registry::load(players_path).map(Some)
+        }
+        (None, Some(project)) => Ok(Some(project.store.players.clone())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Loads the players of the project, or the registry at `store_path` without a project
+///
+/// # Arguments
+///
+/// * `project` - Tournament project, if any
+/// * `store_path` - Path to the JSON player registry
+///
+/// # Returns
+///
+/// * `Result<Registry>` - Ok with the registry if successful, Err otherwise
+pub fn load_registry(project: Option<&Project>, store_path: &str) -> Result<Registry> {
+    match project {
+        Some(project) => Ok(project.store.players.clone()),
+        None => Registry::load(store_path),
+    }
+}
+
+/// Saves players to the project, or to the registry at `store_path` without a project
+///
+/// # Arguments
+///
+/// * `project` - Tournament project, if any
+/// * `registry` - Players to save
+/// * `store_path` - Path to the JSON player registry
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the players were saved, Err otherwise
+pub fn save_registry(
+    project: Option<&mut Project>,
+    registry: Registry,
+    store_path: &str,
+) -> Result<()> {
+    match project {
+        Some(project) => {
+            project.store.players = registry;
+            Ok(())
+        }
+        None => registry.save(store_path),
+    }
+}
+
+/// Prints the problems that were tolerated while reading a CSV file
+///
+/// # Arguments
+///
+/// * `warnings` - Warnings returned with the rows, each starting with its line number
+pub fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+/// Reads registered pairs from a CSV file
+///
+/// # Arguments
+///
+/// * `path` - Path to the CSV file, with `Pair No`, `Player1` and `Player2` columns
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<Vec<Pair>>` - Ok with the pairs in file order if successful, Err otherwise
+pub fn read_pairs(path: &str, encoding: Option<&'static Encoding>) -> Result<Vec<Pair>> {
+    let file = open_input(path, encoding)?;
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(file);
+    let validated = validate::read_rows(
+        &mut reader,
+        &ValidateOptions {
+            required_columns: &["Pair No".to_string(), "Player1".to_string()],
+            rename_columns: &[],
+            tolerant: false,
+            is_pending_pair: None,
+        },
+    )?;
+    print_warnings(&validated.warnings);
+
+    let pairs: Vec<Pair> = validated.rows.iter().map(Pair::from_row).collect();
+    let mut pair_nos = HashSet::new();
+    for pair in &pairs {
+        if !pair_nos.insert(&pair.pair_no) {
+            anyhow::bail!("Duplicate Pair No `{}` in {}", pair.pair_no, path);
+        }
+    }
+
+    Ok(pairs)
+}
+
+/// Reads a match CSV
+///
+/// Pair numbers of bracket matches are blank until the feeding match is played, so the
+/// pair columns are only required in the header.
+///
+/// # Arguments
+///
+/// * `input` - Match CSV, as opened by [`open_matches`]
+/// * `required_columns` - Columns whose values may not be blank, besides the pair columns required in the header
+///
+/// # Returns
+///
+/// * `Result<(Vec<String>, Validated)>` - Ok with the header and the rows with their lines if successful, Err otherwise
+pub fn read_matches(
+    input: Box<dyn Read>,
+    required_columns: &[&str],
+) -> Result<(Vec<String>, Validated)> {
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(input);
+    let columns: Vec<String> = reader
+        .headers()
+        .context("Failed to read CSV header")?
+        .iter()
+        .map(|column| column.trim().to_string())
+        .collect();
+    let missing: Vec<&str> = ["Pair No1", "Pair No2"]
+        .into_iter()
+        .filter(|column| !columns.iter().any(|existing| existing == column))
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Invalid CSV file:\nline 1: header is missing required columns: {}",
+            missing.join(", ")
+        );
+    }
+    let required_columns: Vec<String> = required_columns
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+    let validated = validate::read_rows(
+        &mut reader,
+        &ValidateOptions {
+            required_columns: &required_columns,
+            rename_columns: &[],
+            tolerant: false,
+            is_pending_pair: Some(bracket::is_entrant_label),
+        },
+    )?;
+    print_warnings(&validated.warnings);
+
+    Ok((columns, validated))
+}
@@ -0,0 +1,244 @@
+use super::{load_registry, open_input, save_registry, write_table};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use csv::ReaderBuilder;
+use encoding_rs::Encoding;
+use pickleball_result::players::{self, Player};
+use pickleball_result::project::{self, Project, Table};
+use pickleball_result::validate;
+
+/// Subcommands of `players`
+#[derive(Debug, Subcommand)]
+pub enum PlayersCommand {
+    /// Register the players of a pair or match CSV and report likely duplicates
+    Import(PlayersImportArgs),
+    /// Print the registered players as CSV
+    List(PlayersListArgs),
+    /// Merge a duplicate player into another, keeping its ID as an alias
+    Merge(MergeArgs),
+}
+
+/// Arguments of `players import`
+#[derive(Debug, clap::Args)]
+pub struct PlayersImportArgs {
+    /// Path to a CSV with `Player1`, `Player2`, ... columns, or `-` for stdin
+    /// [default: the pairs of --project]
+    #[arg(short, long)]
+    csv_path: Option<String>,
+    /// Path to the JSON file storing the players; unused with --project
+    #[arg(long, default_value = "players.json")]
+    store_path: String,
+    /// Path for the CSV with the player names replaced by their IDs, or `-` for stdout
+    #[arg(short, long)]
+    output_path: Option<String>,
+}
+
+/// Arguments of `players list`
+#[derive(Debug, clap::Args)]
+pub struct PlayersListArgs {
+    /// Path to the JSON file storing the players; unused with --project
+    #[arg(long, default_value = "players.json")]
+    store_path: String,
+    /// List the players in gojūon order of their readings instead of registration order
+    #[arg(long)]
+    sort_by_reading: bool,
+}
+
+/// Arguments of `players merge`
+#[derive(Debug, clap::Args)]
+pub struct MergeArgs {
+    /// ID of the player kept
+    keep: String,
+    /// ID of the duplicate merged into it
+    merge: String,
+    /// Path to the JSON file storing the players; unused with --project
+    #[arg(long, default_value = "players.json")]
+    store_path: String,
+}
+
+/// Runs a `players` subcommand
+///
+/// # Arguments
+///
+/// * `command` - Subcommand and its arguments
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the subcommand succeeds, Err otherwise
+pub fn run(
+    command: &PlayersCommand,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    match command {
+        PlayersCommand::Import(args) => import_players(args, project, encoding),
+        PlayersCommand::List(args) => list_players(args, project.as_deref()),
+        PlayersCommand::Merge(args) => merge_players(args, project),
+    }
+}
+
+/// Registers the players of a CSV, or of the pairs of the project, and reports likely duplicates
+///
+/// Names are normalized before they are registered, so spellings differing only in
+/// full-width or half-width characters get the same ID. With a project and no CSV,
+/// the players of the stored pairs are replaced by their IDs.
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `players import`
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the players were registered, Err otherwise
+pub fn import_players(
+    args: &PlayersImportArgs,
+    mut project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let mut registry = load_registry(project.as_deref(), &args.store_path)?;
+    let before = registry.players.len();
+
+    let table = match (&args.csv_path, project.as_deref_mut()) {
+        (Some(csv_path), _) => {
+            let file = open_input(csv_path, encoding).context("Failed to open CSV file")?;
+            let mut reader = ReaderBuilder::new().flexible(true).from_reader(file);
+            let columns: Vec<String> = reader
+                .headers()
+                .context("Failed to read CSV header")?
+                .iter()
+                .map(|column| column.trim().to_string())
+                .collect();
+            let player_indices: Vec<usize> = (0..columns.len())
+                .filter(|&index| validate::is_player_column(&columns[index]))
+                .collect();
+            let reading_indices: Vec<Option<usize>> = player_indices
+                .iter()
+                .map(|&index| {
+                    let reading_column = players::reading_column(&columns[index]);
+                    columns.iter().position(|column| *column == reading_column)
+                })
+                .collect();
+            if player_indices.is_empty() {
+                anyhow::bail!("{} has no Player1, Player2, ... columns", csv_path);
+            }
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                let mut row: Vec<String> = record?.iter().map(str::to_string).collect();
+                for (&index, reading_index) in player_indices.iter().zip(&reading_indices) {
+                    let reading = reading_index
+                        .and_then(|reading_index| row.get(reading_index))
+                        .filter(|reading| !reading.trim().is_empty())
+                        .cloned();
+                    if let Some(cell) = row.get_mut(index).filter(|cell| !cell.trim().is_empty()) {
+                        let (id, _) = registry.register(cell);
+                        if let Some(reading) = reading {
+                            registry.set_reading(&id, &reading)?;
+                        }
+                        *cell = id;
+                    }
+                }
+                rows.push(row);
+            }
+            Table::new(&columns, rows)
+        }
+        (None, Some(project)) => {
+            for pair in &mut project.store.pairs {
+                for player in &mut pair.players {
+                    *player = registry.register(player).0;
+                }
+            }
+            let rows = project
+                .store
+                .pairs
+                .iter()
+                .map(|pair| pair.to_fields().to_vec())
+                .collect();
+            Table::new(&["Pair No", "Player1", "Player2"], rows)
+        }
+        (None, None) => anyhow::bail!("--csv-path is required without --project"),
+    };
+    if let Some(output_path) = &args.output_path {
+        write_table(output_path, &table)?;
+    }
+
+    let duplicates: Vec<String> = registry
+        .likely_duplicates()
+        .iter()
+        .map(|[first, second]| {
+            format!(
+                "{} {} / {} {}",
+                first.id, first.name, second.id, second.name
+            )
+        })
+        .collect();
+    eprintln!(
+        "Registered {} new players; {} players in {}",
+        registry.players.len() - before,
+        registry.players.len(),
+        match project.as_deref() {
+            Some(project) => project.path(project::STORE_FILE),
+            None => args.store_path.clone(),
+        }
+    );
+    if !duplicates.is_empty() {
+        eprintln!(
+            "Likely duplicates; merge them with `players merge KEEP MERGE`:\n{}",
+            duplicates.join("\n")
+        );
+    }
+    save_registry(project, registry, &args.store_path)
+}
+
+/// Prints the registered players as CSV
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `players list`
+/// * `project` - Tournament project, if any
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the players were printed, Err otherwise
+pub fn list_players(args: &PlayersListArgs, project: Option<&Project>) -> Result<()> {
+    let registry = load_registry(project, &args.store_path)?;
+    let mut players: Vec<&Player> = registry.players.iter().collect();
+    if args.sort_by_reading {
+        players.sort_by_cached_key(|player| registry.sort_key(&player.id));
+    }
+    let rows = players
+        .into_iter()
+        .map(|player| {
+            vec![
+                player.id.clone(),
+                player.name.clone(),
+                player.reading.clone().unwrap_or_default(),
+                player.aliases.join(" / "),
+            ]
+        })
+        .collect();
+    write_table(
+        "-",
+        &Table::new(&["ID", "Name", "Reading", "Aliases"], rows),
+    )
+}
+
+/// Merges a duplicate player into another
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `players merge`
+/// * `project` - Tournament project, if any
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the players were merged, Err otherwise
+pub fn merge_players(args: &MergeArgs, project: Option<&mut Project>) -> Result<()> {
+    let mut registry = load_registry(project.as_deref(), &args.store_path)?;
+    registry.merge(&args.keep, &args.merge)?;
+    eprintln!("Merged {} into {}", args.merge, args.keep);
+    save_registry(project, registry, &args.store_path)
+}
@@ -0,0 +1,120 @@
+use super::read_pairs;
+use anyhow::Result;
+use clap::Subcommand;
+use encoding_rs::Encoding;
+use pickleball_result::project::{self, Project, Stage};
+
+/// Subcommands of `project`
+#[derive(Debug, Subcommand)]
+pub enum ProjectCommand {
+    /// Create a project directory with `tournament.toml` and an empty store
+    Init(InitArgs),
+    /// Print what a project holds and what is left to play
+    Status(StatusArgs),
+}
+
+/// Arguments of `project init`
+#[derive(Debug, clap::Args)]
+pub struct InitArgs {
+    /// Directory of the project; created if missing
+    dir: String,
+    /// Name of the tournament
+    #[arg(short, long)]
+    tournament_name: String,
+    /// SVG template of the score sheets, copied into the project with its manifest
+    #[arg(short, long)]
+    svg_path: Option<String>,
+    /// Path to the CSV file of registered pairs, with `Pair No`, `Player1` and `Player2` columns, strongest first
+    #[arg(short, long)]
+    pairs_path: Option<String>,
+}
+
+/// Arguments of `project status`
+#[derive(Debug, clap::Args)]
+pub struct StatusArgs {
+    /// Directory of the project
+    dir: String,
+}
+
+/// Runs a `project` subcommand
+///
+/// # Arguments
+///
+/// * `command` - Subcommand and its arguments
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the subcommand succeeds, Err otherwise
+pub fn run(command: &ProjectCommand, encoding: Option<&'static Encoding>) -> Result<()> {
+    match command {
+        ProjectCommand::Init(args) => init_project(args, encoding),
+        ProjectCommand::Status(args) => project_status(args),
+    }
+}
+
+/// Creates a tournament project, optionally registering its pairs
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `project init`
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the project was created, Err otherwise
+pub fn init_project(args: &InitArgs, encoding: Option<&'static Encoding>) -> Result<()> {
+    let mut project = Project::init(&args.dir, &args.tournament_name, args.svg_path.as_deref())?;
+    if let Some(pairs_path) = &args.pairs_path {
+        project.store.pairs = read_pairs(pairs_path, encoding)?;
+        project.save()?;
+    }
+    eprintln!(
+        "Created {} with {} pairs",
+        project.path(project::CONFIG_FILE),
+        project.store.pairs.len()
+    );
+
+    Ok(())
+}
+
+/// Prints what a tournament project holds and how many matches are left to play
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `project status`
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the project was read, Err otherwise
+pub fn project_status(args: &StatusArgs) -> Result<()> {
+    let project = Project::open(&args.dir)?;
+    let results = &project.store.results;
+    println!("{}", project.config.name);
+    println!("Pairs: {}", project.store.pairs.len());
+    for (stage, label) in [(Stage::Pool, "Pool"), (Stage::Playoff, "Playoff")] {
+        let Ok(table) = project.matches(stage) else {
+            println!("{} matches: not generated", label);
+            continue;
+        };
+        let played = table
+            .rows
+            .iter()
+            .filter(|row| results.results.contains_key(&row[0]))
+            .count();
+        println!(
+            "{} matches: {} of {} played",
+            label,
+            played,
+            table.rows.len()
+        );
+    }
+    for (tie, order) in &results.coin_flips {
+        println!("Coin flip {}: {}", tie, order.join(" > "));
+    }
+    for document in &project.store.documents {
+        println!("{}: {}", document.kind, document.path);
+    }
+
+    Ok(())
+}
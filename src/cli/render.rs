@@ -0,0 +1,230 @@
+use super::{
+    create_output, load_players, load_rules, open_input, parse_rename, print_warnings, FontArgs,
+};
+use anyhow::{Context, Result};
+use csv::ReaderBuilder;
+use encoding_rs::Encoding;
+use pickleball_result::project::{Project, Stage};
+use pickleball_result::raster::{ImageFormat, RasterOptions, BASE_DPI};
+use pickleball_result::render::{DataOptions, Job, ScoreSheet};
+use pickleball_result::validate::Validated;
+use std::io::Cursor;
+
+/// Command line arguments structure
+#[derive(Debug, clap::Args)]
+// clap leaves the group of a struct with a flattened field empty, which would make
+// `Cli::args` always None, so every argument is listed explicitly
+#[group(
+    args = [
+        "csv_path", "playoff", "svg_path", "manifest_path", "tournament_name", "output_path",
+        "merge", "page_numbers", "image", "dpi", "scale", "per_card", "rename_columns",
+        "tolerant", "validate_only", "timings", "rules_path", "players_path", "fonts",
+        "no_system_fonts", "font_fallbacks",
+    ],
+    multiple = true
+)]
+pub struct Args {
+    /// Path to the CSV file containing player names, or `-` for stdin; `.xlsx`/`.ods` files are
+    /// read from their first sheet, or from the one named after `#` (e.g. `entries.xlsx#Day 1`),
+    /// and `.json`/`.yaml` files as documents of matches (see schema/input.schema.json)
+    /// [default: the pool matches of --project]
+    #[arg(short, long)]
+    csv_path: Option<String>,
+    /// Render the playoff matches of --project instead of the pool matches
+    #[arg(long, conflicts_with = "csv_path")]
+    playoff: bool,
+    /// Path to the master SVG template file [default: the template of --project]
+    #[arg(short, long)]
+    svg_path: Option<String>,
+    /// Path to the TOML manifest describing the card layout of the template
+    /// [default: the SVG path with a `.toml` extension, if it exists]
+    #[arg(long)]
+    manifest_path: Option<String>,
+    /// Name of the tournament [default: the name of --project]
+    #[arg(short, long)]
+    tournament_name: Option<String>,
+    /// Path for the output PDF files, or `-` to write every page as a single PDF to stdout
+    /// [default: `score-sheets` or `playoff-sheets` in --project]
+    #[arg(short, long)]
+    output_path: Option<String>,
+    /// Merge all pages into a single PDF written to `{output_path}.pdf`
+    #[arg(short, long)]
+    merge: bool,
+    /// Print page numbers in the footer of the merged PDF; needs --merge or `-o -`
+    #[arg(long)]
+    page_numbers: bool,
+    /// Write images in this format instead of PDFs, to `{output_path}_{page}.png` (or `.jpg`)
+    #[arg(long, value_enum, conflicts_with = "merge")]
+    image: Option<ImageFormat>,
+    /// Resolution of the images; 96 draws one pixel per SVG unit [default: 96]
+    #[arg(long, requires = "image", conflicts_with = "scale")]
+    dpi: Option<f32>,
+    /// Pixels per SVG unit of the images, e.g. 2 for twice the size of the template
+    #[arg(long, requires = "image")]
+    scale: Option<f32>,
+    /// Write each card as its own image, cropped to the card, to `{output_path}_{page}_{card}.png`
+    #[arg(long, requires = "image")]
+    per_card: bool,
+    /// Rename a CSV column to the template field it fills, e.g. `--rename-column "Court No=Court"`
+    #[arg(long = "rename-column", value_name = "COLUMN=FIELD", value_parser = parse_rename)]
+    rename_columns: Vec<(String, String)>,
+    /// Pad rows with missing fields with blanks, and only warn about blank names
+    #[arg(long)]
+    tolerant: bool,
+    /// Validate the CSV file against the template without writing any PDF
+    #[arg(long)]
+    validate_only: bool,
+    /// Print the time spent in each stage to stderr
+    #[arg(long)]
+    timings: bool,
+    /// Path to the TOML file with the scoring rules of each division, printed in the `{{Rules}}` field
+    /// [default: the rules of --project, or best of 3 games to 11, win by 2]
+    #[arg(long)]
+    rules_path: Option<String>,
+    /// Path to the JSON player registry whose names replace player IDs [default: the players of --project]
+    #[arg(long)]
+    players_path: Option<String>,
+    #[command(flatten)]
+    font_args: FontArgs,
+}
+
+/// Renders the score sheets of a match CSV, or of the matches of the project
+///
+/// # Arguments
+///
+/// * `args` - Command line arguments
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if processing succeeds, Err otherwise
+pub fn process(
+    args: &Args,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let stage = if args.playoff {
+        Stage::Playoff
+    } else {
+        Stage::Pool
+    };
+    let file = match (&args.csv_path, project.as_deref()) {
+        (Some(csv_path), _) => open_input(csv_path, encoding).context("Failed to open CSV file")?,
+        (None, Some(project)) => Box::new(Cursor::new(project.matches(stage)?.to_csv()?)),
+        (None, None) => anyhow::bail!("--csv-path is required without --project"),
+    };
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(file);
+
+    let svg_path = match (&args.svg_path, project.as_deref()) {
+        (Some(svg_path), _) => svg_path.clone(),
+        (None, Some(project)) => project.path(project.config.template.as_deref().context(
+            "The project has no template; set `template` in tournament.toml or give --svg-path",
+        )?),
+        (None, None) => anyhow::bail!("--svg-path is required without --project"),
+    };
+    let tournament_name = match (&args.tournament_name, project.as_deref()) {
+        (Some(tournament_name), _) => tournament_name.clone(),
+        (None, Some(project)) => project.config.name.clone(),
+        (None, None) => anyhow::bail!("--tournament-name is required without --project"),
+    };
+    let output_path = match (&args.output_path, project.as_deref()) {
+        (Some(output_path), _) => output_path.clone(),
+        (None, Some(project)) => project.path(match stage {
+            Stage::Pool => "score-sheets",
+            Stage::Playoff => "playoff-sheets",
+        }),
+        (None, None) => anyhow::bail!("--output-path is required without --project"),
+    };
+    let image = args
+        .image
+        .map(|format| {
+            let scale = match (args.dpi, args.scale) {
+                (Some(dpi), _) => dpi / BASE_DPI,
+                (None, Some(scale)) => scale,
+                (None, None) => 1.0,
+            };
+            if scale <= 0.0 || !scale.is_finite() {
+                anyhow::bail!("--dpi and --scale must be positive");
+            }
+            Ok(RasterOptions {
+                format,
+                scale,
+                per_card: args.per_card,
+            })
+        })
+        .transpose()?;
+    if image.is_some() && output_path == "-" {
+        anyhow::bail!("Images are written to files; give --output-path a path instead of `-`");
+    }
+    // Writing to stdout always merges the pages
+    if args.page_numbers && !args.merge && output_path != "-" {
+        anyhow::bail!("--page-numbers needs a merged PDF; give --merge or `--output-path -`");
+    }
+    let rules = load_rules(args.rules_path.as_deref(), project.as_deref())?;
+    let players = load_players(args.players_path.as_deref(), project.as_deref())?;
+
+    let sheet = ScoreSheet::load(
+        &svg_path,
+        args.manifest_path.as_deref(),
+        &args.font_args.font_options(),
+    )?;
+    sheet.timings().measure("total", || {
+        let Validated { rows, warnings, .. } = sheet.read_rows(
+            &mut reader,
+            &DataOptions {
+                rename_columns: &args.rename_columns,
+                tolerant: args.tolerant,
+                rules: &rules,
+                players: players.as_ref(),
+            },
+        )?;
+        print_warnings(&warnings);
+        sheet.check_glyphs(&rows, &tournament_name)?;
+        if args.validate_only {
+            return Ok(());
+        }
+
+        if output_path == "-" {
+            return sheet.write_merged(
+                &rows,
+                &tournament_name,
+                args.page_numbers,
+                create_output(&output_path)?,
+            );
+        }
+
+        sheet.render_job(&Job {
+            rows: &rows,
+            tournament_name: &tournament_name,
+            output_path: &output_path,
+            merge: args.merge,
+            page_numbers: args.page_numbers,
+            image,
+        })
+    })?;
+
+    let squeezed = sheet.fitter().squeezed();
+    if !squeezed.is_empty() {
+        eprintln!(
+            "warning: these names were squeezed below {}pt to fit:\n{}",
+            sheet.fitter().min_font_size(),
+            squeezed.join("\n")
+        );
+    }
+
+    if args.timings {
+        sheet.timings().report();
+    }
+
+    if let (Some(project), false, false) = (project, args.validate_only, output_path == "-") {
+        let written = match (&image, args.merge) {
+            (Some(image), _) => format!("{}_*.{}", output_path, image.format.extension()),
+            (None, true) => format!("{}.pdf", output_path),
+            (None, false) => format!("{}_*.pdf", output_path),
+        };
+        project.record_document("Score sheets", &written);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,518 @@
+use super::{
+    create_output, csv_output, load_players, load_results, load_rules, open_input, open_matches,
+    print_warnings, read_matches, save_results, to_table, write_table, FontArgs, TiebreakArgs,
+};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use csv::{ReaderBuilder, Writer};
+use encoding_rs::Encoding;
+use pickleball_result::fonts;
+use pickleball_result::model::{Pair, Row, PAIR_COLUMNS};
+use pickleball_result::pdf::{self, Page};
+use pickleball_result::project::{self, Project, Stage};
+use pickleball_result::results;
+use pickleball_result::rules::DIVISION_COLUMN;
+use pickleball_result::standings::{self, Standing, StandingsOptions, STANDING_COLUMNS};
+use pickleball_result::timings::Timings;
+use pickleball_result::validate::{self, ValidateOptions, Validated};
+use std::collections::HashMap;
+
+/// Subcommands of `results`
+#[derive(Debug, Subcommand)]
+pub enum ResultsCommand {
+    /// Validate the game scores of a results CSV and add them to the result store
+    Import(ImportArgs),
+    /// Fill the pairs decided by stored results into a bracket match CSV
+    Advance(AdvanceArgs),
+    /// Rank the pairs of each pool from the stored results
+    Standings(StandingsArgs),
+}
+
+/// Arguments of `results import`
+#[derive(Debug, clap::Args)]
+pub struct ImportArgs {
+    /// Path to the results CSV, with `Match ID` and `Scores` columns (e.g. `11-7, 9-11, 11-5`), or `-` for stdin
+    #[arg(short, long)]
+    results_path: String,
+    /// Path to the match CSV the results belong to
+    /// [default: the pool and playoff matches of --project]
+    #[arg(short, long)]
+    matches_path: Option<String>,
+    /// Path to the JSON file storing the results; unused with --project
+    #[arg(long, default_value = "results.json")]
+    store_path: String,
+    /// Path to the TOML file with the scoring rules of each division
+    /// [default: the rules of --project, or best of 3 games to 11, win by 2]
+    #[arg(long)]
+    rules_path: Option<String>,
+}
+
+/// Arguments of `results advance`
+#[derive(Debug, clap::Args)]
+pub struct AdvanceArgs {
+    /// Path to the bracket match CSV, or `-` for stdin [default: the playoff matches of --project]
+    #[arg(short, long)]
+    matches_path: Option<String>,
+    /// Path to the JSON file storing the results; unused with --project
+    #[arg(long, default_value = "results.json")]
+    store_path: String,
+    /// Path for the updated match CSV, or `-` for stdout [default: stdout without --project]
+    #[arg(short, long)]
+    output_path: Option<String>,
+}
+
+/// Arguments of `results standings`
+#[derive(Debug, clap::Args)]
+pub struct StandingsArgs {
+    /// Path to the round-robin match CSV, or `-` for stdin [default: the pool matches of --project]
+    #[arg(short, long)]
+    matches_path: Option<String>,
+    /// Path to the JSON file storing the results; unused with --project
+    #[arg(long, default_value = "results.json")]
+    store_path: String,
+    #[command(flatten)]
+    tiebreak_args: TiebreakArgs,
+    /// Path for the standings CSV, or `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    output_path: String,
+    /// Path for the PDF of the standings, with one table per pool
+    #[arg(long)]
+    pdf_path: Option<String>,
+    /// Title printed above the standings
+    #[arg(short, long, default_value = "Standings")]
+    tournament_name: String,
+    /// Path to the JSON player registry whose names replace player IDs [default: the players of --project]
+    #[arg(long)]
+    players_path: Option<String>,
+    /// List the pairs of each pool in gojūon order of the reading of their first player instead of by rank
+    #[arg(long)]
+    sort_by_reading: bool,
+    #[command(flatten)]
+    font_args: FontArgs,
+}
+
+/// Runs a `results` subcommand
+///
+/// # Arguments
+///
+/// * `command` - Subcommand and its arguments
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the subcommand succeeds, Err otherwise
+pub fn run(
+    command: &ResultsCommand,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    match command {
+        ResultsCommand::Import(args) => import_results(args, project, encoding),
+        ResultsCommand::Advance(args) => advance_results(args, project, encoding),
+        ResultsCommand::Standings(args) => compute_standings(args, project, encoding),
+    }
+}
+
+/// Validates a results CSV and adds its results to the result store
+///
+/// Scores are checked against the rules of the division named in the `Division` column
+/// of the match CSV. Pairs not yet known when the match CSV was generated, such as
+/// `Winner of W1-2`, are looked up in the results stored so far, including earlier
+/// lines of the same file.
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `results import`
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if every result is valid and stored, Err listing every invalid line otherwise
+pub fn import_results(
+    args: &ImportArgs,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let rules = load_rules(args.rules_path.as_deref(), project.as_deref())?;
+    let match_rows = match (&args.matches_path, project.as_deref()) {
+        (None, Some(project)) => {
+            // Results of both stages can be imported from the same file
+            let mut match_rows = Vec::new();
+            for stage in [Stage::Pool, Stage::Playoff] {
+                if project.matches(stage).is_ok() {
+                    let input = open_matches(None, Some(project), stage, encoding)?;
+                    match_rows.extend(read_matches(input, &["Match ID"])?.1.rows);
+                }
+            }
+            match_rows
+        }
+        (path, project) => {
+            let input = open_matches(path.as_deref(), project, Stage::Pool, encoding)?;
+            read_matches(input, &["Match ID"])?.1.rows
+        }
+    };
+    let matches: HashMap<&str, &Row> = match_rows
+        .iter()
+        .map(|row| (row["Match ID"].as_str(), row))
+        .collect();
+
+    let file = open_input(&args.results_path, encoding).context("Failed to open results CSV")?;
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(file);
+    let validated = validate::read_rows(
+        &mut reader,
+        &ValidateOptions {
+            required_columns: &["Match ID".to_string(), "Scores".to_string()],
+            rename_columns: &[],
+            tolerant: false,
+            is_pending_pair: None,
+        },
+    )?;
+    print_warnings(&validated.warnings);
+    let Validated {
+        rows: result_rows,
+        lines,
+        ..
+    } = validated;
+
+    let mut store = load_results(project.as_deref(), &args.store_path)?;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for (result_row, line) in result_rows.iter().zip(&lines) {
+        let match_id = &result_row["Match ID"];
+        let Some(match_row) = matches.get(match_id.as_str()) else {
+            errors.push(format!("line {}: unknown match `{}`", line, match_id));
+            continue;
+        };
+        let division = match_row.get(DIVISION_COLUMN).map_or("", String::as_str);
+        let games = match results::parse_scores(&result_row["Scores"]).and_then(|games| {
+            let game_warnings = rules.for_division(division).validate(&games)?;
+            Ok((games, game_warnings))
+        }) {
+            Ok((games, game_warnings)) => {
+                warnings.extend(
+                    game_warnings
+                        .into_iter()
+                        .map(|warning| format!("line {}: {}: {}", line, match_id, warning)),
+                );
+                games
+            }
+            Err(err) => {
+                errors.push(format!("line {}: {}: {}", line, match_id, err));
+                continue;
+            }
+        };
+
+        let mut pairs = Vec::new();
+        for columns in PAIR_COLUMNS {
+            let pair = Pair::from_columns(match_row, columns);
+            if !pair.pair_no.is_empty() {
+                pairs.push(pair);
+                continue;
+            }
+            let label = pair.players.first().map_or("", String::as_str);
+            match store.entrant(label) {
+                Some(pair) => pairs.push(pair.clone()),
+                None => errors.push(format!(
+                    "line {}: {}: `{}` is not known yet; import that result first",
+                    line, match_id, label
+                )),
+            }
+        }
+        let Ok(pairs) = <[Pair; 2]>::try_from(pairs) else {
+            continue;
+        };
+
+        store.results.insert(
+            match_id.clone(),
+            results::MatchResult {
+                pool: match_row.get("Pool").cloned().unwrap_or_default(),
+                round: match_row
+                    .get("Round")
+                    .and_then(|round| round.parse().ok())
+                    .unwrap_or(0),
+                pairs,
+                games,
+            },
+        );
+    }
+    if !warnings.is_empty() {
+        eprintln!(
+            "warning: these scores are implausible, please check them:\n{}",
+            warnings.join("\n")
+        );
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("Invalid results file:\n{}", errors.join("\n"));
+    }
+
+    let (imported, stored) = (result_rows.len(), store.results.len());
+    let store_path = match project.as_deref() {
+        Some(project) => project.path(project::STORE_FILE),
+        None => args.store_path.clone(),
+    };
+    save_results(project, store, &args.store_path)?;
+    eprintln!(
+        "Imported {} results; {} results stored in {}",
+        imported, stored, store_path
+    );
+
+    Ok(())
+}
+
+/// Fills the pairs decided by stored results into a bracket match CSV
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `results advance`
+/// * `project` - Tournament project whose playoff matches are updated, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the match CSV was written, Err otherwise
+pub fn advance_results(
+    args: &AdvanceArgs,
+    project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let input = open_matches(
+        args.matches_path.as_deref(),
+        project.as_deref(),
+        Stage::Playoff,
+        encoding,
+    )?;
+    let (columns, Validated { mut rows, .. }) = read_matches(input, &[])?;
+    let store = load_results(project.as_deref(), &args.store_path)?;
+
+    for row in &mut rows {
+        for columns in PAIR_COLUMNS {
+            if !row[columns[0]].is_empty() {
+                continue;
+            }
+            let label = row.get(columns[1]).cloned().unwrap_or_default();
+            if let Some(pair) = store.entrant(&label) {
+                for (column, value) in columns.iter().zip(pair.to_fields()) {
+                    row.insert(column.to_string(), value);
+                }
+            }
+        }
+    }
+
+    let table = to_table(&columns, &rows);
+    if let Some(output_path) = csv_output(&args.output_path, project.as_deref()) {
+        write_table(output_path, &table)?;
+    }
+    if let Some(project) = project {
+        if args.matches_path.is_none() {
+            project.set_matches(Stage::Playoff, table);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ranks the pairs of each pool of a round-robin match CSV from the stored results
+///
+/// Coin flips made to break ties are saved to the result store, so that the standings
+/// stay the same when they are computed again.
+///
+/// # Arguments
+///
+/// * `matches_path` - Path to the round-robin match CSV, or `-` for stdin; None reads the pool matches of the project
+/// * `store_path` - Path to the JSON result store, used without a project
+/// * `project` - Tournament project, if any
+/// * `tiebreak_args` - Tiebreak chain and differential cap
+/// * `complete` - Whether every match must have a result; otherwise missing results are a warning
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<Vec<(String, Vec<Standing>)>>` - Ok with the standings of each pool in CSV order if successful, Err otherwise
+pub fn rank_pools(
+    matches_path: Option<&str>,
+    store_path: &str,
+    project: Option<&mut Project>,
+    tiebreak_args: &TiebreakArgs,
+    complete: bool,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<(String, Vec<Standing>)>> {
+    let input = open_matches(matches_path, project.as_deref(), Stage::Pool, encoding)?;
+    let rows = read_matches(input, &["Match ID", "Pool"])?.1.rows;
+    let mut store = load_results(project.as_deref(), store_path)?;
+
+    // Pairs and results of each pool, in CSV order
+    let mut pools: Vec<(&str, Vec<Pair>, Vec<&results::MatchResult>)> = Vec::new();
+    let mut unplayed: HashMap<&str, usize> = HashMap::new();
+    for row in &rows {
+        let pool = row["Pool"].as_str();
+        let index = match pools.iter().position(|(name, _, _)| *name == pool) {
+            Some(index) => index,
+            None => {
+                pools.push((pool, Vec::new(), Vec::new()));
+                pools.len() - 1
+            }
+        };
+        let (_, pairs, pool_results) = &mut pools[index];
+        for columns in PAIR_COLUMNS {
+            let pair = Pair::from_columns(row, columns);
+            if pair.pair_no.is_empty() {
+                anyhow::bail!(
+                    "Match {} has no `{}`; standings are only computed for pool matches",
+                    row["Match ID"],
+                    columns[0]
+                );
+            }
+            if !pairs
+                .iter()
+                .any(|existing| existing.pair_no == pair.pair_no)
+            {
+                pairs.push(pair);
+            }
+        }
+        match store.results.get(&row["Match ID"]) {
+            Some(result) => pool_results.push(result),
+            None => *unplayed.entry(pool).or_default() += 1,
+        }
+    }
+    let unplayed: Vec<String> = pools
+        .iter()
+        .filter_map(|(pool, _, _)| {
+            let count = unplayed.get(pool)?;
+            Some(format!(
+                "{} matches of pool {} have no result yet",
+                count, pool
+            ))
+        })
+        .collect();
+    if complete && !unplayed.is_empty() {
+        anyhow::bail!("Pool play is not over:\n{}", unplayed.join("\n"));
+    }
+    for warning in &unplayed {
+        eprintln!("warning: {}", warning);
+    }
+
+    let options = StandingsOptions {
+        tiebreaks: &tiebreak_args.tiebreak,
+        differential_cap: tiebreak_args.differential_cap,
+    };
+    let mut coin_flips = store.coin_flips.clone();
+    let standings = pools
+        .iter()
+        .map(|(pool, pairs, pool_results)| {
+            let pool_standings =
+                standings::pool_standings(pool, pairs, pool_results, &options, &mut coin_flips);
+            (pool.to_string(), pool_standings)
+        })
+        .collect();
+
+    let new_flips: Vec<String> = coin_flips
+        .iter()
+        .filter(|(key, _)| !store.coin_flips.contains_key(*key))
+        .map(|(key, order)| format!("{} -> {}", key, order.join(", ")))
+        .collect();
+    if !new_flips.is_empty() {
+        eprintln!("Ties broken by coin flip:\n{}", new_flips.join("\n"));
+        store.coin_flips = coin_flips;
+        save_results(project, store, store_path)?;
+    }
+
+    Ok(standings)
+}
+
+/// Ranks the pairs of each pool and writes the standings as a CSV and a PDF
+///
+/// # Arguments
+///
+/// * `args` - Arguments of `results standings`
+/// * `project` - Tournament project, if any
+/// * `encoding` - Encoding of the CSV inputs given with `--encoding`; None detects it per file
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if the standings were written, Err otherwise
+pub fn compute_standings(
+    args: &StandingsArgs,
+    mut project: Option<&mut Project>,
+    encoding: Option<&'static Encoding>,
+) -> Result<()> {
+    let mut standings = rank_pools(
+        args.matches_path.as_deref(),
+        &args.store_path,
+        project.as_deref_mut(),
+        &args.tiebreak_args,
+        false,
+        encoding,
+    )?;
+    let registry = load_players(args.players_path.as_deref(), project.as_deref())?;
+    if args.sort_by_reading {
+        let registry = registry.clone().unwrap_or_default();
+        for (_, pool_standings) in &mut standings {
+            pool_standings.sort_by_cached_key(|standing| {
+                standing
+                    .pair
+                    .players
+                    .first()
+                    .map(|player| registry.sort_key(player))
+            });
+        }
+    }
+    if let Some(registry) = registry {
+        for standing in standings.iter_mut().flat_map(|(_, pool)| pool) {
+            standing.pair = registry.resolve_pair(&standing.pair);
+        }
+    }
+
+    let mut writer = Writer::from_writer(create_output(&args.output_path)?);
+    writer.write_record(STANDING_COLUMNS)?;
+    for (pool, pool_standings) in &standings {
+        for standing in pool_standings {
+            writer.write_record(standings::to_record(pool, standing))?;
+        }
+    }
+    writer.flush()?;
+
+    if let Some(pdf_path) = &args.pdf_path {
+        let font_options = args.font_args.font_options();
+        let usvg_options = fonts::load_options(&font_options)?;
+        fonts::check_families(
+            &usvg_options.fontdb,
+            &font_options.fallbacks,
+            ["sans-serif"],
+        )?;
+        let texts = standings
+            .iter()
+            .flat_map(|(pool, pool_standings)| {
+                pool_standings
+                    .iter()
+                    .flat_map(|standing| {
+                        standing.pair.players.iter().chain([&standing.pair.pair_no])
+                    })
+                    .chain([pool])
+            })
+            .map(String::as_str)
+            .chain([args.tournament_name.as_str()]);
+        fonts::check_glyphs(
+            &usvg_options.fontdb,
+            &font_options.fallbacks,
+            texts.map(|text| ("sans-serif", text)),
+        )?;
+
+        let timings = Timings::default();
+        let (titles, pages): (Vec<String>, Vec<Page>) =
+            standings::standings_pages(&standings, &args.tournament_name)
+                .into_iter()
+                .map(|(title, svg)| Ok((title, pdf::svg_to_page(&svg, &usvg_options, &timings)?)))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .unzip();
+        std::fs::write(pdf_path, pdf::merge_pages(pages, &titles, true))
+            .with_context(|| format!("Failed to write {}", pdf_path))?;
+        if let Some(project) = project {
+            project.record_document("Standings", pdf_path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use encoding_rs::{DecoderResult, Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Number of leading bytes looked at to recognize UTF-16 without a byte order mark
+const UTF16_SAMPLE: usize = 1024;
+
+/// Looks up an encoding by name, for `--encoding`
+///
+/// Accepts the WHATWG labels (`utf-8`, `shift_jis`, `utf-16le`, ...) and `cp932`.
+///
+/// # Arguments
+///
+/// * `label` - Name of the encoding
+///
+/// # Returns
+///
+/// * `Result<&'static Encoding>` - Ok with the encoding if the name is known, Err otherwise
+pub fn parse(label: &str) -> Result<&'static Encoding> {
+    let label = label.trim();
+    if label.eq_ignore_ascii_case("cp932") {
+        return Ok(SHIFT_JIS);
+    }
+
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) => Ok(encoding),
+        None => bail!(
+            "unknown encoding '{}' (expected e.g. utf-8, shift_jis, cp932, utf-16le or utf-16be)",
+            label
+        ),
+    }
+}
+
+/// Decodes the bytes of a CSV file to text
+///
+/// Without an explicit encoding, a byte order mark decides; otherwise UTF-16 is recognized by
+/// its zero bytes, then UTF-8 is tried, then Shift_JIS (CP932, as saved by Japanese Excel).
+///
+/// # Arguments
+///
+/// * `bytes` - Contents of the file
+/// * `encoding` - Encoding given with `--encoding`; None detects it
+///
+/// # Returns
+///
+/// * `Result<String>` - Ok with the text if successful, Err naming the line of the file holding the first undecodable bytes otherwise
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> Result<String> {
+    if let Some(encoding) = encoding {
+        return decode_with(bytes, encoding).map_err(|line| undecodable(encoding, line));
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return decode_with(bytes, encoding).map_err(|line| undecodable(encoding, line));
+    }
+    if let Some(encoding) = detect_utf16(bytes) {
+        return decode_with(bytes, encoding).map_err(|line| undecodable(encoding, line));
+    }
+
+    match decode_with(bytes, UTF_8) {
+        Ok(text) => Ok(text),
+        Err(utf8_line) => match decode_with(bytes, SHIFT_JIS) {
+            Ok(text) => Ok(text),
+            // Blame the encoding that got further, as it is the likelier one
+            Err(sjis_line) if sjis_line > utf8_line => Err(undecodable(SHIFT_JIS, sjis_line)),
+            Err(_) => Err(undecodable(UTF_8, utf8_line)),
+        },
+    }
+}
+
+/// Decodes bytes with an encoding, removing its byte order mark
+///
+/// # Arguments
+///
+/// * `bytes` - Contents of the file
+/// * `encoding` - Encoding of the contents
+///
+/// # Returns
+///
+/// * `Result<String, usize>` - Ok with the text if successful, Err with the 1-based line of the file holding the first undecodable bytes otherwise
+fn decode_with(bytes: &[u8], encoding: &'static Encoding) -> Result<String, usize> {
+    let mut decoder = encoding.new_decoder_with_bom_removal();
+    let capacity = decoder
+        .max_utf8_buffer_length_without_replacement(bytes.len())
+        .unwrap_or(bytes.len() * 3);
+    let mut text = String::with_capacity(capacity);
+
+    match decoder.decode_to_string_without_replacement(bytes, &mut text, true) {
+        (DecoderResult::InputEmpty, _) => Ok(text),
+        // Everything decoded before the malformed bytes is in `text`
+        (_, _) => Err(text.matches('\n').count() + 1),
+    }
+}
+
+/// Recognizes UTF-16 without a byte order mark from the zero bytes of ASCII characters
+///
+/// # Arguments
+///
+/// * `bytes` - Contents of the file
+///
+/// # Returns
+///
+/// * `Option<&'static Encoding>` - UTF-16LE or UTF-16BE if the bytes look like it, None otherwise
+fn detect_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SAMPLE)];
+    let units = sample.len() / 2;
+    if units == 0 {
+        return None;
+    }
+
+    let zeros_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&byte| byte == 0)
+            .count()
+    };
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    // The header row alone is ASCII, so a real UTF-16 file has many zeros on one side only
+    if odd * 4 >= units && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 4 >= units && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Builds the error for bytes that are not valid in an encoding
+///
+/// # Arguments
+///
+/// * `encoding` - Encoding the bytes were decoded with
+/// * `line` - 1-based line of the file holding the first undecodable bytes
+///
+/// # Returns
+///
+/// * `anyhow::Error` - Error naming the line and the encoding
+fn undecodable(encoding: &'static Encoding, line: usize) -> anyhow::Error {
+    anyhow::anyhow!(
+        "line {}: bytes that are not valid {}; pass --encoding to read the file in another encoding",
+        line,
+        encoding.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes text as UTF-16
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to encode
+    /// * `big_endian` - true for UTF-16BE, false for UTF-16LE
+    /// * `bom` - Whether to start with a byte order mark
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The encoded bytes
+    fn utf16(text: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+        let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+        units
+            .flat_map(|unit| match big_endian {
+                true => unit.to_be_bytes(),
+                false => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_encodings() {
+        let text = "Name,Club\n山田 太郎,福岡\n";
+        let cases: [(&str, Vec<u8>); 7] = [
+            ("UTF-8", text.as_bytes().to_vec()),
+            (
+                "UTF-8 with BOM",
+                [b"\xEF\xBB\xBF", text.as_bytes()].concat(),
+            ),
+            ("UTF-16LE with BOM", utf16(text, false, true)),
+            ("UTF-16BE with BOM", utf16(text, true, true)),
+            ("UTF-16LE", utf16(text, false, false)),
+            ("UTF-16BE", utf16(text, true, false)),
+            (
+                "Shift_JIS",
+                b"Name,Club\n\x8E\x52\x93\x63 \x91\xBE\x98\x59,\x95\x9F\x89\xAA\n".to_vec(),
+            ),
+        ];
+        for (name, bytes) in cases {
+            assert_eq!(decode(&bytes, None).unwrap(), text, "{}", name);
+        }
+    }
+
+    #[test]
+    fn cp932_extensions() {
+        // ① and the NEC-selected kanji 髙 only exist in CP932, which Excel writes
+        assert_eq!(decode(b"\x87\x40\xFB\xFC\n", None).unwrap(), "①髙\n");
+    }
+
+    #[test]
+    fn explicit_encoding_wins() {
+        let bytes = b"Name\n\x8E\x52\x93\x63\n";
+        assert_eq!(decode(bytes, Some(SHIFT_JIS)).unwrap(), "Name\n山田\n");
+        let err = decode(bytes, Some(UTF_8)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: bytes that are not valid UTF-8; pass --encoding to read the file in another encoding"
+        );
+    }
+
+    #[test]
+    fn undecodable_bytes_name_their_line() {
+        let cases: [(&[u8], &str); 3] = [
+            (
+                b"Name\nAnn\nB\xFFb\n",
+                "line 3: bytes that are not valid UTF-8",
+            ),
+            // Valid Shift_JIS for longer than UTF-8, so Shift_JIS is blamed
+            (
+                b"Name\n\x8E\x52\n\xFF\n",
+                "line 3: bytes that are not valid Shift_JIS",
+            ),
+            // A quoted line break is a line of the file
+            (
+                b"Name\n\"A\nB\"\n\xFF\n",
+                "line 4: bytes that are not valid UTF-8",
+            ),
+        ];
+        for (bytes, message) in cases {
+            let err = decode(bytes, None).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn encoding_labels() {
+        assert_eq!(parse("cp932").unwrap(), SHIFT_JIS);
+        assert_eq!(parse(" Shift_JIS ").unwrap(), SHIFT_JIS);
+        assert_eq!(parse("utf-16le").unwrap(), UTF_16LE);
+        assert_eq!(parse("UTF-16BE").unwrap(), UTF_16BE);
+        assert!(parse("klingon").is_err());
+    }
+}
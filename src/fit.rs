@@ -0,0 +1,389 @@
+use crate::fonts;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use svg2pdf::usvg::fontdb;
+
+/// Font of a text element, as declared in the SVG template
+#[derive(Debug, Clone)]
+pub struct TextStyle {
+    /// Value of the `font-family` attribute
+    pub font_family: String,
+    /// Font size in user units
+    pub font_size: f32,
+}
+
+/// Result of fitting a text into a maximum width
+#[derive(Debug)]
+pub struct Fit {
+    /// Lines of text; two when the text was wrapped
+    pub lines: Vec<String>,
+    /// Font size in user units
+    pub font_size: f32,
+    /// Letter spacing in user units; negative when the text was condensed
+    pub letter_spacing: f32,
+}
+
+/// Shrinks, condenses or wraps text that is wider than its text box
+pub struct TextFitter {
+    /// Font database used to measure text
+    fontdb: Arc<fontdb::Database>,
+    /// Families to try, in order, when a font family is missing or lacks a glyph
+    fallbacks: HashMap<String, Vec<String>>,
+    /// Smallest font size a text may be shrunk to before it is reported
+    min_font_size: f32,
+    /// Texts that could only be fitted below `min_font_size`
+    squeezed: Mutex<BTreeSet<String>>,
+    /// Advance of each character in ems, keyed by the font of the element and the character
+    advances: Mutex<HashMap<(Option<fontdb::ID>, char), f32>>,
+}
+
+/// Largest letter-spacing reduction, as a fraction of the font size
+const MAX_CONDENSE: f32 = 0.1;
+
+/// Line height of wrapped text, as a multiple of the font size
+pub const LINE_HEIGHT: f32 = 1.1;
+
+impl TextFitter {
+    /// Creates a text fitter
+    ///
+    /// # Arguments
+    ///
+    /// * `fontdb` - Font database used to measure text
+    /// * `fallbacks` - Families to try when a font family is missing or lacks a glyph
+    /// * `min_font_size` - Smallest font size a text may be shrunk to before it is reported
+    ///
+    /// # Returns
+    ///
+    /// * `TextFitter` - The text fitter
+    pub fn new(
+        fontdb: Arc<fontdb::Database>,
+        fallbacks: HashMap<String, Vec<String>>,
+        min_font_size: f32,
+    ) -> TextFitter {
+        TextFitter {
+            fontdb,
+            fallbacks,
+            min_font_size,
+            squeezed: Mutex::new(BTreeSet::new()),
+            advances: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fits text into a maximum width
+    ///
+    /// The text is first shrunk down to the minimum font size, then condensed with a
+    /// negative letter-spacing, then wrapped to two lines at the space that best
+    /// balances them. Text that still does not fit at the minimum size gets the largest
+    /// size that fits and is remembered for [`TextFitter::squeezed`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to fit
+    /// * `style` - Font of the text element
+    /// * `max_width` - Maximum width in user units
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Fit>` - None if the text already fits, the adjusted layout otherwise
+    pub fn fit(&self, text: &str, style: &TextStyle, max_width: f32) -> Option<Fit> {
+        let size = style.font_size;
+        let width = self.measure(text, &style.font_family) * size;
+        if width <= max_width {
+            return None;
+        }
+
+        // Shrink
+        let min_size = self.min_font_size.min(size);
+        let shrunk_size = size * max_width / width;
+        if shrunk_size >= min_size {
+            return Some(Fit {
+                lines: vec![text.to_string()],
+                font_size: shrunk_size,
+                letter_spacing: 0.0,
+            });
+        }
+
+        // Condense
+        let gaps = text.chars().count().saturating_sub(1) as f32;
+        let min_width = width * min_size / size;
+        if gaps > 0.0 && min_width - max_width <= gaps * min_size * MAX_CONDENSE {
+            return Some(Fit {
+                lines: vec![text.to_string()],
+                font_size: min_size,
+                letter_spacing: (max_width - min_width) / gaps,
+            });
+        }
+
+        // Wrap
+        let mut fit = Fit {
+            lines: vec![text.to_string()],
+            font_size: shrunk_size,
+            letter_spacing: 0.0,
+        };
+        if let Some((first, second)) = self.split(text, &style.font_family) {
+            let line_width = self
+                .measure(&first, &style.font_family)
+                .max(self.measure(&second, &style.font_family));
+            let wrapped_size = (max_width / line_width).min(size / LINE_HEIGHT);
+            if wrapped_size > fit.font_size {
+                fit = Fit {
+                    lines: vec![first, second],
+                    font_size: wrapped_size,
+                    letter_spacing: 0.0,
+                };
+            }
+        }
+
+        if fit.font_size < min_size {
+            self.squeezed
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .insert(text.to_string());
+        }
+
+        Some(fit)
+    }
+
+    /// Returns the texts that could only be fitted below the minimum font size
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - Texts in sorted order
+    pub fn squeezed(&self) -> Vec<String> {
+        self.squeezed
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Measures the advance width of text in the font of a text element
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to measure
+    /// * `style` - Font of the text element
+    ///
+    /// # Returns
+    ///
+    /// * `f32` - Width of the text in user units
+    pub fn width(&self, text: &str, style: &TextStyle) -> f32 {
+        self.measure(text, &style.font_family) * style.font_size
+    }
+
+    /// Returns the minimum font size
+    ///
+    /// # Returns
+    ///
+    /// * `f32` - Smallest font size a text may be shrunk to before it is reported
+    pub fn min_font_size(&self) -> f32 {
+        self.min_font_size
+    }
+
+    /// Splits text into two lines at the space that best balances their widths
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to split
+    /// * `font_family` - Value of the `font-family` attribute
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(String, String)>` - The two lines, or None if the text has no space
+    fn split(&self, text: &str, font_family: &str) -> Option<(String, String)> {
+        text.char_indices()
+            .filter(|(_, c)| c.is_whitespace())
+            .map(|(index, c)| {
+                let mut first = text[..index].trim().to_string();
+                let mut second = text[index + c.len_utf8()..].trim();
+                // Keep the separator of a pair ("A / B") at the end of the first line
+                if let Some(rest) = second.strip_prefix('/') {
+                    first.push_str(" /");
+                    second = rest.trim_start();
+                }
+                (first, second.to_string())
+            })
+            .filter(|(first, second)| !first.is_empty() && !second.is_empty())
+            .min_by(|a, b| {
+                let width = |(first, second): &(String, String)| {
+                    self.measure(first, font_family)
+                        .max(self.measure(second, font_family))
+                };
+                width(a).total_cmp(&width(b))
+            })
+    }
+
+    /// Measures the advance width of text at a font size of 1
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to measure
+    /// * `font_family` - Value of the `font-family` attribute
+    ///
+    /// # Returns
+    ///
+    /// * `f32` - Width of the text in ems
+    fn measure(&self, text: &str, font_family: &str) -> f32 {
+        let families = fonts::query_families(font_family, &self.fallbacks);
+        let primary = self.fontdb.query(&fontdb::Query {
+            families: &families,
+            ..fontdb::Query::default()
+        });
+
+        text.chars().map(|c| self.advance(primary, c)).sum()
+    }
+
+    /// Returns the advance of a character, looking its font up only the first time
+    ///
+    /// Characters missing from the font of the element are measured with the first
+    /// loaded font that has them, like the renderer does.
+    ///
+    /// # Arguments
+    ///
+    /// * `primary` - Font of the element, if any matches
+    /// * `c` - Character to measure
+    ///
+    /// # Returns
+    ///
+    /// * `f32` - Advance in ems; 0 if no loaded font has the character
+    fn advance(&self, primary: Option<fontdb::ID>, c: char) -> f32 {
+        let cached = self
+            .advances
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&(primary, c))
+            .copied();
+        if let Some(advance) = cached {
+            return advance;
+        }
+
+        let faces = primary
+            .into_iter()
+            .chain(self.fontdb.faces().map(|face| face.id));
+        let advance = faces
+            .filter_map(|id| {
+                self.fontdb.with_face_data(id, |data, index| {
+                    let face = ttf_parser::Face::parse(data, index).ok()?;
+                    let glyph = face.glyph_index(c)?;
+                    let advance = face.glyph_hor_advance(glyph)?;
+                    Some(advance as f32 / face.units_per_em() as f32)
+                })?
+            })
+            .next()
+            .unwrap_or(0.0);
+
+        self.advances
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert((primary, c), advance);
+
+        advance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a fitter measuring with the bundled test font, with a minimum font size of 8
+    ///
+    /// # Returns
+    ///
+    /// * `TextFitter` - The fitter
+    fn test_fitter() -> TextFitter {
+        let mut fontdb = fontdb::Database::new();
+        fontdb.load_font_file("test/fonts/Tuffy.ttf").unwrap();
+        TextFitter::new(Arc::new(fontdb), HashMap::new(), 8.0)
+    }
+
+    /// Returns the font of the name fields of the tests: the test font at size 12
+    ///
+    /// # Returns
+    ///
+    /// * `TextStyle` - The font
+    fn style() -> TextStyle {
+        TextStyle {
+            font_family: "Tuffy".to_string(),
+            font_size: 12.0,
+        }
+    }
+
+    #[test]
+    fn text_that_fits_is_left_alone() {
+        let fitter = test_fitter();
+        let width = fitter.width("Ann Lee", &style());
+
+        assert!(width > 0.0);
+        assert!(fitter.fit("Ann Lee", &style(), width).is_none());
+    }
+
+    #[test]
+    fn slightly_long_text_is_shrunk() {
+        let fitter = test_fitter();
+        let width = fitter.width("Alexander Hamilton", &style());
+
+        let fit = fitter
+            .fit("Alexander Hamilton", &style(), width * 0.75)
+            .unwrap();
+        assert_eq!(fit.lines, ["Alexander Hamilton"]);
+        assert!((fit.font_size - 9.0).abs() < 1e-3);
+        assert_eq!(fit.letter_spacing, 0.0);
+    }
+
+    #[test]
+    fn text_too_long_at_the_minimum_size_is_condensed() {
+        let fitter = test_fitter();
+        let text = "Alexander Hamilton";
+        let min_width = fitter.width(text, &style()) * 8.0 / 12.0;
+        let gaps = (text.chars().count() - 1) as f32;
+
+        // Half of the largest condensing, which is a tenth of the font size per gap
+        let fit = fitter
+            .fit(text, &style(), min_width - gaps * 8.0 * MAX_CONDENSE / 2.0)
+            .unwrap();
+        assert_eq!(fit.lines, [text]);
+        assert_eq!(fit.font_size, 8.0);
+        assert!((fit.letter_spacing + 0.4).abs() < 1e-3);
+        assert!(fitter.squeezed().is_empty());
+    }
+
+    #[test]
+    fn text_too_long_to_condense_is_wrapped_at_the_most_balanced_space() {
+        let fitter = test_fitter();
+        let text = "Alexander Hamilton Jr";
+        let min_width = fitter.width(text, &style()) * 8.0 / 12.0;
+
+        let fit = fitter.fit(text, &style(), min_width * 0.7).unwrap();
+        assert_eq!(fit.lines, ["Alexander", "Hamilton Jr"]);
+        assert_eq!(fit.letter_spacing, 0.0);
+        let line_width = fitter
+            .width("Alexander", &style())
+            .max(fitter.width("Hamilton Jr", &style()));
+        assert!((fit.font_size - 12.0 * min_width * 0.7 / line_width).abs() < 1e-3);
+        assert!(fit.font_size >= 8.0);
+        assert!(fitter.squeezed().is_empty());
+    }
+
+    #[test]
+    fn pairs_wrap_after_their_separator() {
+        let fitter = test_fitter();
+        let text = "Ann Lee / Ben Cho";
+        let min_width = fitter.width(text, &style()) * 8.0 / 12.0;
+
+        let fit = fitter.fit(text, &style(), min_width * 0.6).unwrap();
+        assert_eq!(fit.lines, ["Ann Lee /", "Ben Cho"]);
+    }
+
+    #[test]
+    fn text_below_the_minimum_size_is_reported() {
+        let fitter = test_fitter();
+        let text = "Wolfeschlegelsteinhausenbergerdorff";
+        let width = fitter.width(text, &style());
+
+        let fit = fitter.fit(text, &style(), width / 4.0).unwrap();
+        assert_eq!(fit.lines, [text]);
+        assert!((fit.font_size - 3.0).abs() < 1e-3);
+        assert_eq!(fitter.squeezed(), [text]);
+    }
+}
@@ -0,0 +1,515 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use svg2pdf::usvg::{self, fontdb, FontFamily, FontResolver, FontStretch, FontStyle};
+
+/// Fonts to load and how to fall back between font families
+#[derive(Debug, Default)]
+pub struct FontOptions {
+    /// Whether to load the fonts installed on the system
+    pub system_fonts: bool,
+    /// Font files, or directories scanned recursively for font files
+    pub font_paths: Vec<String>,
+    /// Families to try, in order, when a font family is missing or lacks a glyph
+    pub fallbacks: HashMap<String, Vec<String>>,
+}
+
+/// Builds usvg options with the requested fonts and fallbacks
+///
+/// # Arguments
+///
+/// * `font_options` - Fonts to load and family fallbacks
+///
+/// # Returns
+///
+/// * `Result<usvg::Options<'static>>` - Ok with the options if successful, Err if a font file cannot be loaded
+pub fn load_options(font_options: &FontOptions) -> Result<usvg::Options<'static>> {
+    let mut options = usvg::Options::default();
+
+    let fontdb = options.fontdb_mut();
+    if font_options.system_fonts {
+        fontdb.load_system_fonts();
+    }
+    for font_path in &font_options.font_paths {
+        if Path::new(font_path).is_dir() {
+            fontdb.load_fonts_dir(font_path);
+        } else {
+            fontdb
+                .load_font_file(font_path)
+                .with_context(|| format!("Failed to load font {}", font_path))?;
+        }
+    }
+    if fontdb.is_empty() {
+        anyhow::bail!("No fonts loaded; add fonts with --font or allow system fonts");
+    }
+
+    let fallbacks = Arc::new(font_options.fallbacks.clone());
+    let select_font_fallbacks = Arc::clone(&fallbacks);
+    options.font_resolver = FontResolver {
+        select_font: Box::new(move |font, fontdb| {
+            select_font(font, fontdb, &select_font_fallbacks)
+        }),
+        select_fallback: Box::new(move |c, used_fonts, fontdb| {
+            select_fallback(c, used_fonts, fontdb, &fallbacks)
+                .or_else(|| (FontResolver::default_fallback_selector())(c, used_fonts, fontdb))
+        }),
+    };
+
+    Ok(options)
+}
+
+/// Selects the font for a `<text>` element, trying the fallbacks of each family right after it
+///
+/// # Arguments
+///
+/// * `font` - Font requested by the element
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+///
+/// # Returns
+///
+/// * `Option<fontdb::ID>` - The selected font, or None if no family matches
+fn select_font(
+    font: &usvg::Font,
+    fontdb: &mut Arc<fontdb::Database>,
+    fallbacks: &HashMap<String, Vec<String>>,
+) -> Option<fontdb::ID> {
+    let mut families = Vec::new();
+    for family in font.families() {
+        let (query_family, name) = match family {
+            FontFamily::Serif => (fontdb::Family::Serif, "serif"),
+            FontFamily::SansSerif => (fontdb::Family::SansSerif, "sans-serif"),
+            FontFamily::Cursive => (fontdb::Family::Cursive, "cursive"),
+            FontFamily::Fantasy => (fontdb::Family::Fantasy, "fantasy"),
+            FontFamily::Monospace => (fontdb::Family::Monospace, "monospace"),
+            FontFamily::Named(name) => (fontdb::Family::Name(name), name.as_str()),
+        };
+        families.push(query_family);
+        if let Some(fallbacks) = fallbacks.get(name) {
+            families.extend(fallbacks.iter().map(|name| fontdb::Family::Name(name)));
+        }
+    }
+    families.push(fontdb::Family::Serif);
+
+    let stretch = match font.stretch() {
+        FontStretch::UltraCondensed => fontdb::Stretch::UltraCondensed,
+        FontStretch::ExtraCondensed => fontdb::Stretch::ExtraCondensed,
+        FontStretch::Condensed => fontdb::Stretch::Condensed,
+        FontStretch::SemiCondensed => fontdb::Stretch::SemiCondensed,
+        FontStretch::Normal => fontdb::Stretch::Normal,
+        FontStretch::SemiExpanded => fontdb::Stretch::SemiExpanded,
+        FontStretch::Expanded => fontdb::Stretch::Expanded,
+        FontStretch::ExtraExpanded => fontdb::Stretch::ExtraExpanded,
+        FontStretch::UltraExpanded => fontdb::Stretch::UltraExpanded,
+    };
+    let style = match font.style() {
+        FontStyle::Normal => fontdb::Style::Normal,
+        FontStyle::Italic => fontdb::Style::Italic,
+        FontStyle::Oblique => fontdb::Style::Oblique,
+    };
+
+    fontdb.query(&fontdb::Query {
+        families: &families,
+        weight: fontdb::Weight(font.weight()),
+        stretch,
+        style,
+    })
+}
+
+/// Returns the families to query for a `font-family` value, each followed by its fallbacks
+///
+/// Generic names such as `sans-serif` query the generic family of the font database, and
+/// the list ends with the generic serif family, as usvg does.
+///
+/// # Arguments
+///
+/// * `font_family` - Value of a `font-family` attribute, e.g. `Inter, sans-serif`
+/// * `fallbacks` - Fallback families keyed by family name
+///
+/// # Returns
+///
+/// * `Vec<fontdb::Family<'a>>` - Families in the order they are tried
+pub fn query_families<'a>(
+    font_family: &'a str,
+    fallbacks: &'a HashMap<String, Vec<String>>,
+) -> Vec<fontdb::Family<'a>> {
+    let mut families = Vec::new();
+    for name in font_family.split(',') {
+        let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+        families.push(match name {
+            "serif" => fontdb::Family::Serif,
+            "sans-serif" => fontdb::Family::SansSerif,
+            "cursive" => fontdb::Family::Cursive,
+            "fantasy" => fontdb::Family::Fantasy,
+            "monospace" => fontdb::Family::Monospace,
+            name => fontdb::Family::Name(name),
+        });
+        if let Some(fallbacks) = fallbacks.get(name) {
+            families.extend(fallbacks.iter().map(|name| fontdb::Family::Name(name)));
+        }
+    }
+    families.push(fontdb::Family::Serif);
+
+    families
+}
+
+/// Fails when a font family and its fallbacks match no loaded font
+///
+/// usvg leaves out text whose font cannot be found, so without this check a missing
+/// template font would print blank sheets.
+///
+/// # Arguments
+///
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+/// * `font_families` - Values of the `font-family` attributes that will be printed
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if every family resolves to a loaded font, Err listing the others
+pub fn check_families<'a>(
+    fontdb: &fontdb::Database,
+    fallbacks: &HashMap<String, Vec<String>>,
+    font_families: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let unresolved: BTreeSet<&str> = font_families
+        .into_iter()
+        .filter(|font_family| {
+            fontdb
+                .query(&fontdb::Query {
+                    families: &query_families(font_family, fallbacks),
+                    ..fontdb::Query::default()
+                })
+                .is_none()
+        })
+        .collect();
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = unresolved
+        .iter()
+        .map(|font_family| format!("`{}`", font_family))
+        .collect();
+    anyhow::bail!(
+        "No loaded font matches these font families; add one with --font, or map the family \
+         to a loaded font with --font-fallback (e.g. \"Inter=Noto Sans JP\"):\n{}",
+        lines.join("\n")
+    )
+}
+
+/// Selects a font for a character missing from the fonts already used, preferring configured fallbacks
+///
+/// # Arguments
+///
+/// * `c` - Character to render
+/// * `used_fonts` - Fonts already tried, the first one being the font of the element
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+///
+/// # Returns
+///
+/// * `Option<fontdb::ID>` - A configured fallback font that has the character, if any
+fn select_fallback(
+    c: char,
+    used_fonts: &[fontdb::ID],
+    fontdb: &mut Arc<fontdb::Database>,
+    fallbacks: &HashMap<String, Vec<String>>,
+) -> Option<fontdb::ID> {
+    let base_face = fontdb.face(*used_fonts.first()?)?;
+    let fallback_families = base_face
+        .families
+        .iter()
+        .filter_map(|(family, _)| fallbacks.get(family))
+        .flatten();
+
+    for family in fallback_families {
+        let face = fontdb.faces().find(|face| {
+            !used_fonts.contains(&face.id)
+                && face.families.iter().any(|(name, _)| name == family)
+                && has_char(fontdb, face.id, c)
+        });
+        if let Some(face) = face {
+            return Some(face.id);
+        }
+    }
+
+    None
+}
+
+/// Returns whether a font has a glyph for a character
+///
+/// # Arguments
+///
+/// * `fontdb` - Font database
+/// * `id` - Font to check
+/// * `c` - Character to look up
+///
+/// # Returns
+///
+/// * `bool` - true if the font has a glyph for the character
+fn has_char(fontdb: &fontdb::Database, id: fontdb::ID, c: char) -> bool {
+    fontdb
+        .with_face_data(id, |data, index| {
+            ttf_parser::Face::parse(data, index).is_ok_and(|face| face.glyph_index(c).is_some())
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the fonts that text in a font family is rendered with
+///
+/// These are the font of each family in the `font-family` value and its fallbacks, then
+/// the fallbacks of the family of the selected font, as tried by the font resolver.
+///
+/// # Arguments
+///
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+/// * `font_family` - Value of a `font-family` attribute
+///
+/// # Returns
+///
+/// * `Vec<fontdb::ID>` - Fonts in the order they are tried; empty if the family matches no loaded font
+fn font_chain(
+    fontdb: &fontdb::Database,
+    fallbacks: &HashMap<String, Vec<String>>,
+    font_family: &str,
+) -> Vec<fontdb::ID> {
+    let query = |families: &[fontdb::Family]| {
+        fontdb.query(&fontdb::Query {
+            families,
+            ..fontdb::Query::default()
+        })
+    };
+    let families = query_families(font_family, fallbacks);
+    let Some(selected) = query(&families) else {
+        return Vec::new();
+    };
+
+    let mut chain = vec![selected];
+    let selected_fallbacks = fontdb
+        .face(selected)
+        .into_iter()
+        .flat_map(|face| &face.families)
+        .filter_map(|(family, _)| fallbacks.get(family))
+        .flatten()
+        .map(|name| fontdb::Family::Name(name));
+    for family in families.iter().cloned().chain(selected_fallbacks) {
+        if let Some(id) = query(&[family]).filter(|id| !chain.contains(id)) {
+            chain.push(id);
+        }
+    }
+    chain
+}
+
+/// Returns the characters of the given texts that the fonts of their font family cannot render
+///
+/// # Arguments
+///
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+/// * `texts` - `font-family` value and text of everything that will be printed
+///
+/// # Returns
+///
+/// * `BTreeSet<(&str, char)>` - Font family and character of each glyph missing from the fonts of the family
+fn missing_chars<'a>(
+    fontdb: &fontdb::Database,
+    fallbacks: &HashMap<String, Vec<String>>,
+    texts: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> BTreeSet<(&'a str, char)> {
+    let mut chars: BTreeMap<&str, BTreeSet<char>> = BTreeMap::new();
+    for (font_family, text) in texts {
+        chars.entry(font_family).or_default().extend(
+            text.chars()
+                .filter(|c| !c.is_whitespace() && !c.is_control()),
+        );
+    }
+
+    let mut missing = BTreeSet::new();
+    for (font_family, mut family_chars) in chars {
+        // Each font is parsed once and checked against every remaining character
+        for id in font_chain(fontdb, fallbacks, font_family) {
+            if family_chars.is_empty() {
+                break;
+            }
+            fontdb.with_face_data(id, |data, index| {
+                if let Ok(face) = ttf_parser::Face::parse(data, index) {
+                    family_chars.retain(|c| face.glyph_index(*c).is_none());
+                }
+            });
+        }
+        missing.extend(family_chars.into_iter().map(|c| (font_family, c)));
+    }
+
+    missing
+}
+
+/// Fails with the list of characters that the fonts of their font family cannot render
+///
+/// A character is only found in the font selected for its `font-family` value and in the
+/// fallbacks configured for it, so a glyph present in an unrelated font is still reported.
+///
+/// # Arguments
+///
+/// * `fontdb` - Font database
+/// * `fallbacks` - Fallback families keyed by family name
+/// * `texts` - `font-family` value and text of everything that will be printed
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if every character has a glyph, Err listing the characters and where they appear otherwise
+pub fn check_glyphs<'a>(
+    fontdb: &fontdb::Database,
+    fallbacks: &HashMap<String, Vec<String>>,
+    texts: impl IntoIterator<Item = (&'a str, &'a str)> + Clone,
+) -> Result<()> {
+    let missing = missing_chars(fontdb, fallbacks, texts.clone());
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = missing
+        .iter()
+        .map(|&(font_family, c)| {
+            let example = texts
+                .clone()
+                .into_iter()
+                .find(|&(family, text)| family == font_family && text.contains(c))
+                .map_or("", |(_, text)| text);
+            format!(
+                "'{}' (U+{:04X}) in `{}`, font-family `{}`",
+                c, c as u32, example, font_family
+            )
+        })
+        .collect();
+
+    anyhow::bail!(
+        "No font of the font family or its fallbacks can render these characters; add a font \
+         with --font, or a fallback family that has them with --font-fallback:\n{}",
+        lines.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path of the Latin-only font bundled for tests, whose family is `Tuffy`
+    const TEST_FONT: &str = "test/fonts/Tuffy.ttf";
+
+    /// Returns a font database holding only the test font
+    ///
+    /// # Returns
+    ///
+    /// * `fontdb::Database` - The font database
+    fn test_fontdb() -> fontdb::Database {
+        let mut fontdb = fontdb::Database::new();
+        fontdb.load_font_file(TEST_FONT).unwrap();
+        fontdb
+    }
+
+    #[test]
+    fn unknown_family_is_reported() {
+        let fontdb = test_fontdb();
+
+        let error = check_families(&fontdb, &HashMap::new(), ["Tuffy", "Inter", "Noto Sans JP"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("No loaded font matches these font families"));
+        assert!(error.ends_with(":\n`Inter`\n`Noto Sans JP`"));
+    }
+
+    #[test]
+    fn fallback_resolves_a_missing_family() {
+        let fontdb = test_fontdb();
+        let fallbacks = HashMap::from([("Inter".to_string(), vec!["Tuffy".to_string()])]);
+
+        check_families(&fontdb, &fallbacks, ["Inter", "'Inter', sans-serif"]).unwrap();
+        assert_eq!(
+            query_families("Inter, sans-serif", &fallbacks),
+            [
+                fontdb::Family::Name("Inter"),
+                fontdb::Family::Name("Tuffy"),
+                fontdb::Family::SansSerif,
+                fontdb::Family::Serif,
+            ]
+        );
+    }
+
+    #[test]
+    fn characters_without_a_glyph_are_listed() {
+        let fontdb = test_fontdb();
+        let fallbacks = HashMap::new();
+
+        check_glyphs(
+            &fontdb,
+            &fallbacks,
+            [("Tuffy", "Ann"), ("Tuffy", "Ben / Cat")],
+        )
+        .unwrap();
+        let error = check_glyphs(
+            &fontdb,
+            &fallbacks,
+            [
+                ("Tuffy", "Ann"),
+                ("Tuffy", "サンプル 太郎"),
+                ("Tuffy", "太郎"),
+            ],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.ends_with(
+            ":\n'サ' (U+30B5) in `サンプル 太郎`, font-family `Tuffy`\n\
+             'プ' (U+30D7) in `サンプル 太郎`, font-family `Tuffy`\n\
+             'ル' (U+30EB) in `サンプル 太郎`, font-family `Tuffy`\n\
+             'ン' (U+30F3) in `サンプル 太郎`, font-family `Tuffy`\n\
+             '太' (U+592A) in `サンプル 太郎`, font-family `Tuffy`\n\
+             '郎' (U+90CE) in `サンプル 太郎`, font-family `Tuffy`"
+        ));
+    }
+
+    #[test]
+    fn glyphs_are_only_found_in_the_fonts_of_the_family() {
+        let fontdb = test_fontdb();
+
+        // Tuffy has the glyphs, but text in Inter is not rendered with it
+        let error = check_glyphs(&fontdb, &HashMap::new(), [("Inter", "Ann")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with(
+            ":\n'A' (U+0041) in `Ann`, font-family `Inter`\n\
+             'n' (U+006E) in `Ann`, font-family `Inter`"
+        ));
+
+        let fallbacks = HashMap::from([("Inter".to_string(), vec!["Tuffy".to_string()])]);
+        check_glyphs(&fontdb, &fallbacks, [("Inter", "Ann")]).unwrap();
+        assert_eq!(font_chain(&fontdb, &fallbacks, "Inter").len(), 1);
+        assert!(font_chain(&fontdb, &HashMap::new(), "Inter").is_empty());
+    }
+
+    #[test]
+    fn fonts_must_be_loaded() {
+        let options = load_options(&FontOptions {
+            font_paths: vec![TEST_FONT.to_string()],
+            ..FontOptions::default()
+        })
+        .unwrap();
+        assert_eq!(options.fontdb.len(), 1);
+
+        let error = load_options(&FontOptions::default()).err().unwrap();
+        assert!(error.to_string().starts_with("No fonts loaded"));
+
+        let error = load_options(&FontOptions {
+            font_paths: vec!["test/fonts/missing.ttf".to_string()],
+            ..FontOptions::default()
+        })
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Failed to load font test/fonts/missing.ttf"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{encoding, spreadsheet, structured};
+use anyhow::{Context, Result};
+use encoding_rs::Encoding;
+use std::fs::File;
+use std::io::{Cursor, Read};
+
+/// Opens an input file as CSV, or stdin for `-`
+///
+/// Text is decoded to UTF-8 with the given encoding, or the encoding detected from its
+/// bytes. Spreadsheets (`.xlsx`, `.ods`, ...) are converted to CSV, reading the sheet
+/// named after a `#` in the path or the first sheet, and so are JSON and YAML documents
+/// (`.json`, `.yaml`, or JSON on stdin).
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `encoding` - Encoding of text files; None detects it
+///
+/// # Returns
+///
+/// * `Result<Box<dyn Read>>` - Ok with a reader of CSV text if successful, Err otherwise
+pub fn open(path: &str, encoding: Option<&'static Encoding>) -> Result<Box<dyn Read>> {
+    if let Some((path, sheet)) = spreadsheet::split_sheet(path) {
+        return Ok(Box::new(Cursor::new(spreadsheet::to_csv(path, sheet)?)));
+    }
+
+    let mut bytes = Vec::new();
+    if path == "-" {
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .context("Failed to read stdin")?;
+    } else {
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .with_context(|| format!("Failed to open {}", path))?;
+    }
+    let text =
+        encoding::decode(&bytes, encoding).with_context(|| format!("Failed to decode {}", path))?;
+
+    let format = match path {
+        "-" => structured::sniff(&text),
+        _ => structured::format_of(path),
+    };
+    match format {
+        Some(format) => Ok(Box::new(Cursor::new(
+            structured::to_csv(&text, format)
+                .with_context(|| format!("Failed to read {}", path))?,
+        ))),
+        None => Ok(Box::new(Cursor::new(text.into_bytes()))),
+    }
+}
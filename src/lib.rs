@@ -0,0 +1,82 @@
+//! Score sheets, brackets and standings for pickleball tournaments
+//!
+//! Score sheets are rendered from an SVG template whose text elements are filled from
+//! the rows of a match CSV, several cards per page:
+//!
+//! ```no_run
+//! use csv::ReaderBuilder;
+//! use pickleball_result::fonts::FontOptions;
+//! use pickleball_result::render::{DataOptions, Job, ScoreSheet};
+//! use pickleball_result::rules::RulesConfig;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let fonts = FontOptions {
+//!     system_fonts: true,
+//!     ..FontOptions::default()
+//! };
+//! let sheet = ScoreSheet::load("test/sample.svg", None, &fonts)?;
+//!
+//! let input = pickleball_result::input::open("test/matches.csv", None)?;
+//! let mut reader = ReaderBuilder::new().flexible(true).from_reader(input);
+//! let validated = sheet.read_rows(
+//!     &mut reader,
+//!     &DataOptions {
+//!         rename_columns: &[],
+//!         tolerant: true,
+//!         rules: &RulesConfig::default(),
+//!         players: None,
+//!     },
+//! )?;
+//! for warning in &validated.warnings {
+//!     eprintln!("warning: {}", warning);
+//! }
+//! let rows = validated.rows;
+//! sheet.check_glyphs(&rows, "KINTO CUP")?;
+//!
+//! // One page as SVG or PDF
+//! let cards = sheet.manifest().cards_per_page;
+//! let svg = sheet.render_svg(&rows[..cards], "KINTO CUP")?;
+//! let pdf = sheet.render_pdf(&rows[..cards], "KINTO CUP")?;
+//!
+//! // Every page, written to target/out.pdf
+//! sheet.render_job(&Job {
+//!     rows: &rows,
+//!     tournament_name: "KINTO CUP",
+//!     output_path: "target/out",
+//!     merge: true,
+//!     page_numbers: true,
+//!     image: None,
+//! })?;
+//!
+//! // Every page as a single PDF in memory, or streamed to any writer
+//! let merged = sheet.render_merged(&rows, "KINTO CUP", true)?;
+//! sheet.write_merged(&rows, "KINTO CUP", true, std::io::stdout().lock())?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod advance;
+pub mod bracket;
+pub mod encoding;
+pub mod fit;
+pub mod fonts;
+pub mod input;
+pub mod manifest;
+pub mod model;
+pub mod pdf;
+pub mod players;
+pub mod project;
+pub mod raster;
+pub mod render;
+pub mod results;
+pub mod round_robin;
+pub mod rules;
+pub mod schedule;
+pub mod spreadsheet;
+pub mod standings;
+pub mod structured;
+pub mod template;
+pub mod timings;
+pub mod validate;
+
+pub use render::{DataOptions, Job, ScoreSheet};
@@ -0,0 +1,266 @@
+use crate::fonts::FontOptions;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Layout of an SVG template, read from a TOML file next to the SVG
+///
+/// ```toml
+/// cards_per_page = 4
+/// tournament_name_id = "NAME"
+///
+/// fonts = ["fonts/NotoSansJP-Regular.ttf"]
+/// min_font_size = 8
+///
+/// [fields]
+/// PLAYER1 = "Player1"
+/// PairNo1 = "Pair No1"
+///
+/// [max_widths]
+/// PLAYER1 = 78
+///
+/// [ruby]
+/// PLAYER1 = "Player1Kana"
+///
+/// [font_fallbacks]
+/// Inter = ["Noto Sans JP"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Number of cards on each page; each card consumes one CSV row
+    pub cards_per_page: usize,
+    /// ID of the element on each card that receives the tournament name
+    pub tournament_name_id: Option<String>,
+    /// Element IDs on each card, mapped to the CSV column that fills them
+    ///
+    /// IDs are given for the first card; see [`crate::template::card_slot_id`] for the following cards.
+    pub fields: BTreeMap<String, String>,
+    /// Widths that the text of elements must fit in, keyed by the ID on the first card
+    ///
+    /// Overrides the `data-max-width` attribute of the element.
+    #[serde(default)]
+    pub max_widths: BTreeMap<String, f32>,
+    /// Element IDs of fields, mapped to the CSV column whose text is printed above them as ruby
+    #[serde(default)]
+    pub ruby: BTreeMap<String, String>,
+    /// Smallest font size that text is shrunk to before it is reported
+    #[serde(default = "default_min_font_size")]
+    pub min_font_size: f32,
+    /// Font files or directories to load, relative to the manifest
+    #[serde(default)]
+    pub fonts: Vec<String>,
+    /// Families to try, in order, when a font family is missing or lacks a glyph
+    #[serde(default)]
+    pub font_fallbacks: BTreeMap<String, Vec<String>>,
+}
+
+/// Returns the default smallest font size that text is shrunk to
+///
+/// # Returns
+///
+/// * `f32` - Font size in user units
+fn default_min_font_size() -> f32 {
+    8.0
+}
+
+impl Default for Manifest {
+    /// Layout of `test/sample.svg`: four doubles cards on an A4 landscape page
+    fn default() -> Self {
+        let mut fields = BTreeMap::new();
+        for player_num in 1..=4 {
+            fields.insert(
+                format!("PLAYER{}", player_num),
+                format!("Player{}", player_num),
+            );
+        }
+        for pair_num in 1..=2 {
+            fields.insert(
+                format!("PairNo{}", pair_num),
+                format!("Pair No{}", pair_num),
+            );
+        }
+
+        let mut max_widths = BTreeMap::new();
+        let mut ruby = BTreeMap::new();
+        for player_num in 1..=4 {
+            max_widths.insert(format!("PLAYER{}", player_num), 78.0);
+            ruby.insert(
+                format!("PLAYER{}", player_num),
+                format!("Player{}Kana", player_num),
+            );
+        }
+
+        Manifest {
+            cards_per_page: 4,
+            tournament_name_id: Some("NAME".to_string()),
+            fields,
+            max_widths,
+            ruby,
+            min_font_size: default_min_font_size(),
+            fonts: Vec::new(),
+            font_fallbacks: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Loads the manifest of an SVG template
+    ///
+    /// Without an explicit path, `<template>.toml` next to the SVG is used if it exists,
+    /// and the built-in layout of `test/sample.svg` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest_path` - Path to the manifest file given on the command line, if any
+    /// * `svg_path` - Path to the SVG template
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Manifest>` - Ok with the manifest if successful, Err otherwise
+    pub fn load(manifest_path: Option<&str>, svg_path: &str) -> Result<Manifest> {
+        let manifest_path = match manifest_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let path = Path::new(svg_path).with_extension("toml");
+                if !path.exists() {
+                    return Ok(Manifest::default());
+                }
+                path
+            }
+        };
+
+        let manifest_str = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read manifest {}", manifest_path.display()))?;
+        let mut manifest: Manifest = toml::from_str(&manifest_str)
+            .with_context(|| format!("Failed to parse manifest {}", manifest_path.display()))?;
+
+        if manifest.cards_per_page == 0 {
+            anyhow::bail!(
+                "Invalid manifest {}: cards_per_page must be at least 1",
+                manifest_path.display()
+            );
+        }
+
+        if let Some(id) = manifest
+            .ruby
+            .keys()
+            .find(|id| !manifest.fields.contains_key(*id))
+        {
+            anyhow::bail!(
+                "Invalid manifest {}: ruby element `{}` is not listed in fields",
+                manifest_path.display(),
+                id
+            );
+        }
+
+        let manifest_dir = manifest_path.parent().unwrap_or(Path::new(""));
+        for font_path in &mut manifest.fonts {
+            *font_path = manifest_dir
+                .join(&*font_path)
+                .to_string_lossy()
+                .into_owned();
+        }
+
+        Ok(manifest)
+    }
+
+    /// Combines the fonts of the manifest with fonts given by the caller
+    ///
+    /// # Arguments
+    ///
+    /// * `extra` - Fonts and fallbacks loaded after those of the manifest; its `system_fonts` is kept
+    ///
+    /// # Returns
+    ///
+    /// * `FontOptions` - Fonts to load and family fallbacks
+    pub fn font_options(&self, extra: &FontOptions) -> FontOptions {
+        let mut font_options = FontOptions {
+            system_fonts: extra.system_fonts,
+            font_paths: self.fonts.clone(),
+            fallbacks: self.font_fallbacks.clone().into_iter().collect(),
+        };
+        font_options
+            .font_paths
+            .extend(extra.font_paths.iter().cloned());
+        font_options.fallbacks.extend(extra.fallbacks.clone());
+        font_options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a manifest into its own directory under the system temporary directory
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the directory, unique to the test
+    /// * `contents` - TOML of the manifest
+    ///
+    /// # Returns
+    ///
+    /// * `PathBuf` - Path of the manifest
+    fn write_manifest(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pickleball-result-manifest-{}", name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("template.toml");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn sample_manifest_matches_the_built_in_layout() {
+        let sample = Manifest::load(None, "test/sample.svg").unwrap();
+        let default = Manifest::load(None, "test/missing.svg").unwrap();
+
+        assert_eq!(sample.cards_per_page, default.cards_per_page);
+        assert_eq!(sample.tournament_name_id, default.tournament_name_id);
+        assert_eq!(sample.fields, default.fields);
+        assert_eq!(sample.max_widths, default.max_widths);
+        assert_eq!(sample.ruby, default.ruby);
+    }
+
+    #[test]
+    fn fonts_are_relative_to_the_manifest() {
+        let path = write_manifest(
+            "fonts",
+            "cards_per_page = 2\nfonts = [\"fonts/Noto.ttf\"]\n[fields]\nPLAYER1 = \"Player1\"\n",
+        );
+
+        let manifest = Manifest::load(Some(&path.to_string_lossy()), "template.svg").unwrap();
+        assert_eq!(manifest.cards_per_page, 2);
+        assert_eq!(manifest.min_font_size, 8.0);
+        assert_eq!(
+            manifest.fonts,
+            [path
+                .parent()
+                .unwrap()
+                .join("fonts/Noto.ttf")
+                .to_string_lossy()]
+        );
+    }
+
+    #[test]
+    fn invalid_layouts_are_rejected() {
+        let path = write_manifest("no-cards", "cards_per_page = 0\n[fields]\n");
+        let error = Manifest::load(Some(&path.to_string_lossy()), "template.svg").unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("cards_per_page must be at least 1"));
+
+        let path = write_manifest(
+            "ruby",
+            "cards_per_page = 1\n[fields]\nPLAYER1 = \"Player1\"\n[ruby]\nPLAYER2 = \"Player2Kana\"\n",
+        );
+        let error = Manifest::load(Some(&path.to_string_lossy()), "template.svg").unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("ruby element `PLAYER2` is not listed in fields"));
+
+        let path = write_manifest("unknown", "cards_per_page = 1\ncards = 4\n[fields]\n");
+        assert!(Manifest::load(Some(&path.to_string_lossy()), "template.svg").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A CSV row keyed by column name
+pub type Row = HashMap<String, String>;
+
+/// A registered pair of players
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pair {
+    /// Pair number printed on the score sheet
+    pub pair_no: String,
+    /// Names of the players; one for singles, two for doubles
+    pub players: Vec<String>,
+}
+
+/// A match between two pairs
+#[derive(Debug, Clone)]
+pub struct Match {
+    /// Name of the pool the match belongs to
+    pub pool: String,
+    /// One-based round number within the pool
+    pub round: usize,
+    /// One-based number of the match within its round
+    pub number: usize,
+    /// The two pairs playing the match
+    pub pairs: [Pair; 2],
+}
+
+/// Columns of the match CSV written by the generators, in order
+pub const MATCH_COLUMNS: [&str; 9] = [
+    "Match ID", "Pool", "Round", "Pair No1", "Player1", "Player2", "Pair No2", "Player3", "Player4",
+];
+
+/// Pair number and player columns of the two pairs of a match CSV row
+pub const PAIR_COLUMNS: [[&str; 3]; 2] = [
+    ["Pair No1", "Player1", "Player2"],
+    ["Pair No2", "Player3", "Player4"],
+];
+
+impl Pair {
+    /// Reads a pair from a CSV row with `Pair No`, `Player1` and `Player2` columns
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Values of the row keyed by column name
+    ///
+    /// # Returns
+    ///
+    /// * `Pair` - The pair; blank player columns are skipped
+    pub fn from_row(row: &HashMap<String, String>) -> Pair {
+        Pair::from_columns(row, ["Pair No", "Player1", "Player2"])
+    }
+
+    /// Reads a pair from the given pair number and player columns of a CSV row
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Values of the row keyed by column name
+    /// * `columns` - Pair number column followed by the two player columns, e.g. an entry of [`PAIR_COLUMNS`]
+    ///
+    /// # Returns
+    ///
+    /// * `Pair` - The pair; blank player columns are skipped
+    pub fn from_columns(row: &HashMap<String, String>, columns: [&str; 3]) -> Pair {
+        let field = |column: &str| row.get(column).cloned().unwrap_or_default();
+
+        Pair {
+            pair_no: field(columns[0]),
+            players: columns[1..]
+                .iter()
+                .map(|column| field(column))
+                .filter(|player| !player.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Returns the pair number and the names of the players in the order of a row of the match CSV
+    ///
+    /// # Returns
+    ///
+    /// * `[String; 3]` - Pair number, first player and second player, blank if missing
+    pub fn to_fields(&self) -> [String; 3] {
+        let player = |index: usize| self.players.get(index).cloned().unwrap_or_default();
+        [self.pair_no.clone(), player(0), player(1)]
+    }
+}
+
+impl Match {
+    /// Returns the ID of the match, e.g. `A-2-1` for the first match of round 2 of pool A
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The match ID
+    pub fn match_id(&self) -> String {
+        format!("{}-{}-{}", self.pool, self.round, self.number)
+    }
+
+    /// Returns the match as a row of the match CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - Values in the order of [`MATCH_COLUMNS`]
+    pub fn to_record(&self) -> Vec<String> {
+        let mut record = vec![self.match_id(), self.pool.clone(), self.round.to_string()];
+        for pair in &self.pairs {
+            record.extend(pair.to_fields());
+        }
+        record
+    }
+}
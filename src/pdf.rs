@@ -0,0 +1,275 @@
+use crate::timings::Timings;
+use anyhow::Result;
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use std::collections::HashMap;
+use svg2pdf::{usvg, ConversionOptions, PageOptions};
+
+/// A rendered SVG page ready to be embedded into a multi-page PDF
+pub struct Page {
+    /// PDF objects of the page, as produced by `svg2pdf::to_chunk`
+    chunk: pdf_writer::Chunk,
+    /// Reference of the root XObject inside `chunk`
+    x_object: Ref,
+    /// Width of the page in points
+    width: f32,
+    /// Height of the page in points
+    height: f32,
+}
+
+/// Converts SVG to a page that can be merged with others
+///
+/// # Arguments
+///
+/// * `svg_str` - String containing the SVG content
+/// * `options` - usvg options holding the font database
+/// * `timings` - Time spent in each stage
+///
+/// # Returns
+///
+/// * `Result<Page>` - Ok with the converted page if successful, Err otherwise
+pub fn svg_to_page(svg_str: &str, options: &usvg::Options, timings: &Timings) -> Result<Page> {
+    let tree = timings.measure("parse SVG", || usvg::Tree::from_str(svg_str, options))?;
+
+    let (chunk, x_object) = timings.measure("convert to PDF", || {
+        svg2pdf::to_chunk(&tree, ConversionOptions::default())
+    });
+
+    Ok(Page {
+        chunk,
+        x_object,
+        width: tree.size().width(),
+        height: tree.size().height(),
+    })
+}
+
+/// Assembles pages into a single PDF with one bookmark per page
+///
+/// # Arguments
+///
+/// * `pages` - Pages in output order
+/// * `titles` - Bookmark title of each page
+/// * `page_numbers` - Whether to print "n / total" in the footer of each page
+///
+/// # Returns
+///
+/// * `Vec<u8>` - Bytes of the merged PDF
+pub fn merge_pages(pages: Vec<Page>, titles: &[String], page_numbers: bool) -> Vec<u8> {
+    let mut alloc = Ref::new(1);
+    let catalog_ref = alloc.bump();
+    let page_tree_ref = alloc.bump();
+    let outline_ref = alloc.bump();
+    let font_ref = alloc.bump();
+    let svg_name = Name(b"S1");
+    let font_name = Name(b"F1");
+
+    let page_refs: Vec<Ref> = pages.iter().map(|_| alloc.bump()).collect();
+    let item_refs: Vec<Ref> = pages.iter().map(|_| alloc.bump()).collect();
+    let page_count = pages.len();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_ref)
+        .pages(page_tree_ref)
+        .outlines(outline_ref);
+    pdf.pages(page_tree_ref)
+        .kids(page_refs.iter().copied())
+        .count(page_count as i32);
+    pdf.type1_font(font_ref).base_font(Name(b"Helvetica"));
+
+    for (page_index, page) in pages.into_iter().enumerate() {
+        // Renumber the chunk so that its objects don't clash with the other pages
+        let mut map = HashMap::new();
+        let chunk = page
+            .chunk
+            .renumber(|old| *map.entry(old).or_insert_with(|| alloc.bump()));
+        let svg_ref = map[&page.x_object];
+        let content_ref = alloc.bump();
+
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([page.width, 0.0, 0.0, page.height, 0.0, 0.0]);
+        content.x_object(svg_name);
+        content.restore_state();
+
+        if page_numbers {
+            let label = format!("{} / {}", page_index + 1, page_count);
+            let font_size = 9.0;
+            // Helvetica digits are half an em wide, which is close enough to center the label
+            let label_width = label.len() as f32 * font_size * 0.5;
+            content
+                .begin_text()
+                .set_font(font_name, font_size)
+                .next_line((page.width - label_width) / 2.0, 8.0)
+                .show(Str(label.as_bytes()))
+                .end_text();
+        }
+
+        pdf.stream(content_ref, &content.finish());
+
+        let mut pdf_page = pdf.page(page_refs[page_index]);
+        pdf_page.media_box(Rect::new(0.0, 0.0, page.width, page.height));
+        pdf_page.parent(page_tree_ref);
+        pdf_page.contents(content_ref);
+        let mut resources = pdf_page.resources();
+        resources.x_objects().pair(svg_name, svg_ref);
+        resources.fonts().pair(font_name, font_ref);
+        resources.finish();
+        pdf_page.finish();
+
+        pdf.extend(&chunk);
+    }
+
+    // Bookmarks pointing to each page
+    let mut outline = pdf.outline(outline_ref);
+    if let (Some(first), Some(last)) = (item_refs.first(), item_refs.last()) {
+        outline.first(*first).last(*last);
+    }
+    outline.count(page_count as i32);
+    outline.finish();
+
+    for (page_index, (item_ref, title)) in item_refs.iter().zip(titles).enumerate() {
+        let mut item = pdf.outline_item(*item_ref);
+        item.title(TextStr(title)).parent(outline_ref);
+        if page_index > 0 {
+            item.prev(item_refs[page_index - 1]);
+        }
+        if let Some(next) = item_refs.get(page_index + 1) {
+            item.next(*next);
+        }
+        item.dest().page(page_refs[page_index]).fit();
+    }
+
+    pdf.finish()
+}
+
+/// Converts SVG to a single-page PDF
+///
+/// # Arguments
+///
+/// * `svg_str` - String containing the SVG content
+/// * `options` - usvg options holding the font database
+/// * `timings` - Time spent in each stage
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the bytes of the PDF if conversion succeeds, Err otherwise
+pub fn svg_to_pdf(svg_str: &str, options: &usvg::Options, timings: &Timings) -> Result<Vec<u8>> {
+    let tree = timings.measure("parse SVG", || usvg::Tree::from_str(svg_str, options))?;
+
+    Ok(timings.measure("convert to PDF", || {
+        svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Dictionary, Document, ObjectId};
+
+    /// Converts a blank SVG page of the given size
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the page in user units
+    /// * `height` - Height of the page in user units
+    ///
+    /// # Returns
+    ///
+    /// * `Page` - The page
+    fn page(width: u32, height: u32) -> Page {
+        let svg = format!(
+            r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}" xmlns="http://www.w3.org/2000/svg"><rect width="10" height="10" fill="black"/></svg>"#,
+            w = width,
+            h = height
+        );
+        svg_to_page(&svg, &usvg::Options::default(), &Timings::default()).unwrap()
+    }
+
+    /// Returns the dictionary an entry of another dictionary refers to
+    ///
+    /// # Arguments
+    ///
+    /// * `document` - Parsed PDF
+    /// * `dictionary` - Dictionary holding the reference
+    /// * `key` - Key of the reference
+    ///
+    /// # Returns
+    ///
+    /// * `(ObjectId, &Dictionary)` - ID and contents of the referenced dictionary
+    fn follow<'a>(
+        document: &'a Document,
+        dictionary: &Dictionary,
+        key: &[u8],
+    ) -> (ObjectId, &'a Dictionary) {
+        let id = dictionary.get(key).unwrap().as_reference().unwrap();
+        (id, document.get_dictionary(id).unwrap())
+    }
+
+    #[test]
+    fn merged_pages_have_bookmarks_and_page_numbers() {
+        let pages = vec![page(842, 595), page(842, 595), page(595, 842)];
+        let titles: Vec<String> = ["Group 1", "Group 2", "Group 3"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let document = Document::load_mem(&merge_pages(pages, &titles, true)).unwrap();
+        let page_ids: Vec<ObjectId> = document.get_pages().into_values().collect();
+        assert_eq!(page_ids.len(), 3);
+
+        // The last page keeps its own portrait size
+        let media_box = document
+            .get_dictionary(page_ids[2])
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<f32>>();
+        assert_eq!(media_box, [0.0, 0.0, 595.0, 842.0]);
+
+        // One bookmark per page, in order, each opening its page
+        let (_, outline) = follow(&document, document.catalog().unwrap(), b"Outlines");
+        assert_eq!(outline.get(b"Count").unwrap().as_i64().unwrap(), 3);
+        let (mut item_id, _) = follow(&document, outline, b"First");
+        for (page_index, title) in titles.iter().enumerate() {
+            let item = document.get_dictionary(item_id).unwrap();
+            assert_eq!(
+                item.get(b"Title").unwrap().as_str().unwrap(),
+                title.as_bytes()
+            );
+            let dest = item.get(b"Dest").unwrap().as_array().unwrap();
+            assert_eq!(dest[0].as_reference().unwrap(), page_ids[page_index]);
+            assert_eq!(dest[1].as_name().unwrap(), b"Fit");
+            match item.get(b"Next") {
+                Ok(next) => item_id = next.as_reference().unwrap(),
+                Err(_) => assert_eq!(page_index, titles.len() - 1),
+            }
+        }
+
+        // "n / total" in the footer of every page
+        for (page_index, page_id) in page_ids.iter().enumerate() {
+            let content =
+                String::from_utf8_lossy(&document.get_page_content(*page_id)).into_owned();
+            assert!(
+                content.contains(&format!("({} / 3) Tj", page_index + 1)),
+                "page {} content: {}",
+                page_index + 1,
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn page_numbers_are_optional() {
+        let titles = vec!["Group 1".to_string()];
+
+        let document =
+            Document::load_mem(&merge_pages(vec![page(842, 595)], &titles, false)).unwrap();
+        let page_ids: Vec<ObjectId> = document.get_pages().into_values().collect();
+        assert_eq!(page_ids.len(), 1);
+        let content = document.get_page_content(page_ids[0]);
+        assert!(!String::from_utf8_lossy(&content).contains("Tj"));
+    }
+}
@@ -0,0 +1,775 @@
+use crate::model::Pair;
+use crate::validate::is_player_column;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Half-width katakana and punctuation from U+FF61 to U+FF9D, as full-width characters
+const HALF_WIDTH_KANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// Hiragana with a romaji spelling, in the order of [`ROMAJI`]
+const KANA: &str = "あいうえおかきくけこさしすせそたちつてとなにぬねのはひふへほまみむめもやゆよらりるれろわゐゑをんがぎぐげござじずぜぞだぢづでどばびぶべぼぱぴぷぺぽゔ";
+
+/// Romaji of each hiragana of [`KANA`], used to compare names written in kana and in romaji
+const ROMAJI: [&str; 74] = [
+    "a", "i", "u", "e", "o", "ka", "ki", "ku", "ke", "ko", "sa", "shi", "su", "se", "so", "ta",
+    "chi", "tsu", "te", "to", "na", "ni", "nu", "ne", "no", "ha", "hi", "fu", "he", "ho", "ma",
+    "mi", "mu", "me", "mo", "ya", "yu", "yo", "ra", "ri", "ru", "re", "ro", "wa", "i", "e", "o",
+    "n", "ga", "gi", "gu", "ge", "go", "za", "ji", "zu", "ze", "zo", "da", "ji", "zu", "de", "do",
+    "ba", "bi", "bu", "be", "bo", "pa", "pi", "pu", "pe", "po", "vu",
+];
+
+/// Voiced and semi-voiced hiragana, in the order of [`UNVOICED`]
+const VOICED: &str = "がぎぐげござじずぜぞだぢづでどばびぶべぼぱぴぷぺぽゔ";
+
+/// Hiragana of [`VOICED`] without their sound mark, sorted with them in gojūon order
+const UNVOICED: &str = "かきくけこさしすせそたちつてとはひふへほはひふへほう";
+
+/// Small hiragana, in the order of [`LARGE`]
+const SMALL: &str = "ぁぃぅぇぉっゃゅょゎ";
+
+/// Full-size hiragana of [`SMALL`], sorted with them in gojūon order
+const LARGE: &str = "あいうえおつやゆよわ";
+
+/// A registered player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Player {
+    /// Stable ID of the player, e.g. `P001`
+    pub id: String,
+    /// Normalized name printed on the score sheets
+    pub name: String,
+    /// Other spellings of the name, e.g. from players merged into this one
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// IDs of the players merged into this one, still accepted in CSV files
+    #[serde(default)]
+    pub merged_ids: Vec<String>,
+    /// Reading of the name in kana, printed as ruby and used for sorting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+}
+
+/// Players known to the tournament, each with an ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Registry {
+    /// Players in the order they were registered
+    pub players: Vec<Player>,
+}
+
+/// Returns the column holding the reading of a player column, e.g. `Player1Kana` for `Player1`
+///
+/// # Arguments
+///
+/// * `column` - Player column
+///
+/// # Returns
+///
+/// * `String` - Name of the reading column
+pub fn reading_column(column: &str) -> String {
+    format!("{}Kana", column)
+}
+
+/// Returns whether a column holds the reading of a player, such as `Player1Kana`
+///
+/// # Arguments
+///
+/// * `column` - Name of the column
+///
+/// # Returns
+///
+/// * `bool` - true for a player column followed by `Kana`
+pub fn is_reading_column(column: &str) -> bool {
+    column.strip_suffix("Kana").is_some_and(is_player_column)
+}
+
+/// Returns a key that sorts readings in gojūon order (あいうえお, かきくけこ, ...)
+///
+/// Kana are compared as hiragana without their sound marks and with small kana made
+/// full-size, and `ー` counts as the vowel it lengthens, as in Japanese dictionaries.
+/// Ties are broken by the exact spelling, so `はし` comes before `ばし` and `ぱし`.
+/// Latin letters sort before kana, and kanji after them.
+///
+/// # Arguments
+///
+/// * `reading` - Reading in hiragana or katakana, or a name without a reading
+///
+/// # Returns
+///
+/// * `String` - Key whose code point order is the gojūon order
+pub fn gojuon_key(reading: &str) -> String {
+    let exact: String = normalize(reading)
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            c => c,
+        })
+        .collect();
+
+    let mut primary = String::new();
+    for c in exact.chars() {
+        let c = match c {
+            'ー' => match primary.chars().last().and_then(vowel_of) {
+                Some(vowel) => vowel,
+                None => continue,
+            },
+            c => match (
+                VOICED.chars().position(|voiced| voiced == c),
+                SMALL.chars().position(|small| small == c),
+            ) {
+                (Some(index), _) => UNVOICED.chars().nth(index).unwrap_or(c),
+                (_, Some(index)) => LARGE.chars().nth(index).unwrap_or(c),
+                _ => c,
+            },
+        };
+        primary.push(c);
+    }
+    format!("{}\0{}", primary, exact)
+}
+
+/// Returns the vowel a hiragana ends with, as a hiragana
+///
+/// # Arguments
+///
+/// * `kana` - Hiragana
+///
+/// # Returns
+///
+/// * `Option<char>` - One of `あいうえお`, or None if the character is not a kana with a vowel
+fn vowel_of(kana: char) -> Option<char> {
+    let index = KANA.chars().position(|c| c == kana)?;
+    let vowel = ROMAJI[index].chars().last()?;
+    "aiueo"
+        .chars()
+        .position(|c| c == vowel)
+        .and_then(|index| "あいうえお".chars().nth(index))
+}
+
+/// Normalizes how a name is written
+///
+/// Full-width letters, digits and spaces become half-width, half-width katakana
+/// become full-width, and runs of spaces become a single space.
+///
+/// # Arguments
+///
+/// * `name` - Name as written in a CSV file
+///
+/// # Returns
+///
+/// * `String` - The normalized name
+pub fn normalize(name: &str) -> String {
+    let mut chars: Vec<char> = Vec::new();
+    for c in name.chars() {
+        match c {
+            '\u{FF01}'..='\u{FF5E}' => {
+                chars.push(char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c));
+            }
+            '\u{FF61}'..='\u{FF9D}' => {
+                chars.push(
+                    HALF_WIDTH_KANA
+                        .chars()
+                        .nth(c as usize - 0xFF61)
+                        .unwrap_or(c),
+                );
+            }
+            '\u{FF9E}' | '\u{FF9F}' => {
+                let voiced = c == '\u{FF9E}';
+                match chars
+                    .last()
+                    .copied()
+                    .and_then(|last| combine_mark(last, voiced))
+                {
+                    Some(combined) => *chars.last_mut().unwrap() = combined,
+                    None => chars.push(if voiced { '゛' } else { '゜' }),
+                }
+            }
+            c if c.is_whitespace() => chars.push(' '),
+            c => chars.push(c),
+        }
+    }
+
+    chars
+        .into_iter()
+        .collect::<String>()
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Adds a voiced or semi-voiced sound mark to a katakana, e.g. `カ` to `ガ`
+///
+/// # Arguments
+///
+/// * `kana` - Full-width katakana
+/// * `voiced` - true for the voiced mark, false for the semi-voiced mark
+///
+/// # Returns
+///
+/// * `Option<char>` - The combined katakana, or None if the mark does not combine
+fn combine_mark(kana: char, voiced: bool) -> Option<char> {
+    if voiced && kana == 'ウ' {
+        return Some('ヴ');
+    }
+    let offset = match voiced {
+        true if "カキクケコサシスセソタチツテトハヒフヘホ".contains(kana) => 1,
+        false if "ハヒフヘホ".contains(kana) => 2,
+        _ => return None,
+    };
+    char::from_u32(kana as u32 + offset)
+}
+
+/// Returns the keys two spellings of the same name share
+///
+/// Kana are written in romaji, long vowels are shortened and letters are lowercased,
+/// so that `サンプル 太郎`, `サンプル太郎` and `sanpuru 太郎` share a key. The second key
+/// ignores the order of the words, for family and given names written in either order.
+///
+/// # Arguments
+///
+/// * `name` - Name of the player
+///
+/// # Returns
+///
+/// * `[String; 2]` - The key of the words in order, and of the words sorted
+fn duplicate_keys(name: &str) -> [String; 2] {
+    let mut words: Vec<String> = normalize(name)
+        .split([' ', '・', ',', '.'])
+        .filter(|word| !word.is_empty())
+        .map(|word| shorten_vowels(&romanize(&word.to_lowercase())))
+        .collect();
+    let in_order = words.concat();
+    words.sort();
+    [in_order, words.concat()]
+}
+
+/// Writes the kana of a word in romaji, leaving other characters as they are
+///
+/// # Arguments
+///
+/// * `word` - Word in hiragana, katakana or any other script
+///
+/// # Returns
+///
+/// * `String` - The word with its kana in Hepburn romaji
+fn romanize(word: &str) -> String {
+    let mut romaji = String::new();
+    let mut double_next = false;
+    for c in word.chars() {
+        // Katakana become hiragana
+        let c = match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            c => c,
+        };
+        let syllable = match c {
+            'っ' => {
+                double_next = true;
+                continue;
+            }
+            'ー' => {
+                if let Some(vowel) = romaji.chars().last().filter(|c| "aiueo".contains(*c)) {
+                    romaji.push(vowel);
+                }
+                continue;
+            }
+            'ゃ' | 'ゅ' | 'ょ' if romaji.ends_with('i') => {
+                romaji.pop();
+                let vowel = match c {
+                    'ゃ' => 'a',
+                    'ゅ' => 'u',
+                    _ => 'o',
+                };
+                if !["sh", "ch", "j"].iter().any(|end| romaji.ends_with(end)) {
+                    romaji.push('y');
+                }
+                romaji.push(vowel);
+                continue;
+            }
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' if romaji.ends_with(['a', 'i', 'u', 'e', 'o']) => {
+                romaji.pop();
+                romaji.push(small_vowel(c));
+                continue;
+            }
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' => small_vowel(c).to_string(),
+            'ゃ' => "ya".to_string(),
+            'ゅ' => "yu".to_string(),
+            'ょ' => "yo".to_string(),
+            c => match KANA.chars().position(|kana| kana == c) {
+                Some(index) => ROMAJI[index].to_string(),
+                None => c.to_string(),
+            },
+        };
+        if double_next {
+            double_next = false;
+            if let Some(first) = syllable.chars().next().filter(char::is_ascii_alphabetic) {
+                romaji.push(if syllable.starts_with("ch") {
+                    't'
+                } else {
+                    first
+                });
+            }
+        }
+        romaji.push_str(&syllable);
+    }
+    romaji
+}
+
+/// Returns the vowel of a small kana vowel
+///
+/// # Arguments
+///
+/// * `c` - One of `ぁぃぅぇぉ`
+///
+/// # Returns
+///
+/// * `char` - The vowel in romaji
+fn small_vowel(c: char) -> char {
+    match c {
+        'ぁ' => 'a',
+        'ぃ' => 'i',
+        'ぅ' => 'u',
+        'ぇ' => 'e',
+        _ => 'o',
+    }
+}
+
+/// Shortens long vowels, which romaji spell in many ways (`Tarou`, `Taroo`, `Tarō`)
+///
+/// # Arguments
+///
+/// * `romaji` - Lowercase word
+///
+/// # Returns
+///
+/// * `String` - The word with macrons removed and `ou` or doubled vowels shortened
+fn shorten_vowels(romaji: &str) -> String {
+    let mut shortened = String::new();
+    for c in romaji.chars() {
+        let c = match c {
+            'ā' | 'â' => 'a',
+            'ī' | 'î' => 'i',
+            'ū' | 'û' => 'u',
+            'ē' | 'ê' => 'e',
+            'ō' | 'ô' => 'o',
+            c => c,
+        };
+        let last = shortened.chars().last();
+        if (last == Some(c) && "aiueo".contains(c)) || (last == Some('o') && c == 'u') {
+            continue;
+        }
+        shortened.push(c);
+    }
+    shortened
+}
+
+impl Player {
+    /// Returns whether a name is one of the spellings of the player
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Normalized name
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the name is the name or an alias of the player
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+impl Registry {
+    /// Loads the registry, or returns an empty registry if the file does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the JSON registry
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Registry>` - Ok with the registry if successful, Err if the file cannot be read or parsed
+    pub fn load(path: &str) -> Result<Registry> {
+        if !Path::new(path).exists() {
+            return Ok(Registry::default());
+        }
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read player registry {}", path))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse player registry {}", path))
+    }
+
+    /// Writes the registry
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the JSON registry
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the registry was written, Err otherwise
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write player registry {}", path))
+    }
+
+    /// Returns the player with an ID, including the IDs of players merged into it
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the player
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&Player>` - The player, or None if the ID is unknown
+    pub fn get(&self, id: &str) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|player| player.id == id || player.merged_ids.iter().any(|merged| merged == id))
+    }
+
+    /// Returns the player with a name, once normalized
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the player
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&Player>` - The player, or None if no player has the name
+    pub fn find(&self, name: &str) -> Option<&Player> {
+        let name = normalize(name);
+        self.players.iter().find(|player| player.is_named(&name))
+    }
+
+    /// Registers a player, unless a player with the same normalized name or ID exists
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the player, or the ID of a registered player
+    ///
+    /// # Returns
+    ///
+    /// * `(String, bool)` - ID of the player, and whether the player is new
+    pub fn register(&mut self, name: &str) -> (String, bool) {
+        if let Some(player) = self.get(name).or_else(|| self.find(name)) {
+            return (player.id.clone(), false);
+        }
+        let next = self
+            .players
+            .iter()
+            .flat_map(|player| std::iter::once(&player.id).chain(&player.merged_ids))
+            .filter_map(|id| id.strip_prefix('P')?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let id = format!("P{:03}", next);
+        self.players.push(Player {
+            id: id.clone(),
+            name: normalize(name),
+            aliases: Vec::new(),
+            merged_ids: Vec::new(),
+            reading: None,
+        });
+        (id, true)
+    }
+
+    /// Returns the name to print for a CSV value: the name of a player ID, or the registered spelling of a name
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Player ID or name
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The name of the player, or the normalized value if the player is not registered
+    pub fn resolve(&self, value: &str) -> String {
+        match self.get(value).or_else(|| self.find(value)) {
+            Some(player) => player.name.clone(),
+            None => normalize(value),
+        }
+    }
+
+    /// Sets the reading of a player
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the player
+    /// * `reading` - Reading in kana
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the reading was set, Err if the ID is unknown
+    pub fn set_reading(&mut self, id: &str, reading: &str) -> Result<()> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|player| player.id == id)
+            .with_context(|| format!("Unknown player ID `{}`", id))?;
+        player.reading = Some(normalize(reading));
+        Ok(())
+    }
+
+    /// Returns the reading of a player
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Player ID or name
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The reading, or None if the player is unknown or has no reading
+    pub fn reading(&self, value: &str) -> Option<&str> {
+        self.get(value)
+            .or_else(|| self.find(value))?
+            .reading
+            .as_deref()
+    }
+
+    /// Returns the key sorting a player in gojūon order by reading, or by name without a reading
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Player ID or name
+    ///
+    /// # Returns
+    ///
+    /// * `String` - Key built by [`gojuon_key`]
+    pub fn sort_key(&self, value: &str) -> String {
+        match self.reading(value) {
+            Some(reading) => gojuon_key(reading),
+            None => gojuon_key(&self.resolve(value)),
+        }
+    }
+
+    /// Returns a pair with the names of its players resolved
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - Pair whose players are IDs or names
+    ///
+    /// # Returns
+    ///
+    /// * `Pair` - The pair with the names to print
+    pub fn resolve_pair(&self, pair: &Pair) -> Pair {
+        Pair {
+            pair_no: pair.pair_no.clone(),
+            players: pair
+                .players
+                .iter()
+                .map(|player| self.resolve(player))
+                .collect(),
+        }
+    }
+
+    /// Returns the players whose names are likely spellings of the same person
+    ///
+    /// Names and readings are compared regardless of spaces, kana or romaji, long
+    /// vowels, letter case and the order of the words.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<[&Player; 2]>` - Pairs of likely duplicates, in registration order
+    pub fn likely_duplicates(&self) -> Vec<[&Player; 2]> {
+        let mut by_key: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, player) in self.players.iter().enumerate() {
+            let mut keys = Vec::new();
+            let names = std::iter::once(&player.name)
+                .chain(&player.aliases)
+                .chain(&player.reading);
+            for name in names {
+                keys.extend(duplicate_keys(name));
+            }
+            keys.sort();
+            keys.dedup();
+            for key in keys.into_iter().filter(|key| !key.is_empty()) {
+                by_key.entry(key).or_default().push(index);
+            }
+        }
+
+        let mut duplicates: Vec<[usize; 2]> = Vec::new();
+        for indices in by_key.values() {
+            for (position, &first) in indices.iter().enumerate() {
+                for &second in &indices[position + 1..] {
+                    duplicates.push([first, second]);
+                }
+            }
+        }
+        duplicates.sort_unstable();
+        duplicates.dedup();
+        duplicates
+            .into_iter()
+            .map(|[first, second]| [&self.players[first], &self.players[second]])
+            .collect()
+    }
+
+    /// Merges a player into another, keeping the name of the first and the other as an alias
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - ID of the player kept
+    /// * `merge` - ID of the player merged into it
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the players were merged, Err if an ID is unknown or both are the same player
+    pub fn merge(&mut self, keep: &str, merge: &str) -> Result<()> {
+        let position = |id: &str| match self.players.iter().position(|player| player.id == id) {
+            Some(index) => Ok(index),
+            None => match self.get(id) {
+                Some(player) => anyhow::bail!("`{}` was already merged into `{}`", id, player.id),
+                None => anyhow::bail!("Unknown player ID `{}`", id),
+            },
+        };
+        let (keep_index, merge_index) = (position(keep)?, position(merge)?);
+        if keep_index == merge_index {
+            anyhow::bail!("Cannot merge player `{}` into itself", keep);
+        }
+
+        let merged = self.players.remove(merge_index);
+        let keep_index = if merge_index < keep_index {
+            keep_index - 1
+        } else {
+            keep_index
+        };
+        let player = &mut self.players[keep_index];
+        for name in std::iter::once(merged.name).chain(merged.aliases) {
+            if !player.is_named(&name) {
+                player.aliases.push(name);
+            }
+        }
+        if player.reading.is_none() {
+            player.reading = merged.reading;
+        }
+        player.merged_ids.push(merged.id);
+        player.merged_ids.extend(merged.merged_ids);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_names() {
+        let cases = [
+            ("ｻﾝﾌﾟﾙ", "サンプル"),
+            ("ｶﾞｯｺｳ", "ガッコウ"),
+            ("ｳﾞｨｰﾅｽ", "ヴィーナス"),
+            ("山田\u{3000}太郎", "山田 太郎"),
+            ("  山田 \u{3000} 太郎  ", "山田 太郎"),
+            ("ＡＢＣ１２３", "ABC123"),
+            ("ﾟ", "゜"),
+        ];
+        for (name, normalized) in cases {
+            assert_eq!(normalize(name), normalized, "{}", name);
+        }
+    }
+
+    #[test]
+    fn romanize_kana() {
+        let cases = [
+            ("さんぷる", "sanpuru"),
+            ("サンプル", "sanpuru"),
+            ("きょうこ", "kyouko"),
+            ("しょう", "shou"),
+            ("じゅん", "jun"),
+            ("がっこう", "gakkou"),
+            ("まっちゃ", "matcha"),
+            ("ラーメン", "raamen"),
+            ("ふぁん", "fan"),
+            ("太郎", "太郎"),
+        ];
+        for (word, romaji) in cases {
+            assert_eq!(romanize(word), romaji, "{}", word);
+        }
+    }
+
+    #[test]
+    fn shorten_long_vowels() {
+        for word in ["tarou", "taroo", "tarō", "tarô"] {
+            assert_eq!(shorten_vowels(word), "taro", "{}", word);
+        }
+        assert_eq!(shorten_vowels("raamen"), "ramen");
+    }
+
+    #[test]
+    fn likely_duplicates_across_scripts() {
+        let mut registry = Registry::default();
+        for name in [
+            "サンプル 太郎",
+            "sanpuru 太郎",
+            "太郎 サンプル",
+            "サンプル太郎",
+            "Sanpuru Jiro",
+        ] {
+            registry.register(name);
+        }
+
+        let duplicates: Vec<[&str; 2]> = registry
+            .likely_duplicates()
+            .iter()
+            .map(|[first, second]| [first.id.as_str(), second.id.as_str()])
+            .collect();
+        assert_eq!(
+            duplicates,
+            [
+                ["P001", "P002"],
+                ["P001", "P003"],
+                ["P001", "P004"],
+                ["P002", "P003"],
+                ["P002", "P004"],
+                ["P003", "P004"],
+            ]
+        );
+    }
+
+    #[test]
+    fn gojuon_order() {
+        let cases: [&[&str]; 6] = [
+            &["はし", "ばし", "ぱし"],
+            &["かあ", "かー", "かい"],
+            &["ハシ", "はじ", "ひし"],
+            &["きょう", "きよう", "きる"],
+            &["Zed", "あ", "ん", "山"],
+            &["とおる", "とーる", "とかい"],
+        ];
+        for sorted in cases {
+            let mut readings = sorted.to_vec();
+            readings.reverse();
+            readings.sort_by_key(|reading| gojuon_key(reading));
+            assert_eq!(readings, sorted);
+        }
+    }
+
+    #[test]
+    fn long_vowel_mark_takes_the_preceding_vowel() {
+        let cases = [
+            ("かー", "かあ"),
+            ("カード", "かあと"),
+            ("ルーシー", "るうしい"),
+            ("ーあ", "あ"),
+        ];
+        for (reading, primary) in cases {
+            let key = gojuon_key(reading);
+            assert_eq!(key.split('\0').next(), Some(primary), "{}", reading);
+        }
+    }
+
+    #[test]
+    fn player_columns() {
+        let cases = [
+            ("Player1", true, false),
+            ("Player12", true, false),
+            ("Player", false, false),
+            ("Player1Kana", false, true),
+            ("PlayerKana", false, false),
+            ("Pair No1", false, false),
+        ];
+        for (column, player, reading) in cases {
+            assert_eq!(is_player_column(column), player, "{}", column);
+            assert_eq!(is_reading_column(column), reading, "{}", column);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use crate::manifest::Manifest;
+use crate::model::Pair;
+use crate::players::Registry;
+use crate::results::ResultStore;
+use crate::rules::RulesConfig;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the settings file of a tournament project
+pub const CONFIG_FILE: &str = "tournament.toml";
+
+/// Name of the store holding the data of a tournament project
+pub const STORE_FILE: &str = "tournament.json";
+
+/// Settings of a tournament, read from `tournament.toml`
+///
+/// ```toml
+/// name = "KINTO CUP 福岡2024"
+/// template = "sample.svg"
+///
+/// [schedule]
+/// courts = 4
+/// match_duration = 20
+/// start_time = "09:00"
+/// min_rest = 20
+///
+/// [rules.divisions.Open]
+/// points = 15
+/// best_of = 1
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Name of the tournament, printed on every document
+    pub name: String,
+    /// SVG template of the score sheets, relative to the project directory
+    pub template: Option<String>,
+    /// Courts and timing used by `generate schedule`
+    pub schedule: Option<ScheduleConfig>,
+    /// Scoring rules of each division
+    #[serde(default)]
+    pub rules: RulesConfig,
+}
+
+/// Courts and timing of the schedule of a tournament
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Number of courts played on at the same time
+    pub courts: usize,
+    /// Length of a match in minutes, including changeover
+    pub match_duration: u32,
+    /// Start time of the first matches, as `HH:MM`
+    pub start_time: String,
+    /// Minimum rest in minutes between two matches of the same pair
+    #[serde(default)]
+    pub min_rest: u32,
+}
+
+/// Stage of a tournament whose matches are stored in the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Round-robin pool matches
+    Pool,
+    /// Playoff bracket matches
+    Playoff,
+}
+
+/// A match CSV kept in the project store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Table {
+    /// Header of the CSV
+    pub columns: Vec<String>,
+    /// Values of each row, in the order of `columns`
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A document written for the tournament
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    /// What the document is, e.g. `Score sheets`
+    pub kind: String,
+    /// Path of the document
+    pub path: String,
+    /// When the document was last written, in seconds since the Unix epoch
+    pub written_at: u64,
+}
+
+/// Data of a tournament, kept in `tournament.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProjectStore {
+    /// Registered players, each with an ID
+    #[serde(default)]
+    pub players: Registry,
+    /// Registered pairs, strongest first
+    #[serde(default)]
+    pub pairs: Vec<Pair>,
+    /// Round-robin pool matches, with their courts and times once scheduled
+    #[serde(default)]
+    pub pool_matches: Table,
+    /// Playoff bracket matches, with their courts and times once scheduled
+    #[serde(default)]
+    pub playoff_matches: Table,
+    /// Results of every match and recorded coin flips
+    #[serde(flatten)]
+    pub results: ResultStore,
+    /// Documents written for the tournament
+    #[serde(default)]
+    pub documents: Vec<Document>,
+}
+
+/// A tournament project: a directory holding `tournament.toml` and `tournament.json`
+///
+/// Every subcommand given `--project` reads its inputs from the project when they are
+/// not given on the command line, and stores what it generates, so that an event can
+/// be resumed on another day or machine by copying the directory.
+#[derive(Debug)]
+pub struct Project {
+    /// Directory of the project
+    pub dir: PathBuf,
+    /// Settings read from `tournament.toml`
+    pub config: ProjectConfig,
+    /// Data read from `tournament.json`
+    pub store: ProjectStore,
+}
+
+impl Table {
+    /// Builds a table from a header and records
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - Header of the CSV
+    /// * `rows` - Values of each row, in the order of `columns`
+    ///
+    /// # Returns
+    ///
+    /// * `Table` - The table
+    pub fn new(columns: &[impl AsRef<str>], rows: Vec<Vec<String>>) -> Table {
+        Table {
+            columns: columns
+                .iter()
+                .map(|column| column.as_ref().to_string())
+                .collect(),
+            rows,
+        }
+    }
+
+    /// Writes the table as CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - Ok with the bytes of the CSV if successful, Err otherwise
+    pub fn to_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&self.columns)?;
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+impl Stage {
+    /// Returns the name of the stage for messages
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - `pool` or `playoff`
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Pool => "pool",
+            Stage::Playoff => "playoff",
+        }
+    }
+}
+
+impl Project {
+    /// Creates a project directory with a settings file and an empty store
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory of the project; created if missing
+    /// * `name` - Name of the tournament
+    /// * `template` - SVG template copied into the project with its manifest and the fonts the manifest lists, if any
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Project>` - Ok with the new project if successful, Err if a project already exists or a file cannot be written
+    pub fn init(dir: &str, name: &str, template: Option<&str>) -> Result<Project> {
+        let dir = PathBuf::from(dir);
+        if dir.join(CONFIG_FILE).exists() {
+            anyhow::bail!("{} already has a {}", dir.display(), CONFIG_FILE);
+        }
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let mut config_str = format!("name = {}\n", toml::Value::from(name));
+        if let Some(template) = template {
+            let file_name = Path::new(template)
+                .file_name()
+                .with_context(|| format!("Invalid template path {}", template))?;
+            copy(Path::new(template), &dir.join(file_name))?;
+            let manifest = Path::new(template).with_extension("toml");
+            if manifest.exists() {
+                copy(&manifest, &dir.join(file_name).with_extension("toml"))?;
+                // Fonts are found relative to the manifest, so they keep their place next to it;
+                // absolute paths are left as they are
+                let manifest_dir = manifest.parent().unwrap_or(Path::new(""));
+                for font_path in Manifest::load(None, template)?.fonts {
+                    let Ok(relative) = Path::new(&font_path).strip_prefix(manifest_dir) else {
+                        continue;
+                    };
+                    if relative.is_absolute() {
+                        continue;
+                    }
+                    if relative
+                        .components()
+                        .any(|component| component == std::path::Component::ParentDir)
+                    {
+                        anyhow::bail!(
+                            "Font {} is outside the template directory; list it with an absolute path in {}",
+                            font_path,
+                            manifest.display()
+                        );
+                    }
+                    copy(Path::new(&font_path), &dir.join(relative))?;
+                }
+            }
+            config_str.push_str(&format!(
+                "template = {}\n",
+                toml::Value::from(file_name.to_string_lossy().as_ref())
+            ));
+        }
+        config_str.push_str(
+            "\n# Courts and timing used by `generate schedule`\n\
+             # [schedule]\n\
+             # courts = 4\n\
+             # match_duration = 20\n\
+             # start_time = \"09:00\"\n\
+             # min_rest = 20\n\
+             \n\
+             # Scoring rules of the matches without a division, and of each division\n\
+             # [rules.default]\n\
+             # points = 11\n\
+             # win_by = 2\n\
+             # best_of = 3\n",
+        );
+        let config_path = dir.join(CONFIG_FILE);
+        std::fs::write(&config_path, config_str)
+            .with_context(|| format!("Failed to write {}", config_path.display()))?;
+
+        let project = Project::open(&dir.to_string_lossy())?;
+        project.save()?;
+        Ok(project)
+    }
+
+    /// Opens an existing project
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory of the project
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Project>` - Ok with the project if successful, Err if the settings or the store cannot be read
+    pub fn open(dir: &str) -> Result<Project> {
+        let dir = PathBuf::from(dir);
+        let config_path = dir.join(CONFIG_FILE);
+        let config_str = std::fs::read_to_string(&config_path).with_context(|| {
+            format!(
+                "Failed to read {}; create a project with `project init`",
+                config_path.display()
+            )
+        })?;
+        let config: ProjectConfig = toml::from_str(&config_str)
+            .with_context(|| format!("Failed to parse {}", config_path.display()))?;
+        config
+            .rules
+            .check()
+            .with_context(|| format!("Invalid rules in {}", config_path.display()))?;
+
+        let store_path = dir.join(STORE_FILE);
+        let store = if store_path.exists() {
+            let json = std::fs::read_to_string(&store_path)
+                .with_context(|| format!("Failed to read {}", store_path.display()))?;
+            serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse {}", store_path.display()))?
+        } else {
+            ProjectStore::default()
+        };
+
+        Ok(Project { dir, config, store })
+    }
+
+    /// Writes the store, replacing the previous one only once it is fully written
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the store was written, Err otherwise
+    pub fn save(&self) -> Result<()> {
+        let store_path = self.dir.join(STORE_FILE);
+        let temp_path = self.dir.join(format!("{}.tmp", STORE_FILE));
+        let json = serde_json::to_string_pretty(&self.store)?;
+        std::fs::write(&temp_path, json)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &store_path)
+            .with_context(|| format!("Failed to write {}", store_path.display()))
+    }
+
+    /// Returns the path of a file of the project
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Path relative to the project directory
+    ///
+    /// # Returns
+    ///
+    /// * `String` - Path of the file
+    pub fn path(&self, file: &str) -> String {
+        self.dir.join(file).to_string_lossy().into_owned()
+    }
+
+    /// Returns the stored matches of a stage
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - Pool or playoff
+    ///
+    /// # Returns
+    ///
+    /// * `Result<&Table>` - Ok with the matches if the stage was generated, Err otherwise
+    pub fn matches(&self, stage: Stage) -> Result<&Table> {
+        let table = match stage {
+            Stage::Pool => &self.store.pool_matches,
+            Stage::Playoff => &self.store.playoff_matches,
+        };
+        if table.rows.is_empty() {
+            anyhow::bail!(
+                "The project has no {} matches yet; generate them first",
+                stage.name()
+            );
+        }
+        Ok(table)
+    }
+
+    /// Replaces the stored matches of a stage
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - Pool or playoff
+    /// * `table` - New matches
+    pub fn set_matches(&mut self, stage: Stage, table: Table) {
+        match stage {
+            Stage::Pool => self.store.pool_matches = table,
+            Stage::Playoff => self.store.playoff_matches = table,
+        }
+    }
+
+    /// Records that a document was written, replacing an earlier entry with the same path
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - What the document is, e.g. `Score sheets`
+    /// * `path` - Path of the document
+    pub fn record_document(&mut self, kind: &str, path: &str) {
+        let written_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        self.store
+            .documents
+            .retain(|document| document.path != path);
+        self.store.documents.push(Document {
+            kind: kind.to_string(),
+            path: path.to_string(),
+            written_at,
+        });
+    }
+}
+
+/// Copies a file, or a directory with everything in it, into the project
+///
+/// # Arguments
+///
+/// * `from` - File or directory to copy
+/// * `to` - Destination in the project directory; missing parent directories are created
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok if everything was copied, Err otherwise
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        let entries = std::fs::read_dir(from)
+            .with_context(|| format!("Failed to read {}", from.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to read {}", from.display()))?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::copy(from, to)
+        .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an empty temporary directory for a test
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the test
+    ///
+    /// # Returns
+    ///
+    /// * `PathBuf` - Path of the directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pickleball-result-project-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn init_open_and_save_round_trip_the_store() {
+        let dir = temp_dir("round-trip").join("event");
+        let dir_str = dir.to_string_lossy().into_owned();
+
+        let mut project = Project::init(&dir_str, "Spring \"Open\"", None).unwrap();
+        assert_eq!(project.config.name, "Spring \"Open\"");
+        assert!(project.config.template.is_none());
+        assert!(project.matches(Stage::Pool).is_err());
+
+        project.store.pairs.push(Pair {
+            pair_no: "1".to_string(),
+            players: vec!["Alice".to_string(), "Bob".to_string()],
+        });
+        project.set_matches(
+            Stage::Pool,
+            Table::new(
+                &["match_id", "pool"],
+                vec![vec!["A-1-1".into(), "A".into()]],
+            ),
+        );
+        project.record_document("Score sheets", "sheets.pdf");
+        project.record_document("Score sheets", "sheets.pdf");
+        project.save().unwrap();
+        assert!(!dir.join(format!("{}.tmp", STORE_FILE)).exists());
+
+        let reopened = Project::open(&dir_str).unwrap();
+        assert_eq!(reopened.store.pairs, project.store.pairs);
+        let pool = reopened.matches(Stage::Pool).unwrap();
+        assert_eq!(pool.columns, ["match_id", "pool"]);
+        assert_eq!(pool.rows, [["A-1-1", "A"]]);
+        assert_eq!(reopened.store.documents.len(), 1);
+        assert_eq!(reopened.store.documents[0].path, "sheets.pdf");
+
+        assert!(Project::init(&dir_str, "Again", None).is_err());
+    }
+
+    #[test]
+    fn init_copies_the_template_with_its_manifest_and_fonts() {
+        let source = temp_dir("template-source");
+        std::fs::copy("test/sample.svg", source.join("sheet.svg")).unwrap();
+        std::fs::create_dir_all(source.join("fonts")).unwrap();
+        std::fs::copy("test/fonts/Tuffy.ttf", source.join("fonts/Tuffy.ttf")).unwrap();
+        std::fs::write(
+            source.join("sheet.toml"),
+            "cards_per_page = 2\nfonts = [\"fonts\"]\n\n[fields]\nPLAYER1 = \"Player1\"\n",
+        )
+        .unwrap();
+
+        let dir = temp_dir("template-copy").join("event");
+        let dir_str = dir.to_string_lossy().into_owned();
+        let template = source.join("sheet.svg").to_string_lossy().into_owned();
+        let project = Project::init(&dir_str, "Cup", Some(&template)).unwrap();
+        assert_eq!(project.config.template.as_deref(), Some("sheet.svg"));
+
+        let manifest = Manifest::load(None, &project.path("sheet.svg")).unwrap();
+        assert_eq!(manifest.cards_per_page, 2);
+        assert_eq!(
+            manifest.fonts,
+            [dir.join("fonts").to_string_lossy().into_owned()]
+        );
+        assert_eq!(
+            std::fs::read(dir.join("fonts/Tuffy.ttf")).unwrap(),
+            std::fs::read("test/fonts/Tuffy.ttf").unwrap()
+        );
+    }
+
+    #[test]
+    fn init_rejects_fonts_outside_the_template_directory() {
+        let source = temp_dir("template-parent").join("templates");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::copy("test/sample.svg", source.join("sheet.svg")).unwrap();
+        std::fs::write(
+            source.join("sheet.toml"),
+            "cards_per_page = 4\nfonts = [\"../Tuffy.ttf\"]\n\n[fields]\n",
+        )
+        .unwrap();
+
+        let dir = temp_dir("template-parent-copy").join("event");
+        let template = source.join("sheet.svg").to_string_lossy().into_owned();
+        let error = Project::init(&dir.to_string_lossy(), "Cup", Some(&template)).unwrap_err();
+        assert!(
+            error.to_string().contains("outside the template directory"),
+            "{}",
+            error
+        );
+    }
+}
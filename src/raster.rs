@@ -0,0 +1,332 @@
+use crate::timings::Timings;
+use anyhow::{bail, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use svg2pdf::usvg::{self, Group, Node, Rect};
+
+/// Quality of JPEG images, from 1 to 100
+const JPEG_QUALITY: u8 = 90;
+
+/// Resolution at which one SVG user unit is one pixel
+pub const BASE_DPI: f32 = 96.0;
+
+/// Image formats that pages and cards can be rendered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageFormat {
+    /// Lossless PNG, best for text and lines
+    Png,
+    /// JPEG at quality 90, smaller files for sharing
+    Jpeg,
+}
+
+/// How pages are rendered to images
+#[derive(Debug, Clone, Copy)]
+pub struct RasterOptions {
+    /// Format of the images
+    pub format: ImageFormat,
+    /// Pixels per SVG user unit; `dpi / 96`
+    pub scale: f32,
+    /// Write each card as its own image instead of the whole page
+    pub per_card: bool,
+}
+
+impl ImageFormat {
+    /// Returns the file extension of the format
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - Extension without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Renders SVG to an image of the whole page
+///
+/// # Arguments
+///
+/// * `svg_str` - String containing the SVG content
+/// * `options` - usvg options holding the font database
+/// * `raster` - Format and scale of the image
+/// * `timings` - Time spent in each stage
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the bytes of the image if successful, Err otherwise
+pub fn svg_to_image(
+    svg_str: &str,
+    options: &usvg::Options,
+    raster: &RasterOptions,
+    timings: &Timings,
+) -> Result<Vec<u8>> {
+    let tree = timings.measure("parse SVG", || usvg::Tree::from_str(svg_str, options))?;
+    let page = tree
+        .size()
+        .to_rect(0.0, 0.0)
+        .context("The page has no size")?;
+
+    render_region(&tree, page, raster, timings)
+}
+
+/// Renders SVG to one image per card, each cropped to its card
+///
+/// A card is the smallest group of the template that holds every element of the card
+/// found in the page, such as the `<g>` that Figma makes of a frame.
+///
+/// # Arguments
+///
+/// * `svg_str` - String containing the SVG content
+/// * `options` - usvg options holding the font database
+/// * `cards` - Element IDs of each card to render
+/// * `raster` - Format and scale of the images
+/// * `timings` - Time spent in each stage
+///
+/// # Returns
+///
+/// * `Result<Vec<Vec<u8>>>` - Ok with the bytes of the image of each card if successful, Err otherwise
+pub fn svg_to_card_images(
+    svg_str: &str,
+    options: &usvg::Options,
+    cards: &[Vec<String>],
+    raster: &RasterOptions,
+    timings: &Timings,
+) -> Result<Vec<Vec<u8>>> {
+    let tree = timings.measure("parse SVG", || usvg::Tree::from_str(svg_str, options))?;
+
+    cards
+        .iter()
+        .enumerate()
+        .map(|(card_index, ids)| {
+            let ids: Vec<&str> = ids
+                .iter()
+                .map(String::as_str)
+                .filter(|id| tree.node_by_id(id).is_some())
+                .collect();
+            if ids.is_empty() {
+                bail!("Card {} has no elements in the template", card_index + 1);
+            }
+            let card = enclosing_group(tree.root(), &ids)
+                .and_then(|group| group.abs_stroke_bounding_box().to_non_zero_rect())
+                .with_context(|| format!("Card {} has no group of its own", card_index + 1))?;
+
+            render_region(&tree, card.to_rect(), raster, timings)
+        })
+        .collect()
+}
+
+/// Renders a region of a tree on a white background and encodes it
+///
+/// # Arguments
+///
+/// * `tree` - Parsed SVG
+/// * `region` - Region to render, in user units
+/// * `raster` - Format and scale of the image
+/// * `timings` - Time spent in each stage
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the bytes of the image if successful, Err otherwise
+fn render_region(
+    tree: &usvg::Tree,
+    region: Rect,
+    raster: &RasterOptions,
+    timings: &Timings,
+) -> Result<Vec<u8>> {
+    let width = (region.width() * raster.scale).ceil() as u32;
+    let height = (region.height() * raster.scale).ceil() as u32;
+    let mut pixmap = Pixmap::new(width, height)
+        .with_context(|| format!("Invalid image size {}x{}", width, height))?;
+
+    timings.measure("rasterize", || {
+        pixmap.fill(Color::WHITE);
+        let transform = Transform::from_scale(raster.scale, raster.scale)
+            .pre_translate(-region.x(), -region.y());
+        resvg::render(tree, transform, &mut pixmap.as_mut());
+    });
+
+    timings.measure("encode image", || encode(&pixmap, raster.format))
+}
+
+/// Encodes an opaque pixmap
+///
+/// # Arguments
+///
+/// * `pixmap` - Rendered pixels on an opaque background
+/// * `format` - Format of the image
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the bytes of the image if successful, Err otherwise
+fn encode(pixmap: &Pixmap, format: ImageFormat) -> Result<Vec<u8>> {
+    // The background is opaque, so the premultiplied pixels are plain RGB
+    let rgb: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| [pixel.red(), pixel.green(), pixel.blue()])
+        .collect();
+    let image = RgbImage::from_raw(pixmap.width(), pixmap.height(), rgb)
+        .context("Failed to convert the rendered pixels")?;
+
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Png => PngEncoder::new(&mut bytes).write_image(
+            &image,
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgb8,
+        ),
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).write_image(
+            &image,
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgb8,
+        ),
+    }
+    .context("Failed to encode image")?;
+
+    Ok(bytes)
+}
+
+/// Finds the smallest group that holds every given element
+///
+/// # Arguments
+///
+/// * `group` - Group to search
+/// * `ids` - IDs of the elements
+///
+/// # Returns
+///
+/// * `Option<&Group>` - The innermost group holding every element, None if `group` lacks one of them
+fn enclosing_group<'a>(group: &'a Group, ids: &[&str]) -> Option<&'a Group> {
+    if !ids.iter().all(|id| contains_id(group, id)) {
+        return None;
+    }
+
+    group
+        .children()
+        .iter()
+        .find_map(|child| match child {
+            Node::Group(child) => enclosing_group(child, ids),
+            _ => None,
+        })
+        .or(Some(group))
+}
+
+/// Checks whether a group holds an element
+///
+/// # Arguments
+///
+/// * `group` - Group to search
+/// * `id` - ID of the element
+///
+/// # Returns
+///
+/// * `bool` - True if the element is the group or one of its descendants
+fn contains_id(group: &Group, id: &str) -> bool {
+    group.id() == id
+        || group.children().iter().any(|child| match child {
+            Node::Group(child) => contains_id(child, id),
+            _ => child.id() == id,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two cards drawn as groups of rectangles inside a page group, the way Figma exports frames
+    const CARDS_SVG: &str = r##"<svg width="400" height="200" xmlns="http://www.w3.org/2000/svg">
+        <g id="page">
+            <g id="card">
+                <rect id="NAME" x="10" y="20" width="100" height="50" fill="#000000" />
+                <rect id="COURT" x="10" y="80" width="30" height="10" fill="#ff0000" />
+            </g>
+            <g id="card_2">
+                <rect id="NAME_2" x="210" y="20" width="150" height="30" fill="#000000" />
+                <rect id="COURT_2" x="210" y="60" width="30" height="10" fill="#ff0000" />
+            </g>
+        </g>
+    </svg>"##;
+
+    /// Returns the IDs of the elements of each card in `CARDS_SVG`
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` - IDs of each card
+    fn card_ids() -> Vec<Vec<String>> {
+        vec![
+            vec!["NAME".to_string(), "COURT".to_string()],
+            vec!["NAME_2".to_string(), "COURT_2".to_string()],
+        ]
+    }
+
+    #[test]
+    fn cards_are_found_in_their_innermost_group() {
+        let tree = usvg::Tree::from_str(CARDS_SVG, &usvg::Options::default()).unwrap();
+        let root = tree.root();
+
+        assert!(contains_id(root, "COURT_2"));
+        assert!(!contains_id(root, "COURT_3"));
+        assert_eq!(
+            enclosing_group(root, &["NAME", "COURT"]).unwrap().id(),
+            "card"
+        );
+        assert_eq!(enclosing_group(root, &["NAME_2"]).unwrap().id(), "card_2");
+        assert_eq!(
+            enclosing_group(root, &["NAME", "NAME_2"]).unwrap().id(),
+            "page"
+        );
+        assert!(enclosing_group(root, &["NAME", "MISSING"]).is_none());
+    }
+
+    #[test]
+    fn card_images_are_cropped_to_the_card() {
+        let raster = RasterOptions {
+            format: ImageFormat::Png,
+            scale: 1.5,
+            per_card: true,
+        };
+        let images = svg_to_card_images(
+            CARDS_SVG,
+            &usvg::Options::default(),
+            &card_ids(),
+            &raster,
+            &Timings::default(),
+        )
+        .unwrap();
+        assert_eq!(images.len(), 2);
+
+        // Card 1 spans 100 x 70 user units and card 2 150 x 50, scaled by 1.5
+        let first = image::load_from_memory(&images[0]).unwrap().to_rgb8();
+        assert_eq!(first.dimensions(), (150, 105));
+        assert_eq!(first.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(first.get_pixel(149, 104).0, [255, 255, 255]);
+        assert_eq!(first.get_pixel(0, 104).0, [255, 0, 0]);
+
+        let second = image::load_from_memory(&images[1]).unwrap();
+        assert_eq!((second.width(), second.height()), (225, 75));
+    }
+
+    #[test]
+    fn cards_without_elements_are_rejected() {
+        let raster = RasterOptions {
+            format: ImageFormat::Jpeg,
+            scale: 1.0,
+            per_card: true,
+        };
+        let error = svg_to_card_images(
+            CARDS_SVG,
+            &usvg::Options::default(),
+            &[card_ids()[0].clone(), vec!["NAME_3".to_string()]],
+            &raster,
+            &Timings::default(),
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Card 2 has no elements in the template");
+    }
+}
@@ -0,0 +1,883 @@
+use crate::bracket;
+use crate::fit::TextFitter;
+use crate::fonts::{self, FontOptions};
+use crate::manifest::Manifest;
+use crate::model::Row;
+use crate::pdf::{self, Page};
+use crate::players::{self, Registry};
+use crate::raster::{self, RasterOptions};
+use crate::rules::{RulesConfig, DIVISION_COLUMN, RULES_COLUMN};
+use crate::template::{card_index_of, card_slot_id, expand_placeholders, Template};
+use crate::timings::Timings;
+use crate::validate::{self, ValidateOptions, Validated};
+use anyhow::{bail, Context, Result};
+use csv::Reader;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use svg2pdf::usvg;
+
+/// A score sheet template loaded with its card layout and fonts, ready to render pages
+///
+/// Loading is the slow part (scanning font directories, parsing the template), so a
+/// score sheet is loaded once and shared read-only by every page, including across
+/// rayon workers.
+pub struct ScoreSheet {
+    /// Parsed master SVG template
+    template: Template,
+    /// Card layout of the template
+    manifest: Manifest,
+    /// usvg options holding the font database and font fallbacks
+    options: usvg::Options<'static>,
+    /// Fits long names into their text box
+    fitter: TextFitter,
+    /// Families to try, in order, when a font family is missing or lacks a glyph
+    fallbacks: HashMap<String, Vec<String>>,
+    /// Time spent in each stage
+    timings: Timings,
+}
+
+/// How the rows of a match CSV are read for a score sheet
+pub struct DataOptions<'a> {
+    /// CSV columns renamed to the template field they fill
+    pub rename_columns: &'a [(String, String)],
+    /// Pad short rows with blank fields and report blank names as warnings instead of errors
+    pub tolerant: bool,
+    /// Scoring rules of each division, printed in the `{{Rules}}` field
+    pub rules: &'a RulesConfig,
+    /// Registry whose names replace player IDs, if any
+    pub players: Option<&'a Registry>,
+}
+
+/// Score sheets to render for a list of matches
+pub struct Job<'a> {
+    /// Rows of the matches, one card each, as in [`Validated::rows`] from [`ScoreSheet::read_rows`]
+    pub rows: &'a [Row],
+    /// Name of the tournament
+    pub tournament_name: &'a str,
+    /// Base path of the output files: `{output_path}_{page}.pdf`, `{output_path}.pdf` when
+    /// merged, or `{output_path}_{page}.png` and `{output_path}_{page}_{card}.png` for images
+    pub output_path: &'a str,
+    /// Write every page into a single PDF with one bookmark per page
+    pub merge: bool,
+    /// Print page numbers in the footer of the merged PDF
+    pub page_numbers: bool,
+    /// Write images instead of PDFs
+    pub image: Option<RasterOptions>,
+}
+
+impl ScoreSheet {
+    /// Loads an SVG template, its manifest and the fonts it needs
+    ///
+    /// # Arguments
+    ///
+    /// * `svg_path` - Path to the master SVG template
+    /// * `manifest_path` - Path to the manifest; None uses the SVG path with a `.toml` extension if it exists, or the layout of `test/sample.svg`
+    /// * `font_options` - Fonts loaded after those of the manifest, and whether to load system fonts
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ScoreSheet>` - Ok with the score sheet if successful, Err otherwise
+    pub fn load(
+        svg_path: &str,
+        manifest_path: Option<&str>,
+        font_options: &FontOptions,
+    ) -> Result<ScoreSheet> {
+        let timings = Timings::default();
+        let template = timings.measure("parse template", || {
+            let master_svg_str =
+                std::fs::read_to_string(svg_path).context("Failed to read SVG file")?;
+            Template::parse(&master_svg_str)
+        })?;
+        let manifest = Manifest::load(manifest_path, svg_path)?;
+
+        ScoreSheet::build(template, manifest, font_options, timings)
+    }
+
+    /// Builds a score sheet from a template and a manifest already in memory
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - Parsed master SVG template
+    /// * `manifest` - Card layout of the template
+    /// * `font_options` - Fonts loaded after those of the manifest, and whether to load system fonts
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ScoreSheet>` - Ok with the score sheet if successful, Err otherwise
+    pub fn new(
+        template: Template,
+        manifest: Manifest,
+        font_options: &FontOptions,
+    ) -> Result<ScoreSheet> {
+        ScoreSheet::build(template, manifest, font_options, Timings::default())
+    }
+
+    /// Applies the text widths of the manifest and loads the fonts
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - Parsed master SVG template
+    /// * `manifest` - Card layout of the template
+    /// * `font_options` - Fonts loaded after those of the manifest
+    /// * `timings` - Time spent in each stage so far
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ScoreSheet>` - Ok with the score sheet if successful, Err otherwise
+    fn build(
+        mut template: Template,
+        manifest: Manifest,
+        font_options: &FontOptions,
+        timings: Timings,
+    ) -> Result<ScoreSheet> {
+        for (id, max_width) in &manifest.max_widths {
+            for card_index in 0..manifest.cards_per_page {
+                template.set_max_width(&card_slot_id(id, card_index), *max_width)?;
+            }
+        }
+
+        // Scanning the system font directories is slow, so it is done once for all pages
+        let font_options = manifest.font_options(font_options);
+        let options = timings.measure("load fonts", || fonts::load_options(&font_options))?;
+        fonts::check_families(
+            &options.fontdb,
+            &font_options.fallbacks,
+            template.font_families(),
+        )?;
+        let fitter = TextFitter::new(
+            options.fontdb.clone(),
+            font_options.fallbacks.clone(),
+            manifest.min_font_size,
+        );
+
+        Ok(ScoreSheet {
+            template,
+            manifest,
+            options,
+            fitter,
+            fallbacks: font_options.fallbacks,
+            timings,
+        })
+    }
+
+    /// Returns the card layout of the template
+    ///
+    /// # Returns
+    ///
+    /// * `&Manifest` - The manifest
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Returns the fitter, which remembers the names squeezed below the minimum font size
+    ///
+    /// # Returns
+    ///
+    /// * `&TextFitter` - The fitter
+    pub fn fitter(&self) -> &TextFitter {
+        &self.fitter
+    }
+
+    /// Returns the time spent in each stage of loading and rendering so far
+    ///
+    /// # Returns
+    ///
+    /// * `&Timings` - The timings
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Returns the columns the template reads from every row
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - Columns of the manifest fields and `{{Column}}` placeholders, sorted
+    pub fn required_columns(&self) -> Vec<String> {
+        let mut required_columns: Vec<String> = self.manifest.fields.values().cloned().collect();
+        required_columns.extend(self.template.placeholder_columns());
+        required_columns.sort_unstable();
+        required_columns.dedup();
+        required_columns
+    }
+
+    /// Reads and validates the rows of a match CSV, completing them for the template
+    ///
+    /// Rows without a `Rules` value get the rules of their division, and player IDs are
+    /// replaced by the names and readings of the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - CSV reader, e.g. over [`crate::input::open`]
+    /// * `options` - Column renames, tolerance, rules and players
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Validated>` - Ok with the rows in CSV order and the tolerated problems if valid, Err listing every problem otherwise
+    pub fn read_rows<R: Read>(
+        &self,
+        reader: &mut Reader<R>,
+        options: &DataOptions,
+    ) -> Result<Validated> {
+        let required_columns = self.required_columns();
+        let mut validated = self.timings.measure("read CSV", || {
+            validate::read_rows(
+                reader,
+                &ValidateOptions {
+                    // The rules and readings are filled in below when the CSV does not have them
+                    required_columns: &required_columns
+                        .iter()
+                        .filter(|column| {
+                            *column != RULES_COLUMN && !players::is_reading_column(column)
+                        })
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    rename_columns: options.rename_columns,
+                    tolerant: options.tolerant,
+                    is_pending_pair: Some(bracket::is_entrant_label),
+                },
+            )
+        })?;
+
+        for row in &mut validated.rows {
+            if row.get(RULES_COLUMN).is_none_or(String::is_empty) {
+                let division = row.get(DIVISION_COLUMN).map_or("", String::as_str);
+                let description = options.rules.for_division(division).describe();
+                row.insert(RULES_COLUMN.to_string(), description);
+            }
+            let player_columns: Vec<String> = row
+                .keys()
+                .filter(|column| validate::is_player_column(column))
+                .cloned()
+                .collect();
+            for column in player_columns {
+                let reading_column = players::reading_column(&column);
+                if row.get(&reading_column).is_none_or(String::is_empty) {
+                    let reading = options
+                        .players
+                        .and_then(|registry| registry.reading(&row[&column]))
+                        .unwrap_or_default()
+                        .to_string();
+                    row.insert(reading_column, reading);
+                }
+                if let Some(registry) = options.players {
+                    let name = registry.resolve(&row[&column]);
+                    row.insert(column, name);
+                }
+            }
+        }
+
+        Ok(validated)
+    }
+
+    /// Checks that the loaded fonts can render every text printed on the score sheets
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the matches
+    /// * `tournament_name` - Name of the tournament
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if every character has a font, Err listing the missing characters otherwise
+    pub fn check_glyphs(&self, rows: &[Row], tournament_name: &str) -> Result<()> {
+        self.timings.measure("check glyphs", || {
+            let pages = rows
+                .chunks(self.manifest.cards_per_page)
+                .map(|rows| slot_values(&self.template, &self.manifest, rows, tournament_name))
+                .collect::<Result<Vec<_>>>()?;
+            let texts = pages.iter().flat_map(|(values, ruby)| {
+                values
+                    .iter()
+                    .chain(ruby)
+                    .filter_map(|(id, text)| Some((self.template.font_family(id)?, text.as_str())))
+            });
+            fonts::check_glyphs(&self.options.fontdb, &self.fallbacks, texts)
+        })
+    }
+
+    /// Renders one page of score sheets as SVG
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - Ok with the SVG of the page if successful, Err otherwise
+    pub fn render_svg(&self, rows: &[Row], tournament_name: &str) -> Result<String> {
+        self.timings.measure("fill template", || {
+            replace_svg(
+                &self.template,
+                &self.manifest,
+                &self.fitter,
+                rows,
+                tournament_name,
+            )
+        })
+    }
+
+    /// Renders one page of score sheets as a page to merge with others
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Page>` - Ok with the page if successful, Err otherwise
+    pub fn render_page(&self, rows: &[Row], tournament_name: &str) -> Result<Page> {
+        let svg = self.render_svg(rows, tournament_name)?;
+        pdf::svg_to_page(&svg, &self.options, &self.timings)
+    }
+
+    /// Renders one page of score sheets as a PDF
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - Ok with the bytes of the PDF if successful, Err otherwise
+    pub fn render_pdf(&self, rows: &[Row], tournament_name: &str) -> Result<Vec<u8>> {
+        let svg = self.render_svg(rows, tournament_name)?;
+        pdf::svg_to_pdf(&svg, &self.options, &self.timings)
+    }
+
+    /// Renders one page of score sheets as a PDF and writes it to a writer
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    /// * `writer` - Destination of the PDF, such as a file, stdout or a response body
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the PDF was written, Err otherwise
+    pub fn write_pdf<W: Write>(
+        &self,
+        rows: &[Row],
+        tournament_name: &str,
+        mut writer: W,
+    ) -> Result<()> {
+        let pdf = self.render_pdf(rows, tournament_name)?;
+        self.timings
+            .measure("write PDF", || {
+                writer.write_all(&pdf).and_then(|_| writer.flush())
+            })
+            .context("Failed to write PDF")
+    }
+
+    /// Renders every page in parallel into a single PDF with one bookmark per page
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the matches, one card each
+    /// * `tournament_name` - Name of the tournament
+    /// * `page_numbers` - Print page numbers in the footer
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - Ok with the bytes of the PDF if successful, Err otherwise
+    pub fn render_merged(
+        &self,
+        rows: &[Row],
+        tournament_name: &str,
+        page_numbers: bool,
+    ) -> Result<Vec<u8>> {
+        let pages = rows
+            .par_chunks(self.manifest.cards_per_page)
+            .map(|chunk| self.render_page(chunk, tournament_name))
+            .collect::<Result<Vec<_>>>()?;
+        let titles: Vec<String> = (1..=pages.len())
+            .map(|group| format!("Group {}", group))
+            .collect();
+
+        Ok(self.timings.measure("merge pages", || {
+            pdf::merge_pages(pages, &titles, page_numbers)
+        }))
+    }
+
+    /// Renders every page into a single PDF and writes it to a writer
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the matches, one card each
+    /// * `tournament_name` - Name of the tournament
+    /// * `page_numbers` - Print page numbers in the footer
+    /// * `writer` - Destination of the PDF, such as a file, stdout or a response body
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the PDF was written, Err otherwise
+    pub fn write_merged<W: Write>(
+        &self,
+        rows: &[Row],
+        tournament_name: &str,
+        page_numbers: bool,
+        mut writer: W,
+    ) -> Result<()> {
+        let pdf = self.render_merged(rows, tournament_name, page_numbers)?;
+        self.timings
+            .measure("write PDF", || {
+                writer.write_all(&pdf).and_then(|_| writer.flush())
+            })
+            .context("Failed to write PDF")
+    }
+
+    /// Renders one page of score sheets as an image
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    /// * `raster` - Format and scale of the image
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - Ok with the bytes of the image if successful, Err otherwise
+    pub fn render_image(
+        &self,
+        rows: &[Row],
+        tournament_name: &str,
+        raster: &RasterOptions,
+    ) -> Result<Vec<u8>> {
+        let svg = self.render_svg(rows, tournament_name)?;
+        raster::svg_to_image(&svg, &self.options, raster, &self.timings)
+    }
+
+    /// Renders each card of one page as its own image, cropped to the card
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows of the cards on the page, at most `cards_per_page` of the manifest
+    /// * `tournament_name` - Name of the tournament
+    /// * `raster` - Format and scale of the images
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Vec<u8>>>` - Ok with the bytes of the image of each row if successful, Err otherwise
+    pub fn render_card_images(
+        &self,
+        rows: &[Row],
+        tournament_name: &str,
+        raster: &RasterOptions,
+    ) -> Result<Vec<Vec<u8>>> {
+        let cards: Vec<Vec<String>> = (0..rows.len())
+            .map(|card_index| {
+                self.manifest
+                    .fields
+                    .keys()
+                    .chain(&self.manifest.tournament_name_id)
+                    .map(|id| card_slot_id(id, card_index))
+                    .collect()
+            })
+            .collect();
+
+        let svg = self.render_svg(rows, tournament_name)?;
+        raster::svg_to_card_images(&svg, &self.options, &cards, raster, &self.timings)
+    }
+
+    /// Renders every page of a job in parallel and writes the PDF or image files
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Rows, tournament name and output of the score sheets
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if every file was written, Err otherwise
+    pub fn render_job(&self, job: &Job) -> Result<()> {
+        if let Some(raster) = &job.image {
+            if job.merge {
+                bail!("Images cannot be merged; write one image per page or per card");
+            }
+            return self.render_images(job, raster);
+        }
+
+        if job.merge {
+            let pdf = self.render_merged(job.rows, job.tournament_name, job.page_numbers)?;
+            let output_path = format!("{}.pdf", job.output_path);
+            return self
+                .timings
+                .measure("write PDF", || std::fs::write(&output_path, pdf))
+                .with_context(|| format!("Failed to write {}", output_path));
+        }
+
+        job.rows
+            .par_chunks(self.manifest.cards_per_page)
+            .enumerate()
+            .try_for_each(|(group_index, chunk)| {
+                let pdf = self.render_pdf(chunk, job.tournament_name)?;
+                let output_path = format!("{}_{}.pdf", job.output_path, group_index);
+                self.timings
+                    .measure("write PDF", || std::fs::write(&output_path, pdf))
+                    .with_context(|| format!("Failed to write {}", output_path))
+            })
+    }
+
+    /// Renders every page of a job in parallel and writes an image of each page or card
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Rows, tournament name and output of the score sheets
+    /// * `raster` - Format, scale and cropping of the images
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if every file was written, Err otherwise
+    fn render_images(&self, job: &Job, raster: &RasterOptions) -> Result<()> {
+        let extension = raster.format.extension();
+
+        job.rows
+            .par_chunks(self.manifest.cards_per_page)
+            .enumerate()
+            .try_for_each(|(group_index, chunk)| {
+                let images = if raster.per_card {
+                    self.render_card_images(chunk, job.tournament_name, raster)?
+                        .into_iter()
+                        .enumerate()
+                        .map(|(card_index, image)| {
+                            let path = format!(
+                                "{}_{}_{}.{}",
+                                job.output_path, group_index, card_index, extension
+                            );
+                            (path, image)
+                        })
+                        .collect()
+                } else {
+                    let image = self.render_image(chunk, job.tournament_name, raster)?;
+                    vec![(
+                        format!("{}_{}.{}", job.output_path, group_index, extension),
+                        image,
+                    )]
+                };
+
+                images.into_iter().try_for_each(|(output_path, image)| {
+                    self.timings
+                        .measure("write image", || std::fs::write(&output_path, image))
+                        .with_context(|| format!("Failed to write {}", output_path))
+                })
+            })
+    }
+}
+
+/// Fills the text elements of the SVG template with player names and tournament name
+///
+/// # Arguments
+///
+/// * `template` - Parsed SVG template
+/// * `manifest` - Card layout of the template
+/// * `fitter` - Fits long names into their text box
+/// * `player_groups` - Slice of HashMaps containing player information
+/// * `tournament_name` - Name of the tournament
+///
+/// # Returns
+///
+/// * `Result<String>` - Ok with modified SVG string if successful, Err otherwise
+fn replace_svg(
+    template: &Template,
+    manifest: &Manifest,
+    fitter: &TextFitter,
+    player_groups: &[HashMap<String, String>],
+    tournament_name: &str,
+) -> Result<String> {
+    let (values, ruby) = slot_values(template, manifest, player_groups, tournament_name)?;
+    template.fill(&values, &ruby, Some(fitter))
+}
+
+/// Returns the text printed in each text element of one page, and the ruby above it
+///
+/// Each card exposes the element IDs declared in the manifest, suffixed per card as
+/// described in [`card_slot_id`]. Other text elements may use any CSV column as a
+/// `{{Column}}` placeholder, filled from the row of the card the element's ID belongs to.
+/// Cards without a player group are left blank.
+///
+/// # Arguments
+///
+/// * `template` - Parsed SVG template
+/// * `manifest` - Card layout of the template
+/// * `player_groups` - Slice of HashMaps containing player information
+/// * `tournament_name` - Name of the tournament
+///
+/// # Returns
+///
+/// * `Result<(HashMap<String, String>, HashMap<String, String>)>` - Ok with the text and the ruby keyed by element ID if successful, Err otherwise
+fn slot_values(
+    template: &Template,
+    manifest: &Manifest,
+    player_groups: &[HashMap<String, String>],
+    tournament_name: &str,
+) -> Result<(HashMap<String, String>, HashMap<String, String>)> {
+    if player_groups.is_empty() || player_groups.len() > manifest.cards_per_page {
+        anyhow::bail!("Invalid player groups: {:?}", player_groups);
+    }
+
+    let mut values = HashMap::new();
+    let mut ruby = HashMap::new();
+
+    for card_index in 0..manifest.cards_per_page {
+        let group = player_groups.get(card_index);
+
+        // Embed player names and pair numbers into SVG
+        for (id, column) in &manifest.fields {
+            let value = match group {
+                Some(group) => group
+                    .get(column)
+                    .cloned()
+                    .with_context(|| format!("Missing column `{}` in {:?}", column, group))?,
+                None => String::new(),
+            };
+            values.insert(card_slot_id(id, card_index), value);
+        }
+
+        // Embed readings printed as ruby above their fields
+        for (id, column) in &manifest.ruby {
+            if let Some(reading) = group.and_then(|group| group.get(column)) {
+                ruby.insert(card_slot_id(id, card_index), reading.clone());
+            }
+        }
+
+        // Embed tournament name into SVG
+        if let Some(id) = &manifest.tournament_name_id {
+            values.insert(card_slot_id(id, card_index), tournament_name.to_string());
+        }
+    }
+
+    // Embed other CSV columns into `{{Column}}` placeholders
+    for (id, text) in template.placeholder_slots() {
+        if values.contains_key(id) {
+            continue;
+        }
+        let group = player_groups.get(card_index_of(id, manifest.cards_per_page));
+        let value = expand_placeholders(text, group)
+            .with_context(|| format!("Failed to fill text element `{}`", id))?;
+        values.insert(id.to_string(), value);
+    }
+
+    Ok((values, ruby))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use svg2pdf::usvg::fontdb;
+
+    /// Builds a row from column and value pairs
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Column and value of each field
+    ///
+    /// # Returns
+    ///
+    /// * `Row` - The row
+    fn row(entries: &[(&str, &str)]) -> Row {
+        entries
+            .iter()
+            .map(|(column, value)| (column.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_filled_from_the_row_of_their_card() {
+        let template = Template::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text id="PLAYER1">P</text><text id="COURT">Court {{Court}}</text><text id="PLAYER1_2">P</text><text id="COURT_2">Court {{Court}}</text><text id="PLAYER1_3">P</text><text id="COURT_3">Court {{Court}}</text></svg>"#,
+        )
+        .unwrap();
+        let manifest = Manifest {
+            cards_per_page: 3,
+            tournament_name_id: None,
+            fields: BTreeMap::from([("PLAYER1".to_string(), "Player1".to_string())]),
+            ..Manifest::default()
+        };
+        let fitter = TextFitter::new(Arc::new(fontdb::Database::new()), HashMap::new(), 8.0);
+        let rows = [
+            row(&[("Player1", "Ann"), ("Court", "1")]),
+            row(&[("Player1", "Ben"), ("Court", "2")]),
+        ];
+
+        let svg = replace_svg(&template, &manifest, &fitter, &rows, "Cup").unwrap();
+        assert_eq!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text id="PLAYER1">Ann</text><text id="COURT">Court 1</text><text id="PLAYER1_2">Ben</text><text id="COURT_2">Court 2</text><text id="PLAYER1_3"></text><text id="COURT_3">Court </text></svg>"#
+        );
+    }
+
+    /// Loads `test/sample.svg` with the bundled test font standing in for Inter
+    ///
+    /// # Returns
+    ///
+    /// * `ScoreSheet` - The score sheet
+    fn sample_sheet() -> ScoreSheet {
+        ScoreSheet::load(
+            "test/sample.svg",
+            None,
+            &FontOptions {
+                system_fonts: false,
+                font_paths: vec!["test/fonts/Tuffy.ttf".to_string()],
+                fallbacks: HashMap::from([("Inter".to_string(), vec!["Tuffy".to_string()])]),
+            },
+        )
+        .unwrap()
+    }
+
+    /// Reads the first rows of `test/matches.csv` for `test/sample.svg`
+    ///
+    /// # Arguments
+    ///
+    /// * `sheet` - Score sheet of `test/sample.svg`
+    /// * `count` - Number of rows to keep
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Row>` - The rows
+    fn sample_rows(sheet: &ScoreSheet, count: usize) -> Vec<Row> {
+        let mut reader = csv::Reader::from_path("test/matches.csv").unwrap();
+        let mut rows = sheet
+            .read_rows(
+                &mut reader,
+                &DataOptions {
+                    rename_columns: &[],
+                    tolerant: false,
+                    rules: &RulesConfig::default(),
+                    players: None,
+                },
+            )
+            .unwrap()
+            .rows;
+        rows.truncate(count);
+        rows
+    }
+
+    #[test]
+    fn fonts_and_template_are_loaded_once_for_every_page() {
+        let sheet = sample_sheet();
+        let rows = sample_rows(&sheet, 9);
+
+        for chunk in rows.chunks(sheet.manifest().cards_per_page) {
+            sheet.render_page(chunk, "KINTO CUP").unwrap();
+        }
+        let timings = sheet.timings();
+        assert_eq!(timings.calls("parse template"), 1);
+        assert_eq!(timings.calls("load fonts"), 1);
+        assert_eq!(timings.calls("fill template"), 3);
+        assert_eq!(timings.calls("parse SVG"), 3);
+    }
+
+    #[test]
+    fn pdfs_are_written_into_any_writer() {
+        let sheet = sample_sheet();
+        let rows = sample_rows(&sheet, 9);
+
+        let mut page = Vec::new();
+        sheet.write_pdf(&rows[..4], "KINTO CUP", &mut page).unwrap();
+        assert!(page.starts_with(b"%PDF-"));
+        assert_eq!(
+            lopdf::Document::load_mem(&page).unwrap().get_pages().len(),
+            1
+        );
+
+        let mut merged = Vec::new();
+        sheet
+            .write_merged(&rows, "KINTO CUP", true, &mut merged)
+            .unwrap();
+        assert!(merged.starts_with(b"%PDF-"));
+        assert_eq!(
+            lopdf::Document::load_mem(&merged)
+                .unwrap()
+                .get_pages()
+                .len(),
+            rows.len().div_ceil(sheet.manifest().cards_per_page)
+        );
+    }
+
+    #[test]
+    fn glyphs_are_checked_in_the_font_of_their_element() {
+        let sheet = sample_sheet();
+        let mut rows = sample_rows(&sheet, 4);
+        for row in &mut rows {
+            for column in ["Player1", "Player2", "Player3", "Player4"] {
+                row.insert(column.to_string(), "Ann".to_string());
+            }
+        }
+        sheet.check_glyphs(&rows, "KINTO CUP").unwrap();
+
+        rows[1].insert("Player1".to_string(), "山田".to_string());
+        let error = sheet
+            .check_glyphs(&rows, "KINTO CUP")
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with(
+            ":\n'山' (U+5C71) in `山田`, font-family `Inter`\n\
+             '田' (U+7530) in `山田`, font-family `Inter`"
+        ));
+    }
+
+    #[test]
+    fn score_sheets_of_the_sample_data_are_written_to_files() {
+        let sheet = sample_sheet();
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path("test/data.csv")
+            .unwrap();
+        let Validated { rows, warnings, .. } = sheet
+            .read_rows(
+                &mut reader,
+                &DataOptions {
+                    rename_columns: &[],
+                    tolerant: true,
+                    rules: &RulesConfig::default(),
+                    players: None,
+                },
+            )
+            .unwrap();
+        assert!(rows.len() > 300);
+        assert!(!warnings.is_empty());
+        // Three pages are enough to tell pages and merged files apart
+        let rows = &rows[..9];
+
+        let dir = std::env::temp_dir().join("pickleball-result-render-sample-data");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let output_path = dir.join("out").to_string_lossy().into_owned();
+        let pages = rows.len().div_ceil(sheet.manifest().cards_per_page);
+        for merge in [false, true] {
+            sheet
+                .render_job(&Job {
+                    rows,
+                    tournament_name: "KINTO CUP",
+                    output_path: &output_path,
+                    merge,
+                    page_numbers: merge,
+                    image: None,
+                })
+                .unwrap();
+        }
+
+        for page in 0..pages {
+            let pdf = std::fs::read(format!("{}_{}.pdf", output_path, page)).unwrap();
+            assert_eq!(
+                lopdf::Document::load_mem(&pdf).unwrap().get_pages().len(),
+                1
+            );
+        }
+        assert!(!std::path::Path::new(&format!("{}_{}.pdf", output_path, pages)).exists());
+        let merged = std::fs::read(format!("{}.pdf", output_path)).unwrap();
+        assert_eq!(
+            lopdf::Document::load_mem(&merged)
+                .unwrap()
+                .get_pages()
+                .len(),
+            pages
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use crate::bracket::{self, Outcome};
+use crate::model::Pair;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Result of a played match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchResult {
+    /// Pool, or bracket side, the match belongs to
+    pub pool: String,
+    /// One-based round number within the pool
+    pub round: usize,
+    /// The two pairs, in the order of the match CSV
+    pub pairs: [Pair; 2],
+    /// Points of each game, in the order of `pairs`
+    pub games: Vec<[u32; 2]>,
+}
+
+/// Results of every imported match, keyed by match ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResultStore {
+    /// Results keyed by match ID
+    pub results: BTreeMap<String, MatchResult>,
+    /// Orders decided by coin flips in the standings, keyed by pool and tied pair numbers
+    #[serde(default)]
+    pub coin_flips: BTreeMap<String, Vec<String>>,
+}
+
+impl MatchResult {
+    /// Returns the number of games won by each pair
+    ///
+    /// # Returns
+    ///
+    /// * `[usize; 2]` - Games won, in the order of `pairs`
+    pub fn games_won(&self) -> [usize; 2] {
+        let mut won = [0; 2];
+        for game in &self.games {
+            won[usize::from(game[1] > game[0])] += 1;
+        }
+        won
+    }
+
+    /// Returns which pair won the match
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Index of the winner in `pairs`
+    pub fn winner(&self) -> usize {
+        let won = self.games_won();
+        usize::from(won[1] > won[0])
+    }
+}
+
+impl ResultStore {
+    /// Loads the result store, or returns an empty store if the file does not exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the JSON store
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ResultStore>` - Ok with the store if successful, Err if the file cannot be read or parsed
+    pub fn load(path: &str) -> Result<ResultStore> {
+        if !Path::new(path).exists() {
+            return Ok(ResultStore::default());
+        }
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read result store {}", path))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse result store {}", path))
+    }
+
+    /// Writes the result store
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the JSON store
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the store was written, Err otherwise
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write result store {}", path))
+    }
+
+    /// Returns the pair behind an entrant label such as `Winner of W1-2`
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - Player name written to the match CSV for a pair not yet known
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&Pair>` - The pair if the label refers to a match with a stored result
+    pub fn entrant(&self, label: &str) -> Option<&Pair> {
+        let (outcome, match_id) = bracket::feeder(label)?;
+        let result = self.results.get(match_id)?;
+        let winner = result.winner();
+        Some(match outcome {
+            Outcome::Winner => &result.pairs[winner],
+            Outcome::Loser => &result.pairs[1 - winner],
+        })
+    }
+}
+
+/// Parses game scores written as `11-7, 9-11, 11-5`
+///
+/// # Arguments
+///
+/// * `text` - Scores of each game separated by commas, from the point of view of the first pair
+///
+/// # Returns
+///
+/// * `Result<Vec<[u32; 2]>>` - Ok with the points of each game if successful, Err otherwise
+pub fn parse_scores(text: &str) -> Result<Vec<[u32; 2]>> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|game| !game.is_empty())
+        .map(|game| {
+            let (first, second) = game
+                .split_once('-')
+                .with_context(|| format!("Expected POINTS-POINTS, found `{}`", game))?;
+            let points = |value: &str| {
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .with_context(|| format!("Invalid points `{}` in `{}`", value.trim(), game))
+            };
+            Ok([points(first)?, points(second)?])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a pair with one player named after its number
+    ///
+    /// # Arguments
+    ///
+    /// * `pair_no` - Pair number
+    ///
+    /// # Returns
+    ///
+    /// * `Pair` - The pair
+    fn pair(pair_no: &str) -> Pair {
+        Pair {
+            pair_no: pair_no.to_string(),
+            players: vec![format!("Player {}", pair_no)],
+        }
+    }
+
+    #[test]
+    fn scores_are_parsed_from_the_first_pair_view() {
+        assert_eq!(
+            parse_scores("11-7, 9-11,11 - 5,").unwrap(),
+            [[11, 7], [9, 11], [11, 5]]
+        );
+        assert!(parse_scores("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_scores_are_rejected() {
+        assert_eq!(
+            parse_scores("11-7, 11:9").unwrap_err().to_string(),
+            "Expected POINTS-POINTS, found `11:9`"
+        );
+        assert_eq!(
+            parse_scores("11-x").unwrap_err().to_string(),
+            "Invalid points `x` in `11-x`"
+        );
+        assert!(parse_scores("11--7").is_err());
+    }
+
+    #[test]
+    fn entrants_resolve_to_the_winner_or_loser_of_a_stored_result() {
+        let mut store = ResultStore::default();
+        store.results.insert(
+            "W1-1".to_string(),
+            MatchResult {
+                pool: "Main".to_string(),
+                round: 1,
+                pairs: [pair("4"), pair("5")],
+                games: vec![[11, 9], [7, 11], [8, 11]],
+            },
+        );
+
+        assert_eq!(store.entrant("Winner of W1-1"), Some(&pair("5")));
+        assert_eq!(store.entrant("Loser of W1-1"), Some(&pair("4")));
+        assert_eq!(store.entrant("Winner of W1-2"), None);
+        assert_eq!(store.entrant("Player 4"), None);
+    }
+}
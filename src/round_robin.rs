@@ -0,0 +1,232 @@
+use crate::model::{Match, Pair};
+
+/// Splits pairs into pools of at most `pool_size` pairs
+///
+/// Pairs are dealt in serpentine order (A, B, C, C, B, A, ...), so that when the list
+/// is ordered by seed every pool gets a similar mix of strong and weak pairs, and pool
+/// sizes differ by at most one.
+///
+/// # Arguments
+///
+/// * `pairs` - Registered pairs, strongest first
+/// * `pool_size` - Maximum number of pairs in a pool
+///
+/// # Returns
+///
+/// * `Vec<(String, Vec<Pair>)>` - Pool names (`A`, `B`, ...) with their pairs
+pub fn assign_pools(pairs: &[Pair], pool_size: usize) -> Vec<(String, Vec<Pair>)> {
+    let pool_count = pairs.len().div_ceil(pool_size.max(1)).max(1);
+    let mut pools: Vec<(String, Vec<Pair>)> = (0..pool_count)
+        .map(|pool_index| (pool_name(pool_index), Vec::new()))
+        .collect();
+
+    for (index, pair) in pairs.iter().enumerate() {
+        let lap = index / pool_count;
+        let offset = index % pool_count;
+        let pool_index = if lap.is_multiple_of(2) {
+            offset
+        } else {
+            pool_count - 1 - offset
+        };
+        pools[pool_index].1.push(pair.clone());
+    }
+
+    pools
+}
+
+/// Generates every match of a pool with the circle method
+///
+/// One pair stays in place while the others rotate around it, so each pair meets every
+/// other pair exactly once. With an odd number of pairs a bye is added, and the pair
+/// drawn against it sits out that round.
+///
+/// # Arguments
+///
+/// * `pool` - Name of the pool
+/// * `pairs` - Pairs in the pool
+///
+/// # Returns
+///
+/// * `Vec<Match>` - Matches ordered by round
+pub fn round_robin(pool: &str, pairs: &[Pair]) -> Vec<Match> {
+    let mut slots: Vec<Option<&Pair>> = pairs.iter().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.push(None);
+    }
+    let slot_count = slots.len();
+
+    let mut matches = Vec::new();
+    for round in 0..slot_count.saturating_sub(1) {
+        let mut number = 0;
+        for index in 0..slot_count / 2 {
+            let (Some(first), Some(second)) = (slots[index], slots[slot_count - 1 - index]) else {
+                continue;
+            };
+            // Swap sides of the fixed pair every other round so it is not always listed first
+            let pairs = if index == 0 && round % 2 == 1 {
+                [second.clone(), first.clone()]
+            } else {
+                [first.clone(), second.clone()]
+            };
+            number += 1;
+            matches.push(Match {
+                pool: pool.to_string(),
+                round: round + 1,
+                number,
+                pairs,
+            });
+        }
+        slots[1..].rotate_right(1);
+    }
+
+    matches
+}
+
+/// Returns the name of a pool in spreadsheet column style
+///
+/// # Arguments
+///
+/// * `pool_index` - Zero-based index of the pool
+///
+/// # Returns
+///
+/// * `String` - `A`-`Z`, then `AA`, `AB`, ...
+fn pool_name(pool_index: usize) -> String {
+    let mut name = String::new();
+    let mut index = pool_index + 1;
+    while index > 0 {
+        index -= 1;
+        name.insert(0, (b'A' + (index % 26) as u8) as char);
+        index /= 26;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::MATCH_COLUMNS;
+    use std::collections::BTreeSet;
+
+    /// Returns pairs numbered from 1
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Pair>` - The pairs, with one player each
+    fn pairs(count: usize) -> Vec<Pair> {
+        (1..=count)
+            .map(|pair_no| Pair {
+                pair_no: pair_no.to_string(),
+                players: vec![format!("Player {}", pair_no)],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_pair_meets_every_other_once() {
+        for count in 2..=9 {
+            let matches = round_robin("A", &pairs(count));
+            let meetings: BTreeSet<(String, String)> = matches
+                .iter()
+                .map(|m| {
+                    let [first, second] = &m.pairs;
+                    let mut nos = [first.pair_no.clone(), second.pair_no.clone()];
+                    nos.sort();
+                    (nos[0].clone(), nos[1].clone())
+                })
+                .collect();
+            assert_eq!(matches.len(), count * (count - 1) / 2, "{} pairs", count);
+            assert_eq!(meetings.len(), matches.len(), "{} pairs", count);
+        }
+    }
+
+    #[test]
+    fn odd_pools_give_each_pair_one_bye() {
+        for count in [3, 5, 7] {
+            let matches = round_robin("A", &pairs(count));
+            let rounds = matches.iter().map(|m| m.round).max().unwrap();
+            assert_eq!(rounds, count, "{} pairs", count);
+
+            for round in 1..=rounds {
+                let playing: Vec<&str> = matches
+                    .iter()
+                    .filter(|m| m.round == round)
+                    .flat_map(|m| m.pairs.iter().map(|pair| pair.pair_no.as_str()))
+                    .collect();
+                let unique: BTreeSet<&str> = playing.iter().copied().collect();
+                assert_eq!(playing.len(), count - 1, "{} pairs, round {}", count, round);
+                assert_eq!(
+                    unique.len(),
+                    playing.len(),
+                    "{} pairs, round {}",
+                    count,
+                    round
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_are_numbered_within_their_round() {
+        let numbers: Vec<(usize, usize)> = round_robin("B", &pairs(4))
+            .iter()
+            .map(|m| (m.round, m.number))
+            .collect();
+        assert_eq!(numbers, [(1, 1), (1, 2), (2, 1), (2, 2), (3, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn pools_are_dealt_in_serpentine_order() {
+        let pools: Vec<(String, Vec<String>)> = assign_pools(&pairs(7), 4)
+            .into_iter()
+            .map(|(name, pairs)| (name, pairs.into_iter().map(|pair| pair.pair_no).collect()))
+            .collect();
+        assert_eq!(
+            pools,
+            [
+                ("A".to_string(), vec!["1".into(), "4".into(), "5".into()]),
+                (
+                    "B".to_string(),
+                    vec!["2".into(), "3".into(), "6".into(), "7".into()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn pool_names() {
+        assert_eq!(pool_name(0), "A");
+        assert_eq!(pool_name(25), "Z");
+        assert_eq!(pool_name(26), "AA");
+        assert_eq!(pool_name(27), "AB");
+    }
+
+    #[test]
+    fn matches_are_written_in_the_columns_of_the_match_csv() {
+        let mut pairs = pairs(2);
+        pairs[1].players.push("Player 2b".to_string());
+
+        let matches = round_robin("C", &pairs);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].match_id(), "C-1-1");
+        assert_eq!(
+            matches[0].to_record(),
+            [
+                "C-1-1",
+                "C",
+                "1",
+                "1",
+                "Player 1",
+                "",
+                "2",
+                "Player 2",
+                "Player 2b"
+            ]
+        );
+        assert_eq!(matches[0].to_record().len(), MATCH_COLUMNS.len());
+    }
+}
@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Column filled with the description of the scoring rules of each match
+pub const RULES_COLUMN: &str = "Rules";
+
+/// Column of the match CSV naming the division a match belongs to
+pub const DIVISION_COLUMN: &str = "Division";
+
+/// How games are scored and how many are played in a division
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScoringRules {
+    /// Points needed to win a game, usually 11, 15 or 21
+    #[serde(default = "default_points")]
+    pub points: u32,
+    /// Lead needed to win a game
+    #[serde(default = "default_win_by")]
+    pub win_by: u32,
+    /// Score that wins a game even without the lead, if any
+    #[serde(default)]
+    pub cap: Option<u32>,
+    /// Number of games in a match; the first pair to win the majority wins
+    #[serde(default = "default_best_of")]
+    pub best_of: usize,
+    /// Whether a point is scored on every rally instead of on serve only
+    ///
+    /// Printed on the score sheet only: a final score reads the same under either way of
+    /// scoring, so games are validated the same way.
+    #[serde(default)]
+    pub rally: bool,
+}
+
+/// Scoring rules of each division
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Rules of the matches without a division, or of a division not listed
+    #[serde(default)]
+    pub default: ScoringRules,
+    /// Rules keyed by division name
+    #[serde(default)]
+    pub divisions: BTreeMap<String, ScoringRules>,
+}
+
+/// Points past the target after which a game is reported as implausibly long
+const PLAUSIBLE_EXTRA_POINTS: u32 = 10;
+
+/// Returns the default number of points needed to win a game
+///
+/// # Returns
+///
+/// * `u32` - 11 points
+fn default_points() -> u32 {
+    11
+}
+
+/// Returns the default lead needed to win a game
+///
+/// # Returns
+///
+/// * `u32` - 2 points
+fn default_win_by() -> u32 {
+    2
+}
+
+/// Returns the default number of games in a match
+///
+/// # Returns
+///
+/// * `usize` - Best of 3 games
+fn default_best_of() -> usize {
+    3
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        ScoringRules {
+            points: default_points(),
+            win_by: default_win_by(),
+            cap: None,
+            best_of: default_best_of(),
+            rally: false,
+        }
+    }
+}
+
+impl ScoringRules {
+    /// Checks that the rules can be played
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the rules are consistent, Err otherwise
+    pub fn check(&self) -> Result<()> {
+        if self.points == 0 || self.win_by == 0 {
+            anyhow::bail!("`points` and `win_by` must be at least 1");
+        }
+        if self.best_of.is_multiple_of(2) {
+            anyhow::bail!("`best_of` must be odd, found {}", self.best_of);
+        }
+        if let Some(cap) = self.cap {
+            if cap <= self.points {
+                anyhow::bail!("`cap` must be above `points`, found {}", cap);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rules in words, as printed on the score sheet
+    ///
+    /// # Returns
+    ///
+    /// * `String` - e.g. `Best of 3 games to 11, win by 2`
+    pub fn describe(&self) -> String {
+        let mut text = if self.best_of == 1 {
+            format!("1 game to {}", self.points)
+        } else {
+            format!("Best of {} games to {}", self.best_of, self.points)
+        };
+        if self.win_by > 1 {
+            text.push_str(&format!(", win by {}", self.win_by));
+        }
+        if let Some(cap) = self.cap {
+            text.push_str(&format!(", cap {}", cap));
+        }
+        if self.rally {
+            text.push_str(", rally scoring");
+        }
+        text
+    }
+
+    /// Checks the scores of a match against the rules
+    ///
+    /// A game is won by the first pair to reach `points` with a lead of `win_by`, or
+    /// to reach the cap. The match ends as soon as a pair has won the majority of
+    /// `best_of` games. Rally scoring does not change which scores are possible.
+    ///
+    /// # Arguments
+    ///
+    /// * `games` - Points of each game
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<String>>` - Ok with warnings about implausible scores if the scores are valid, Err describing the first problem otherwise
+    pub fn validate(&self, games: &[[u32; 2]]) -> Result<Vec<String>> {
+        if games.is_empty() {
+            anyhow::bail!("no games");
+        }
+        let games_to_win = self.best_of / 2 + 1;
+        let mut warnings = Vec::new();
+        let mut won = [0; 2];
+        for (game_index, game) in games.iter().enumerate() {
+            if won.contains(&games_to_win) {
+                anyhow::bail!(
+                    "game {} `{}-{}` was played after the match was already decided",
+                    game_index + 1,
+                    game[0],
+                    game[1]
+                );
+            }
+            if let Some(problem) = self.game_problem(*game) {
+                anyhow::bail!(
+                    "game {} `{}-{}`: {}",
+                    game_index + 1,
+                    game[0],
+                    game[1],
+                    problem
+                );
+            }
+            if game[0].max(game[1]) > self.points + PLAUSIBLE_EXTRA_POINTS {
+                warnings.push(format!(
+                    "game {} `{}-{}` is implausibly long for a game to {}",
+                    game_index + 1,
+                    game[0],
+                    game[1],
+                    self.points
+                ));
+            }
+            won[usize::from(game[1] > game[0])] += 1;
+        }
+        if !won.contains(&games_to_win) {
+            anyhow::bail!(
+                "the match is not finished at {}-{} in games ({})",
+                won[0],
+                won[1],
+                self.describe()
+            );
+        }
+
+        Ok(warnings)
+    }
+
+    /// Returns why the score of a single game is not possible
+    ///
+    /// A game goes past `points` only if it was undecided when the first pair reached
+    /// `points`, which needs `win_by` of at least 2. It then ends as soon as the lead is
+    /// `win_by`, or at the cap with a lead of at most `win_by`, since a larger lead would
+    /// have ended it one point earlier.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - Points of both pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The problem, or None if the score is valid
+    fn game_problem(&self, game: [u32; 2]) -> Option<String> {
+        let high = game[0].max(game[1]);
+        let lead = game[0].abs_diff(game[1]);
+        if high < self.points {
+            return Some(format!("nobody reached {} points", self.points));
+        }
+        if let Some(cap) = self.cap {
+            if high > cap {
+                return Some(format!("past the cap of {} points", cap));
+            }
+        }
+        if high == self.points {
+            return (lead < self.win_by).then(|| format!("must be won by {} points", self.win_by));
+        }
+
+        if self.win_by < 2 {
+            return Some(format!(
+                "a game to {} won by 1 ends at {} points",
+                self.points, self.points
+            ));
+        }
+        if self.cap == Some(high) {
+            if lead == 0 {
+                return Some(format!("a game at the cap of {} points has a winner", high));
+            }
+            if lead > self.win_by {
+                return Some(format!(
+                    "the game ended before the cap of {} points, as soon as the lead was {}",
+                    high, self.win_by
+                ));
+            }
+            return None;
+        }
+        if lead < self.win_by {
+            return Some(format!("must be won by {} points", self.win_by));
+        }
+        if lead > self.win_by {
+            return Some(format!(
+                "a game past {} points ends as soon as the lead is {}",
+                self.points, self.win_by
+            ));
+        }
+        None
+    }
+}
+
+impl RulesConfig {
+    /// Loads the scoring rules of each division
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the TOML rules file; None uses the default rules for every division
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RulesConfig>` - Ok with the rules if successful, Err if the file cannot be read or the rules are invalid
+    pub fn load(path: Option<&str>) -> Result<RulesConfig> {
+        let Some(path) = path else {
+            return Ok(RulesConfig::default());
+        };
+        let toml_str = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rules file {}", path))?;
+        let config: RulesConfig = toml::from_str(&toml_str)
+            .with_context(|| format!("Failed to parse rules file {}", path))?;
+        config
+            .check()
+            .with_context(|| format!("Invalid rules file {}", path))?;
+
+        Ok(config)
+    }
+
+    /// Checks that the rules of every division can be played
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if every division has consistent rules, Err otherwise
+    pub fn check(&self) -> Result<()> {
+        self.default.check().context("Invalid default rules")?;
+        for (division, rules) in &self.divisions {
+            rules
+                .check()
+                .with_context(|| format!("Invalid rules for division `{}`", division))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the rules of a division
+    ///
+    /// # Arguments
+    ///
+    /// * `division` - Name of the division; blank or unknown divisions use the default rules
+    ///
+    /// # Returns
+    ///
+    /// * `&ScoringRules` - The rules of the division
+    pub fn for_division(&self, division: &str) -> &ScoringRules {
+        self.divisions.get(division).unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns rules of one game with the given target, lead and cap
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Points needed to win a game
+    /// * `win_by` - Lead needed to win a game
+    /// * `cap` - Score that wins a game even without the lead, if any
+    ///
+    /// # Returns
+    ///
+    /// * `ScoringRules` - The rules
+    fn rules(points: u32, win_by: u32, cap: Option<u32>) -> ScoringRules {
+        ScoringRules {
+            points,
+            win_by,
+            cap,
+            best_of: 1,
+            rally: false,
+        }
+    }
+
+    #[test]
+    fn game_scores() {
+        let cases = [
+            (rules(11, 2, None), [11, 10], false),
+            (rules(11, 2, None), [12, 10], true),
+            (rules(11, 2, None), [11, 9], true),
+            (rules(11, 2, None), [11, 3], true),
+            (rules(11, 2, None), [14, 10], false),
+            (rules(11, 2, None), [10, 8], false),
+            (rules(11, 1, None), [11, 10], true),
+            (rules(11, 1, None), [13, 12], false),
+            (rules(11, 2, None), [13, 12], false),
+            (rules(15, 2, Some(17)), [17, 10], false),
+            (rules(15, 2, Some(17)), [17, 16], true),
+            (rules(15, 2, Some(17)), [17, 15], true),
+            (rules(15, 2, Some(17)), [16, 14], true),
+            (rules(15, 2, Some(17)), [18, 16], false),
+            (rules(11, 2, Some(15)), [15, 12], false),
+            (rules(11, 2, Some(15)), [15, 14], true),
+        ];
+        for (rules, game, valid) in cases {
+            let problem = rules.game_problem(game);
+            assert_eq!(
+                problem.is_none(),
+                valid,
+                "{}-{} under `{}`: {:?}",
+                game[0],
+                game[1],
+                rules.describe(),
+                problem
+            );
+        }
+    }
+
+    #[test]
+    fn match_ends_when_decided() {
+        let rules = ScoringRules::default();
+
+        assert!(rules.validate(&[[11, 5], [11, 7]]).is_ok());
+        assert!(rules.validate(&[[11, 5], [5, 11], [12, 10]]).is_ok());
+        assert!(rules.validate(&[[11, 5], [11, 7], [11, 3]]).is_err());
+        assert!(rules.validate(&[[11, 5], [5, 11]]).is_err());
+    }
+
+    #[test]
+    fn cap_must_be_above_points() {
+        assert!(rules(11, 2, Some(11)).check().is_err());
+        assert!(rules(11, 2, Some(15)).check().is_ok());
+    }
+
+    #[test]
+    fn long_games_are_warned_about() {
+        let rules = rules(11, 2, None);
+
+        assert!(rules.validate(&[[21, 19]]).unwrap().is_empty());
+        assert_eq!(
+            rules.validate(&[[22, 20]]).unwrap(),
+            ["game 1 `22-20` is implausibly long for a game to 11"]
+        );
+    }
+
+    #[test]
+    fn rally_scoring_is_described_and_validated_like_side_out_scoring() {
+        let side_out = rules(15, 2, Some(17));
+        let rally = ScoringRules {
+            rally: true,
+            ..side_out.clone()
+        };
+
+        assert_eq!(
+            rally.describe(),
+            "1 game to 15, win by 2, cap 17, rally scoring"
+        );
+        for game in [[15, 9], [17, 16], [16, 14], [15, 14], [18, 16]] {
+            assert_eq!(
+                rally.validate(&[game]).is_ok(),
+                side_out.validate(&[game]).is_ok(),
+                "{}-{}",
+                game[0],
+                game[1]
+            );
+        }
+    }
+}
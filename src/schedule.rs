@@ -0,0 +1,523 @@
+use crate::model::PAIR_COLUMNS;
+use crate::template::escape_xml;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Courts, timing and rest constraints of a schedule
+#[derive(Debug)]
+pub struct ScheduleOptions {
+    /// Number of courts played on at the same time
+    pub courts: usize,
+    /// Start time of the first matches, in minutes after midnight
+    pub start_time: u32,
+    /// Length of a time slot in minutes
+    pub match_duration: u32,
+    /// Minimum time in minutes between the end of a match and the next match of the same pair
+    pub min_rest: u32,
+}
+
+/// A match waiting for a court
+#[derive(Debug)]
+pub struct Pending<'a> {
+    /// Keys of the two pairs; pair numbers, or entrant labels such as `Winner of W1-2`
+    pub pairs: [&'a str; 2],
+    /// Round number; earlier rounds are placed first
+    pub round: usize,
+    /// Indices of the matches that must be over, and the rest taken, before this one
+    pub after: Vec<usize>,
+}
+
+/// Court and start time assigned to a match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    /// Zero-based index of the time slot
+    pub time_index: usize,
+    /// One-based court number
+    pub court: usize,
+}
+
+/// Width of an A4 page in portrait, in points
+const PAGE_WIDTH: f32 = 595.0;
+/// Height of an A4 page in portrait, in points
+const PAGE_HEIGHT: f32 = 842.0;
+/// Margin around the grid, in points
+const MARGIN: f32 = 36.0;
+/// Width of the time column, in points
+const TIME_WIDTH: f32 = 56.0;
+/// Height of the court header row, in points
+const HEADER_HEIGHT: f32 = 24.0;
+/// Height of a time slot row, in points
+const ROW_HEIGHT: f32 = 32.0;
+
+/// Parses a start time written as `HH:MM`
+///
+/// # Arguments
+///
+/// * `value` - Value given on the command line
+///
+/// # Returns
+///
+/// * `Result<u32>` - Ok with the minutes after midnight if successful, Err otherwise
+pub fn parse_time(value: &str) -> Result<u32> {
+    let (hours, minutes) = value.split_once(':').context("Expected HH:MM")?;
+    let hours: u32 = hours.trim().parse().context("Invalid hours")?;
+    let minutes: u32 = minutes.trim().parse().context("Invalid minutes")?;
+    if hours > 23 || minutes > 59 {
+        anyhow::bail!("Time out of range: {}", value);
+    }
+
+    Ok(hours * 60 + minutes)
+}
+
+/// Formats minutes after midnight as `HH:MM`
+///
+/// # Arguments
+///
+/// * `minutes` - Minutes after midnight; times past midnight keep counting hours
+///
+/// # Returns
+///
+/// * `String` - The formatted time
+pub fn format_time(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Returns the start time of a time slot
+///
+/// # Arguments
+///
+/// * `options` - Courts and timing of the schedule
+/// * `time_index` - Zero-based index of the time slot
+///
+/// # Returns
+///
+/// * `u32` - Start time in minutes after midnight
+pub fn slot_start(options: &ScheduleOptions, time_index: usize) -> u32 {
+    options.start_time + options.match_duration * time_index as u32
+}
+
+/// Assigns a court and a time slot to every match
+///
+/// Time slots are filled one after the other. Each slot takes, in order of round and
+/// then of input order, the matches whose pairs are not already playing in that slot
+/// and have rested at least `min_rest` since their previous match, until every court
+/// is busy. A bracket match also waits for the matches its entrants come from. Courts
+/// are left idle when no waiting match can be played yet.
+///
+/// # Arguments
+///
+/// * `matches` - Pairs, round and preceding matches of each match
+/// * `options` - Courts, timing and rest constraints
+///
+/// # Returns
+///
+/// * `Result<Vec<Slot>>` - Ok with the slot of each match, in input order, Err if the options are invalid
+pub fn schedule(matches: &[Pending], options: &ScheduleOptions) -> Result<Vec<Slot>> {
+    if options.courts == 0 {
+        anyhow::bail!("At least one court is required");
+    }
+    if options.match_duration == 0 {
+        anyhow::bail!("Match duration must be at least one minute");
+    }
+    for pending in matches {
+        if pending.pairs[0] == pending.pairs[1] {
+            anyhow::bail!("Pair `{}` cannot play against itself", pending.pairs[0]);
+        }
+        if let Some(index) = pending.after.iter().find(|&&index| index >= matches.len()) {
+            anyhow::bail!("Unknown preceding match {}", index);
+        }
+    }
+
+    // Slots to skip after a match so that the pair gets its rest
+    let rest_slots = options.min_rest.div_ceil(options.match_duration) as usize;
+
+    let mut waiting: Vec<usize> = (0..matches.len()).collect();
+    waiting.sort_by_key(|&index| matches[index].round);
+
+    let mut slots: Vec<Option<Slot>> = vec![None; matches.len()];
+    let mut next_free: HashMap<&str, usize> = HashMap::new();
+    let mut time_index = 0;
+    while !waiting.is_empty() {
+        if time_index > matches.len() * (rest_slots + 1) {
+            anyhow::bail!("Matches wait for each other in a cycle");
+        }
+        let mut playing = HashSet::new();
+        let mut court = 0;
+        waiting.retain(|&index| {
+            let pairs = matches[index].pairs;
+            let ready = court < options.courts
+                && pairs.iter().all(|pair| {
+                    !playing.contains(pair)
+                        && next_free.get(pair).copied().unwrap_or(0) <= time_index
+                })
+                && matches[index].after.iter().all(|&before| {
+                    slots[before].is_some_and(|slot| slot.time_index + 1 + rest_slots <= time_index)
+                });
+            if !ready {
+                return true;
+            }
+
+            court += 1;
+            for pair in pairs {
+                playing.insert(pair);
+                next_free.insert(pair, time_index + 1 + rest_slots);
+            }
+            slots[index] = Some(Slot { time_index, court });
+            false
+        });
+        time_index += 1;
+    }
+
+    Ok(slots.into_iter().flatten().collect())
+}
+
+/// Returns the two lines of text printed for a match in the schedule grid
+///
+/// Each pair is named by its pair number, or by the player column while the pair is
+/// still written as a label such as `Winner of W1-2`.
+///
+/// # Arguments
+///
+/// * `row` - Values of the match CSV row keyed by column name
+///
+/// # Returns
+///
+/// * `[String; 2]` - The pairs, and the pool and round
+pub fn grid_label(row: &HashMap<String, String>) -> [String; 2] {
+    let field = |column: &str| row.get(column).map_or("", String::as_str);
+    let entrants: Vec<&str> = PAIR_COLUMNS
+        .iter()
+        .map(|[pair_no, player, _]| match field(pair_no) {
+            "" => field(player),
+            pair_no => pair_no,
+        })
+        .collect();
+
+    let (pool, round) = (field("Pool"), field("Round"));
+    let detail = match (pool.is_empty(), round.is_empty()) {
+        (false, false) => format!("Pool {} / Round {}", pool, round),
+        (false, true) => format!("Pool {}", pool),
+        (true, false) => format!("Round {}", round),
+        (true, true) => String::new(),
+    };
+
+    [entrants.join(" vs "), detail]
+}
+
+/// Draws the schedule of one court as a grid of time slots, split into A4 portrait pages
+///
+/// Every court gets a row for each time slot of the whole schedule, so the grids of all
+/// courts line up and a slot where the court is idle is left blank.
+///
+/// # Arguments
+///
+/// * `labels` - Two lines of text for each match: the pairs, and the pool and round
+/// * `slots` - Slot of each match, in the order of `labels`
+/// * `options` - Courts and timing of the schedule
+/// * `title` - Title printed above the grid
+/// * `court` - One-based court number
+///
+/// # Returns
+///
+/// * `Vec<(String, String)>` - Bookmark title and SVG of each page
+pub fn grid_pages(
+    labels: &[[String; 2]],
+    slots: &[Slot],
+    options: &ScheduleOptions,
+    title: &str,
+    court: usize,
+) -> Vec<(String, String)> {
+    let time_slots = slots
+        .iter()
+        .map(|slot| slot.time_index + 1)
+        .max()
+        .unwrap_or(0);
+    let rows_per_page =
+        ((PAGE_HEIGHT - 2.0 * MARGIN - 2.0 * HEADER_HEIGHT) / ROW_HEIGHT).max(1.0) as usize;
+
+    let cells: HashMap<usize, &[String; 2]> = labels
+        .iter()
+        .zip(slots)
+        .filter(|(_, slot)| slot.court == court)
+        .map(|(label, slot)| (slot.time_index, label))
+        .collect();
+
+    let mut pages = Vec::new();
+    for first_row in (0..time_slots).step_by(rows_per_page) {
+        let last_row = (first_row + rows_per_page).min(time_slots);
+        let bookmark = format!(
+            "Court {}, {}-{}",
+            court,
+            format_time(slot_start(options, first_row)),
+            format_time(slot_start(options, last_row))
+        );
+        let svg = grid_page(&cells, options, title, court, first_row..last_row);
+        pages.push((bookmark, svg));
+    }
+
+    pages
+}
+
+/// Draws one page of the schedule grid of a court
+///
+/// # Arguments
+///
+/// * `cells` - Labels of the matches on the court keyed by time slot
+/// * `options` - Courts and timing of the schedule
+/// * `title` - Title printed above the grid
+/// * `court` - One-based court number
+/// * `rows` - Time slots drawn as rows
+///
+/// # Returns
+///
+/// * `String` - SVG of the page
+fn grid_page(
+    cells: &HashMap<usize, &[String; 2]>,
+    options: &ScheduleOptions,
+    title: &str,
+    court: usize,
+    rows: std::ops::Range<usize>,
+) -> String {
+    let court_width = PAGE_WIDTH - 2.0 * MARGIN - TIME_WIDTH;
+    let court_x = MARGIN + TIME_WIDTH;
+    let top = MARGIN + HEADER_HEIGHT;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+        w = PAGE_WIDTH,
+        h = PAGE_HEIGHT
+    );
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" font-size="14" font-weight="bold">{}</text>"#,
+        MARGIN,
+        MARGIN + 14.0,
+        escape_xml(title)
+    ));
+
+    // Court header row
+    svg.push_str(&format!(
+        r##"<rect x="{court_x}" y="{top}" width="{court_width}" height="{HEADER_HEIGHT}" fill="#e0e0e0" stroke="#000" stroke-width="0.5"/>"##
+    ));
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" font-size="10" font-weight="bold" text-anchor="middle">Court {}</text>"#,
+        court_x + court_width / 2.0,
+        top + 16.0,
+        court
+    ));
+
+    // One row per time slot
+    for (row, time_index) in rows.enumerate() {
+        let y = top + HEADER_HEIGHT + row as f32 * ROW_HEIGHT;
+        svg.push_str(&format!(
+            r##"<rect x="{MARGIN}" y="{y}" width="{TIME_WIDTH}" height="{ROW_HEIGHT}" fill="#e0e0e0" stroke="#000" stroke-width="0.5"/>"##
+        ));
+        svg.push_str(&format!(
+            r#"<text x="{}" y="{}" font-size="10" font-weight="bold" text-anchor="middle">{}</text>"#,
+            MARGIN + TIME_WIDTH / 2.0,
+            y + 19.0,
+            format_time(slot_start(options, time_index))
+        ));
+
+        svg.push_str(&format!(
+            r##"<rect x="{court_x}" y="{y}" width="{court_width}" height="{ROW_HEIGHT}" fill="none" stroke="#000" stroke-width="0.5"/>"##
+        ));
+        if let Some([pairs, detail]) = cells.get(&time_index) {
+            svg.push_str(&format!(
+                r#"<text x="{}" y="{}" font-size="10" text-anchor="middle">{}</text>"#,
+                court_x + court_width / 2.0,
+                y + 14.0,
+                escape_xml(pairs)
+            ));
+            svg.push_str(&format!(
+                r##"<text x="{}" y="{}" font-size="7" fill="#555" text-anchor="middle">{}</text>"##,
+                court_x + court_width / 2.0,
+                y + 25.0,
+                escape_xml(detail)
+            ));
+        }
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns schedule options starting at 9:00 with 20-minute slots
+    ///
+    /// # Arguments
+    ///
+    /// * `courts` - Number of courts
+    /// * `min_rest` - Minimum rest in minutes
+    ///
+    /// # Returns
+    ///
+    /// * `ScheduleOptions` - The options
+    fn options(courts: usize, min_rest: u32) -> ScheduleOptions {
+        ScheduleOptions {
+            courts,
+            start_time: 9 * 60,
+            match_duration: 20,
+            min_rest,
+        }
+    }
+
+    /// Returns a match without preceding matches
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - Key of the first pair
+    /// * `second` - Key of the second pair
+    /// * `round` - Round number
+    ///
+    /// # Returns
+    ///
+    /// * `Pending` - The match
+    fn pending<'a>(first: &'a str, second: &'a str, round: usize) -> Pending<'a> {
+        Pending {
+            pairs: [first, second],
+            round,
+            after: Vec::new(),
+        }
+    }
+
+    /// Returns the time slot and court of each scheduled match
+    ///
+    /// # Arguments
+    ///
+    /// * `slots` - Slots returned by the scheduler
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(usize, usize)>` - Time index and court of each match
+    fn positions(slots: &[Slot]) -> Vec<(usize, usize)> {
+        slots
+            .iter()
+            .map(|slot| (slot.time_index, slot.court))
+            .collect()
+    }
+
+    #[test]
+    fn min_rest_skips_slots() {
+        let matches = [
+            pending("1", "2", 1),
+            pending("3", "4", 1),
+            pending("1", "3", 2),
+            pending("2", "4", 2),
+        ];
+
+        // No rest: the next round follows right away
+        let slots = schedule(&matches, &options(2, 0)).unwrap();
+        assert_eq!(positions(&slots), [(0, 1), (0, 2), (1, 1), (1, 2)]);
+
+        // 30 minutes of rest with 20-minute slots skips two slots
+        let slots = schedule(&matches, &options(2, 30)).unwrap();
+        assert_eq!(positions(&slots), [(0, 1), (0, 2), (3, 1), (3, 2)]);
+        assert_eq!(format_time(slot_start(&options(2, 30), 3)), "10:00");
+    }
+
+    #[test]
+    fn pairs_never_play_twice_in_a_slot() {
+        let matches = [
+            pending("1", "2", 1),
+            pending("1", "3", 1),
+            pending("2", "3", 1),
+        ];
+        let slots = schedule(&matches, &options(3, 0)).unwrap();
+        assert_eq!(positions(&slots), [(0, 1), (1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn bracket_matches_wait_for_their_feeders() {
+        let mut matches = vec![
+            pending("1", "4", 1),
+            pending("2", "3", 1),
+            pending("Winner of W1-1", "Winner of W1-2", 2),
+        ];
+        matches[2].after = vec![0, 1];
+        let slots = schedule(&matches, &options(1, 20)).unwrap();
+        assert_eq!(positions(&slots), [(0, 1), (1, 1), (3, 1)]);
+
+        matches[0].after = vec![2];
+        assert!(schedule(&matches, &options(1, 0)).is_err());
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let matches = [pending("1", "2", 1)];
+        assert!(schedule(&matches, &options(0, 0)).is_err());
+        assert!(schedule(&[pending("1", "1", 1)], &options(1, 0)).is_err());
+        let mut matches = vec![pending("1", "2", 1)];
+        matches[0].after = vec![3];
+        assert!(schedule(&matches, &options(1, 0)).is_err());
+    }
+
+    #[test]
+    fn grid_of_each_court_holds_only_its_matches() {
+        let labels = [
+            ["1 vs 2".to_string(), "Pool A".to_string()],
+            ["3 vs 4".to_string(), "Pool A".to_string()],
+        ];
+        let slots = [
+            Slot {
+                time_index: 0,
+                court: 1,
+            },
+            Slot {
+                time_index: 40,
+                court: 2,
+            },
+        ];
+        let options = options(2, 0);
+
+        let pages = grid_pages(&labels, &slots, &options, "Cup", 1);
+        assert_eq!(pages[0].0, "Court 1, 09:00-16:20");
+        assert!(pages[0].1.contains("1 vs 2"));
+        assert!(pages.iter().all(|(_, svg)| !svg.contains("3 vs 4")));
+
+        // Both courts cover every time slot, so the idle one gets blank pages too
+        let pages = grid_pages(&labels, &slots, &options, "Cup", 2);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].0, "Court 2, 16:20-22:40");
+        assert!(pages[1].1.contains("3 vs 4"));
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("9:05").unwrap(), 9 * 60 + 5);
+        assert_eq!(format_time(9 * 60 + 5), "09:05");
+        assert!(parse_time("24:00").is_err());
+        assert!(parse_time("0900").is_err());
+    }
+
+    #[test]
+    fn grid_labels_name_pairs_not_known_yet_by_their_label() {
+        let row = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+            entries
+                .iter()
+                .map(|(column, value)| (column.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            grid_label(&row(&[
+                ("Pair No1", "3"),
+                ("Pair No2", "7"),
+                ("Pool", "A"),
+                ("Round", "2"),
+            ])),
+            ["3 vs 7", "Pool A / Round 2"]
+        );
+        assert_eq!(
+            grid_label(&row(&[
+                ("Pair No1", "1"),
+                ("Player1", "Ann"),
+                ("Pair No2", ""),
+                ("Player3", "Winner of W1-2"),
+                ("Round", "2"),
+            ])),
+            ["1 vs Winner of W1-2", "Round 2"]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{bail, Context, Result};
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use csv::Writer;
+use std::path::Path;
+
+/// File extensions read as spreadsheets rather than CSV
+const EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// Separator between a spreadsheet path and the name of a sheet in it
+const SHEET_SEPARATOR: char = '#';
+
+/// Checks whether a path names a spreadsheet, by its extension
+///
+/// # Arguments
+///
+/// * `path` - Path of the file, without a sheet name
+///
+/// # Returns
+///
+/// * `bool` - True if the file is read as a spreadsheet
+pub fn is_spreadsheet(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        })
+}
+
+/// Splits an input path of the form `book.xlsx#Sheet` into the file and the sheet name
+///
+/// # Arguments
+///
+/// * `path` - Input path, optionally followed by `#` and a sheet name
+///
+/// # Returns
+///
+/// * `Option<(&str, Option<&str>)>` - The spreadsheet path and sheet name, or None if the path is not a spreadsheet
+pub fn split_sheet(path: &str) -> Option<(&str, Option<&str>)> {
+    if is_spreadsheet(path) {
+        return Some((path, None));
+    }
+
+    path.match_indices(SHEET_SEPARATOR)
+        .map(|(index, _)| (&path[..index], &path[index + 1..]))
+        .find(|(file, sheet)| is_spreadsheet(file) && !sheet.is_empty())
+        .map(|(file, sheet)| (file, Some(sheet)))
+}
+
+/// Reads a sheet of a spreadsheet and converts it to CSV
+///
+/// Fully blank rows are skipped, so a sheet with notes below a gap still reads as one table.
+///
+/// # Arguments
+///
+/// * `path` - Path of the spreadsheet
+/// * `sheet` - Name of the sheet to read; None reads the first sheet
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the CSV bytes if successful, Err otherwise
+pub fn to_csv(path: &str, sheet: Option<&str>) -> Result<Vec<u8>> {
+    let mut workbook =
+        open_workbook_auto(path).with_context(|| format!("Failed to open {}", path))?;
+
+    let range = match sheet {
+        Some(sheet) => {
+            let names = workbook.sheet_names();
+            if !names.iter().any(|name| name == sheet) {
+                bail!(
+                    "Sheet '{}' not found in {} (available: {})",
+                    sheet,
+                    path,
+                    names.join(", ")
+                );
+            }
+            workbook
+                .worksheet_range(sheet)
+                .with_context(|| format!("Failed to read sheet '{}' of {}", sheet, path))?
+        }
+        None => workbook
+            .worksheet_range_at(0)
+            .with_context(|| format!("{} has no sheets", path))?
+            .with_context(|| format!("Failed to read the first sheet of {}", path))?,
+    };
+
+    write_csv(&range)
+}
+
+/// Writes the non-blank rows of a sheet as CSV
+///
+/// # Arguments
+///
+/// * `range` - Cells of the sheet
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the CSV bytes if successful, Err otherwise
+fn write_csv(range: &Range<Data>) -> Result<Vec<u8>> {
+    let mut writer = Writer::from_writer(Vec::new());
+    for row in range.rows() {
+        let cells: Vec<String> = row.iter().map(cell_text).collect();
+        if cells.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        writer.write_record(&cells)?;
+    }
+
+    writer
+        .into_inner()
+        .context("Failed to convert sheet to CSV")
+}
+
+/// Formats a cell the way it would read in a CSV export
+///
+/// Times are written as `HH:MM` and dates as `YYYY-MM-DD`; text cells are kept as is so
+/// numbers stored as text keep their leading zeros.
+///
+/// # Arguments
+///
+/// * `cell` - Cell value
+///
+/// # Returns
+///
+/// * `String` - Text of the cell
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(datetime) => {
+            let (year, month, day, hour, minute, second, _) = datetime.to_ymd_hms_milli();
+            let time = if second == 0 {
+                format!("{:02}:{:02}", hour, minute)
+            } else {
+                format!("{:02}:{:02}:{:02}", hour, minute, second)
+            };
+            if datetime.is_duration() || datetime.as_f64() < 1.0 {
+                time
+            } else if (hour, minute, second) == (0, 0, 0) {
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            } else {
+                format!("{:04}-{:02}-{:02} {}", year, month, day, time)
+            }
+        }
+        Data::DateTimeIso(text) | Data::DurationIso(text) => text.clone(),
+        _ => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    /// Returns a date/time cell
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Days since the Excel epoch, with the time of day as a fraction
+    /// * `datetime_type` - Whether the cell holds a point in time or a duration
+    ///
+    /// # Returns
+    ///
+    /// * `Data` - The cell
+    fn datetime(value: f64, datetime_type: ExcelDateTimeType) -> Data {
+        Data::DateTime(ExcelDateTime::new(value, datetime_type, false))
+    }
+
+    #[test]
+    fn sheet_names_follow_the_spreadsheet_path() {
+        assert_eq!(split_sheet("book.xlsx"), Some(("book.xlsx", None)));
+        assert_eq!(
+            split_sheet("book.XLSX#Day 1"),
+            Some(("book.XLSX", Some("Day 1")))
+        );
+        assert_eq!(
+            split_sheet("results#2024/book.ods#Pool#A"),
+            Some(("results#2024/book.ods", Some("Pool#A")))
+        );
+        assert_eq!(split_sheet("book.xlsx#"), None);
+        assert_eq!(split_sheet("matches.csv"), None);
+        assert_eq!(split_sheet("matches.csv#Sheet1"), None);
+    }
+
+    #[test]
+    fn dates_and_times_read_as_in_a_csv_export() {
+        use ExcelDateTimeType::{DateTime, TimeDelta};
+
+        assert_eq!(cell_text(&datetime(0.375, DateTime)), "09:00");
+        assert_eq!(
+            cell_text(&datetime(0.5 + 30.0 / 86400.0, DateTime)),
+            "12:00:30"
+        );
+        assert_eq!(cell_text(&datetime(45580.0, DateTime)), "2024-10-15");
+        assert_eq!(
+            cell_text(&datetime(45580.40625, DateTime)),
+            "2024-10-15 09:45"
+        );
+        assert_eq!(cell_text(&datetime(20.0 / 1440.0, TimeDelta)), "00:20");
+        assert_eq!(
+            cell_text(&Data::DateTimeIso("2024-10-15T09:00:00".to_string())),
+            "2024-10-15T09:00:00"
+        );
+        assert_eq!(cell_text(&Data::DurationIso("PT20M".to_string())), "PT20M");
+    }
+
+    #[test]
+    fn other_cells_keep_their_text() {
+        assert_eq!(cell_text(&Data::String("007".to_string())), "007");
+        assert_eq!(cell_text(&Data::Float(12.0)), "12");
+        assert_eq!(cell_text(&Data::Int(3)), "3");
+        assert_eq!(cell_text(&Data::Empty), "");
+    }
+
+    #[test]
+    fn sheets_are_read_as_csv_without_blank_rows() {
+        let csv = to_csv("test/matches.xlsx", Some("Matches")).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "Match ID,Pair No1,Time,Date\n\
+             A-1-1,007,09:00,2024-10-15\n\
+             A-1-2,12,09:45,2024-10-15\n"
+        );
+
+        let first = to_csv("test/matches.xlsx", None).unwrap();
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            "Printed on A4 landscape\n"
+        );
+
+        assert_eq!(
+            to_csv("test/matches.xlsx", Some("Playoff"))
+                .unwrap_err()
+                .to_string(),
+            "Sheet 'Playoff' not found in test/matches.xlsx (available: Notes, Matches)"
+        );
+    }
+}
@@ -0,0 +1,700 @@
+use crate::model::Pair;
+use crate::results::MatchResult;
+use crate::template::escape_xml;
+use rand::seq::SliceRandom;
+use std::collections::BTreeMap;
+
+/// Columns of the standings CSV
+pub const STANDING_COLUMNS: [&str; 14] = [
+    "Pool",
+    "Rank",
+    "Pair No",
+    "Player1",
+    "Player2",
+    "Played",
+    "Won",
+    "Lost",
+    "Games Won",
+    "Games Lost",
+    "Points For",
+    "Points Against",
+    "Point Differential",
+    "Decided By",
+];
+
+/// A rule ranking pairs that are level on everything before it in the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Tiebreak {
+    /// Most matches won
+    MatchWins,
+    /// Most matches won against the other tied pairs only
+    HeadToHead,
+    /// Highest share of games won
+    GameWinPct,
+    /// Highest point differential, counting at most the differential cap per game
+    PointDifferential,
+    /// Fewest points allowed
+    PointsAllowed,
+    /// A coin flip, recorded in the result store so that it is only flipped once
+    CoinFlip,
+}
+
+/// How pairs of a pool are ranked
+#[derive(Debug)]
+pub struct StandingsOptions<'a> {
+    /// Tiebreaks applied in order until the tied pairs are separated
+    pub tiebreaks: &'a [Tiebreak],
+    /// Largest point differential counted for a single game
+    pub differential_cap: u32,
+}
+
+/// Matches, games and points of a pair
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    /// Matches with a result
+    pub played: usize,
+    /// Matches won
+    pub won: usize,
+    /// Matches lost
+    pub lost: usize,
+    /// Games won
+    pub games_won: usize,
+    /// Games lost
+    pub games_lost: usize,
+    /// Points scored
+    pub points_for: u32,
+    /// Points allowed
+    pub points_against: u32,
+    /// Point differential, capped per game
+    pub differential: i64,
+}
+
+/// Rank of a pair within its pool
+#[derive(Debug, Clone)]
+pub struct Standing {
+    /// One-based rank; pairs still tied share a rank
+    pub rank: usize,
+    /// The pair
+    pub pair: Pair,
+    /// Record of the pair over every match of the pool
+    pub record: Record,
+    /// Tiebreak that separated the pair from the pairs next to it, if any
+    pub decided_by: Option<Tiebreak>,
+}
+
+/// Width of an A4 page in landscape, in points
+const PAGE_WIDTH: f32 = 842.0;
+/// Height of an A4 page in landscape, in points
+const PAGE_HEIGHT: f32 = 595.0;
+/// Margin around the table, in points
+const MARGIN: f32 = 36.0;
+/// Height of the header row and of a pair row, in points
+const ROW_HEIGHT: f32 = 24.0;
+/// Heading and relative width of each column of the standings table
+const TABLE_COLUMNS: [(&str, f32); 11] = [
+    ("Rank", 0.6),
+    ("Pair", 0.6),
+    ("Players", 3.6),
+    ("P", 0.5),
+    ("W", 0.5),
+    ("L", 0.5),
+    ("Games", 0.9),
+    ("Game %", 0.9),
+    ("Points", 1.1),
+    ("Diff", 0.7),
+    ("Decided by", 1.8),
+];
+
+impl Tiebreak {
+    /// Returns the name of the tiebreak as printed in the standings
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - e.g. `Head-to-head`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tiebreak::MatchWins => "Match wins",
+            Tiebreak::HeadToHead => "Head-to-head",
+            Tiebreak::GameWinPct => "Game win %",
+            Tiebreak::PointDifferential => "Point differential",
+            Tiebreak::PointsAllowed => "Points allowed",
+            Tiebreak::CoinFlip => "Coin flip",
+        }
+    }
+}
+
+impl Record {
+    /// Returns the share of games won
+    ///
+    /// # Returns
+    ///
+    /// * `f64` - Games won divided by games played, 0 without games
+    pub fn game_win_pct(&self) -> f64 {
+        let games = self.games_won + self.games_lost;
+        if games == 0 {
+            return 0.0;
+        }
+        self.games_won as f64 / games as f64
+    }
+
+    /// Adds a match to the record
+    ///
+    /// # Arguments
+    ///
+    /// * `games` - Points of each game, the pair's points first
+    /// * `differential_cap` - Largest point differential counted for a single game
+    fn add(&mut self, games: impl Iterator<Item = [u32; 2]>, differential_cap: u32) {
+        let mut won = [0; 2];
+        for [scored, allowed] in games {
+            won[usize::from(allowed > scored)] += 1;
+            self.points_for += scored;
+            self.points_against += allowed;
+            let cap = i64::from(differential_cap);
+            self.differential += (i64::from(scored) - i64::from(allowed)).clamp(-cap, cap);
+        }
+        self.played += 1;
+        self.games_won += won[0];
+        self.games_lost += won[1];
+        if won[0] > won[1] {
+            self.won += 1;
+        } else {
+            self.lost += 1;
+        }
+    }
+}
+
+/// Ranks the pairs of a pool
+///
+/// Pairs level on a tiebreak are ranked by the next one. Once a tiebreak splits the
+/// tied pairs, each smaller group still tied starts over from the first tiebreak, so
+/// head-to-head is recomputed among the pairs of that group only. A coin flip is taken
+/// from `coin_flips` when the same pairs were tied before, and recorded there otherwise.
+///
+/// # Arguments
+///
+/// * `pool` - Name of the pool
+/// * `pairs` - Pairs of the pool
+/// * `results` - Results of the matches of the pool
+/// * `options` - Tiebreak chain and differential cap
+/// * `coin_flips` - Orders of earlier coin flips keyed by pool and tied pair numbers
+///
+/// # Returns
+///
+/// * `Vec<Standing>` - Standings from first to last
+pub fn pool_standings(
+    pool: &str,
+    pairs: &[Pair],
+    results: &[&MatchResult],
+    options: &StandingsOptions,
+    coin_flips: &mut BTreeMap<String, Vec<String>>,
+) -> Vec<Standing> {
+    let all: Vec<usize> = (0..pairs.len()).collect();
+    let records = records(pairs, results, &all, options.differential_cap);
+    let ranking = Ranking {
+        pool,
+        pairs,
+        results,
+        records: &records,
+        options,
+    };
+    let mut groups = Vec::new();
+    ranking.rank(all, None, coin_flips, &mut groups);
+
+    let mut standings = Vec::new();
+    for (group, decided_by) in groups {
+        let rank = standings.len() + 1;
+        for index in group {
+            standings.push(Standing {
+                rank,
+                pair: pairs[index].clone(),
+                record: records[index].clone(),
+                decided_by,
+            });
+        }
+    }
+    standings
+}
+
+/// Computes the records of some pairs over the matches they played against each other
+///
+/// # Arguments
+///
+/// * `pairs` - Pairs of the pool
+/// * `results` - Results of the matches of the pool
+/// * `members` - Indices in `pairs` of the pairs whose matches are counted
+/// * `differential_cap` - Largest point differential counted for a single game
+///
+/// # Returns
+///
+/// * `Vec<Record>` - Record of each pair of `pairs`; blank for pairs outside `members`
+fn records(
+    pairs: &[Pair],
+    results: &[&MatchResult],
+    members: &[usize],
+    differential_cap: u32,
+) -> Vec<Record> {
+    let member_of = |pair: &Pair| {
+        members
+            .iter()
+            .copied()
+            .find(|&index| pairs[index].pair_no == pair.pair_no)
+    };
+
+    let mut records = vec![Record::default(); pairs.len()];
+    for result in results {
+        let (Some(first), Some(second)) =
+            (member_of(&result.pairs[0]), member_of(&result.pairs[1]))
+        else {
+            continue;
+        };
+        records[first].add(result.games.iter().copied(), differential_cap);
+        records[second].add(
+            result.games.iter().map(|game| [game[1], game[0]]),
+            differential_cap,
+        );
+    }
+    records
+}
+
+/// State shared while ranking the tied groups of a pool
+struct Ranking<'a> {
+    /// Name of the pool
+    pool: &'a str,
+    /// Pairs of the pool
+    pairs: &'a [Pair],
+    /// Results of the matches of the pool
+    results: &'a [&'a MatchResult],
+    /// Record of each pair over every match of the pool
+    records: &'a [Record],
+    /// Tiebreak chain and differential cap
+    options: &'a StandingsOptions<'a>,
+}
+
+impl Ranking<'_> {
+    /// Orders a group of tied pairs and appends its subgroups to `groups`, best first
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - Indices in `pairs` of the tied pairs
+    /// * `decided_by` - Tiebreak that separated the group from the other pairs
+    /// * `coin_flips` - Orders of earlier coin flips keyed by pool and tied pair numbers
+    /// * `groups` - Ranked groups with the tiebreak that decided them; a group of more than one pair stays tied
+    fn rank(
+        &self,
+        group: Vec<usize>,
+        decided_by: Option<Tiebreak>,
+        coin_flips: &mut BTreeMap<String, Vec<String>>,
+        groups: &mut Vec<(Vec<usize>, Option<Tiebreak>)>,
+    ) {
+        if group.len() < 2 {
+            groups.push((group, decided_by));
+            return;
+        }
+
+        for &tiebreak in self.options.tiebreaks {
+            if tiebreak == Tiebreak::CoinFlip {
+                for index in self.flip(&group, coin_flips) {
+                    groups.push((vec![index], Some(tiebreak)));
+                }
+                return;
+            }
+
+            let keys = self.keys(tiebreak, &group);
+            let mut order: Vec<(usize, f64)> = group.iter().copied().zip(keys).collect();
+            order.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let subgroups: Vec<Vec<usize>> = order
+                .chunk_by(|a, b| a.1 == b.1)
+                .map(|chunk| chunk.iter().map(|(index, _)| *index).collect())
+                .collect();
+            if subgroups.len() > 1 {
+                for subgroup in subgroups {
+                    self.rank(subgroup, Some(tiebreak), coin_flips, groups);
+                }
+                return;
+            }
+        }
+
+        // No tiebreak separates the pairs, so they share a rank
+        groups.push((group, None));
+    }
+
+    /// Computes the value a tiebreak ranks the pairs of a group by
+    ///
+    /// # Arguments
+    ///
+    /// * `tiebreak` - Tiebreak to apply; not a coin flip
+    /// * `group` - Indices in `pairs` of the tied pairs
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` - Value of each pair of `group`; higher is better
+    fn keys(&self, tiebreak: Tiebreak, group: &[usize]) -> Vec<f64> {
+        if tiebreak == Tiebreak::HeadToHead {
+            let records = records(
+                self.pairs,
+                self.results,
+                group,
+                self.options.differential_cap,
+            );
+            return group
+                .iter()
+                .map(|&index| records[index].won as f64)
+                .collect();
+        }
+
+        group
+            .iter()
+            .map(|&index| {
+                let record = &self.records[index];
+                match tiebreak {
+                    Tiebreak::MatchWins => record.won as f64,
+                    Tiebreak::GameWinPct => record.game_win_pct(),
+                    Tiebreak::PointDifferential => record.differential as f64,
+                    Tiebreak::PointsAllowed => -f64::from(record.points_against),
+                    Tiebreak::HeadToHead | Tiebreak::CoinFlip => 0.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Orders a group of tied pairs by a coin flip, reusing the flip recorded for the same pairs
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - Indices in `pairs` of the tied pairs
+    /// * `coin_flips` - Orders of earlier coin flips keyed by pool and tied pair numbers
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<usize>` - Indices of `group` from first to last
+    fn flip(&self, group: &[usize], coin_flips: &mut BTreeMap<String, Vec<String>>) -> Vec<usize> {
+        let mut pair_nos: Vec<&str> = group
+            .iter()
+            .map(|&index| self.pairs[index].pair_no.as_str())
+            .collect();
+        pair_nos.sort_unstable();
+        let key = format!("{}: {}", self.pool, pair_nos.join(", "));
+
+        let order = coin_flips.entry(key).or_insert_with(|| {
+            let mut order: Vec<String> =
+                pair_nos.iter().map(|pair_no| pair_no.to_string()).collect();
+            order.shuffle(&mut rand::thread_rng());
+            order
+        });
+        order
+            .iter()
+            .filter_map(|pair_no| {
+                group
+                    .iter()
+                    .copied()
+                    .find(|&index| &self.pairs[index].pair_no == pair_no)
+            })
+            .collect()
+    }
+}
+
+/// Returns a standing as a row of the standings CSV
+///
+/// # Arguments
+///
+/// * `pool` - Name of the pool
+/// * `standing` - Standing of the pair
+///
+/// # Returns
+///
+/// * `Vec<String>` - Values in the order of [`STANDING_COLUMNS`]
+pub fn to_record(pool: &str, standing: &Standing) -> Vec<String> {
+    let record = &standing.record;
+    let mut fields = vec![pool.to_string(), standing.rank.to_string()];
+    fields.extend(standing.pair.to_fields());
+    fields.extend(
+        [
+            record.played,
+            record.won,
+            record.lost,
+            record.games_won,
+            record.games_lost,
+        ]
+        .map(|value| value.to_string()),
+    );
+    fields.push(record.points_for.to_string());
+    fields.push(record.points_against.to_string());
+    fields.push(record.differential.to_string());
+    fields.push(
+        standing
+            .decided_by
+            .map_or(String::new(), |tiebreak| tiebreak.name().to_string()),
+    );
+    fields
+}
+
+/// Draws the standings of every pool as pages of a table
+///
+/// # Arguments
+///
+/// * `pools` - Standings of each pool, in pool order
+/// * `title` - Title printed above each table
+///
+/// # Returns
+///
+/// * `Vec<(String, String)>` - Bookmark title and SVG of each page
+pub fn standings_pages(pools: &[(String, Vec<Standing>)], title: &str) -> Vec<(String, String)> {
+    let rows_per_page = ((PAGE_HEIGHT - 2.0 * MARGIN) / ROW_HEIGHT - 2.0).max(1.0) as usize;
+
+    let mut pages = Vec::new();
+    for (pool, standings) in pools {
+        let chunks: Vec<&[Standing]> = standings.chunks(rows_per_page).collect();
+        for (chunk_index, chunk) in chunks.iter().enumerate() {
+            let bookmark = if chunks.len() > 1 {
+                format!("Pool {} ({}/{})", pool, chunk_index + 1, chunks.len())
+            } else {
+                format!("Pool {}", pool)
+            };
+            let heading = format!("{} - Pool {}", title, pool);
+            pages.push((bookmark, standings_page(&heading, chunk)));
+        }
+    }
+    pages
+}
+
+/// Draws one page of the standings table
+///
+/// # Arguments
+///
+/// * `heading` - Title printed above the table
+/// * `standings` - Standings drawn as rows
+///
+/// # Returns
+///
+/// * `String` - SVG of the page
+fn standings_page(heading: &str, standings: &[Standing]) -> String {
+    let total_weight: f32 = TABLE_COLUMNS.iter().map(|(_, weight)| weight).sum();
+    let unit = (PAGE_WIDTH - 2.0 * MARGIN) / total_weight;
+    let top = MARGIN + ROW_HEIGHT;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+        w = PAGE_WIDTH,
+        h = PAGE_HEIGHT
+    );
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" font-size="14" font-weight="bold">{}</text>"#,
+        MARGIN,
+        MARGIN + 14.0,
+        escape_xml(heading)
+    ));
+
+    let rows = standings.iter().map(|standing| {
+        let record = &standing.record;
+        [
+            standing.rank.to_string(),
+            standing.pair.pair_no.clone(),
+            standing.pair.players.join(" / "),
+            record.played.to_string(),
+            record.won.to_string(),
+            record.lost.to_string(),
+            format!("{}-{}", record.games_won, record.games_lost),
+            format!("{:.1}", record.game_win_pct() * 100.0),
+            format!("{}-{}", record.points_for, record.points_against),
+            format!("{:+}", record.differential),
+            standing
+                .decided_by
+                .map_or(String::new(), |tiebreak| tiebreak.name().to_string()),
+        ]
+    });
+    let header = TABLE_COLUMNS.map(|(name, _)| name.to_string());
+
+    for (row, cells) in std::iter::once(header).chain(rows).enumerate() {
+        let y = top + row as f32 * ROW_HEIGHT;
+        let (fill, weight) = if row == 0 {
+            ("#e0e0e0", "bold")
+        } else {
+            ("none", "normal")
+        };
+        let mut x = MARGIN;
+        for (cell, (_, column_weight)) in cells.iter().zip(TABLE_COLUMNS) {
+            let width = column_weight * unit;
+            svg.push_str(&format!(
+                r##"<rect x="{x}" y="{y}" width="{width}" height="{ROW_HEIGHT}" fill="{fill}" stroke="#000" stroke-width="0.5"/>"##
+            ));
+            svg.push_str(&format!(
+                r#"<text x="{}" y="{}" font-size="10" font-weight="{}" text-anchor="middle">{}</text>"#,
+                x + width / 2.0,
+                y + 16.0,
+                weight,
+                escape_xml(cell)
+            ));
+            x += width;
+        }
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a pair with one player named after its number
+    ///
+    /// # Arguments
+    ///
+    /// * `pair_no` - Pair number
+    ///
+    /// # Returns
+    ///
+    /// * `Pair` - The pair
+    fn pair(pair_no: &str) -> Pair {
+        Pair {
+            pair_no: pair_no.to_string(),
+            players: vec![format!("Player {}", pair_no)],
+        }
+    }
+
+    /// Returns the result of a match between two pairs of pool A
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - Pair number of the first pair
+    /// * `second` - Pair number of the second pair
+    /// * `games` - Points of each game, the first pair's points first
+    ///
+    /// # Returns
+    ///
+    /// * `MatchResult` - The result
+    fn result(first: &str, second: &str, games: &[[u32; 2]]) -> MatchResult {
+        MatchResult {
+            pool: "A".to_string(),
+            round: 1,
+            pairs: [pair(first), pair(second)],
+            games: games.to_vec(),
+        }
+    }
+
+    /// Returns the rank, pair number and deciding tiebreak of each standing
+    ///
+    /// # Arguments
+    ///
+    /// * `standings` - Standings from first to last
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(usize, &str, Option<Tiebreak>)>` - One entry per pair
+    fn summary(standings: &[Standing]) -> Vec<(usize, &str, Option<Tiebreak>)> {
+        standings
+            .iter()
+            .map(|standing| {
+                (
+                    standing.rank,
+                    standing.pair.pair_no.as_str(),
+                    standing.decided_by,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn three_way_tie_restarts_the_chain() {
+        // 1, 2 and 3 beat 4 and each other in a circle; the differential puts 1 ahead,
+        // then 2 and 3 are level again and head-to-head between them alone decides
+        let pairs: Vec<Pair> = ["1", "2", "3", "4"].into_iter().map(pair).collect();
+        let results = [
+            result("1", "4", &[[11, 5]]),
+            result("2", "4", &[[11, 5]]),
+            result("3", "4", &[[11, 5]]),
+            result("1", "2", &[[11, 5]]),
+            result("2", "3", &[[11, 7]]),
+            result("3", "1", &[[11, 9]]),
+        ];
+        let results: Vec<&MatchResult> = results.iter().collect();
+        let options = StandingsOptions {
+            tiebreaks: &[
+                Tiebreak::MatchWins,
+                Tiebreak::HeadToHead,
+                Tiebreak::PointDifferential,
+                Tiebreak::PointsAllowed,
+            ],
+            differential_cap: 11,
+        };
+
+        let standings = pool_standings("A", &pairs, &results, &options, &mut BTreeMap::new());
+        assert_eq!(
+            summary(&standings),
+            [
+                (1, "1", Some(Tiebreak::PointDifferential)),
+                (2, "2", Some(Tiebreak::HeadToHead)),
+                (3, "3", Some(Tiebreak::HeadToHead)),
+                (4, "4", Some(Tiebreak::MatchWins)),
+            ]
+        );
+    }
+
+    #[test]
+    fn differential_is_capped_per_game() {
+        let mut record = Record::default();
+        record.add([[11, 0], [9, 11], [11, 2]].into_iter(), 5);
+        assert_eq!(record.differential, 5 - 2 + 5);
+        assert_eq!((record.points_for, record.points_against), (31, 13));
+        assert_eq!((record.games_won, record.games_lost), (2, 1));
+        assert_eq!((record.won, record.lost), (1, 0));
+    }
+
+    #[test]
+    fn pairs_level_on_every_tiebreak_share_a_rank() {
+        let pairs: Vec<Pair> = ["1", "2", "3"].into_iter().map(pair).collect();
+        let results = [result("1", "3", &[[11, 3]]), result("2", "3", &[[11, 3]])];
+        let results: Vec<&MatchResult> = results.iter().collect();
+        let options = StandingsOptions {
+            tiebreaks: &[Tiebreak::MatchWins, Tiebreak::HeadToHead],
+            differential_cap: 11,
+        };
+
+        let standings = pool_standings("A", &pairs, &results, &options, &mut BTreeMap::new());
+        assert_eq!(
+            summary(&standings),
+            [
+                (1, "1", None),
+                (1, "2", None),
+                (3, "3", Some(Tiebreak::MatchWins))
+            ]
+        );
+    }
+
+    #[test]
+    fn coin_flip_is_recorded_and_reused() {
+        let pairs: Vec<Pair> = ["1", "2", "3"].into_iter().map(pair).collect();
+        let options = StandingsOptions {
+            tiebreaks: &[Tiebreak::MatchWins, Tiebreak::CoinFlip],
+            differential_cap: 11,
+        };
+
+        let mut coin_flips = BTreeMap::new();
+        let first = pool_standings("A", &pairs, &[], &options, &mut coin_flips);
+        let order = coin_flips["A: 1, 2, 3"].clone();
+        let ranked: Vec<&str> = first
+            .iter()
+            .map(|standing| standing.pair.pair_no.as_str())
+            .collect();
+        assert_eq!(ranked, order);
+
+        for _ in 0..10 {
+            let again = pool_standings("A", &pairs, &[], &options, &mut coin_flips);
+            assert_eq!(summary(&again), summary(&first));
+        }
+        assert_eq!(coin_flips.len(), 1);
+
+        let mut coin_flips = BTreeMap::from([(
+            "A: 1, 2, 3".to_string(),
+            vec!["3".to_string(), "1".to_string(), "2".to_string()],
+        )]);
+        let standings = pool_standings("A", &pairs, &[], &options, &mut coin_flips);
+        assert_eq!(
+            summary(&standings),
+            [
+                (1, "3", Some(Tiebreak::CoinFlip)),
+                (2, "1", Some(Tiebreak::CoinFlip)),
+                (3, "2", Some(Tiebreak::CoinFlip)),
+            ]
+        );
+    }
+}
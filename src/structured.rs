@@ -0,0 +1,482 @@
+use crate::players::reading_column;
+use anyhow::{bail, Context, Result};
+use csv::Writer;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Formats of structured input documents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// JSON document
+    Json,
+    /// YAML document
+    Yaml,
+}
+
+/// An input document: either the matches to print score sheets for, or registered pairs
+///
+/// The layout is described by `schema/input.schema.json`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InputDocument {
+    /// Schema the document follows; only checked by editors
+    #[serde(rename = "$schema", default)]
+    _schema: Option<String>,
+    /// Matches, read like the rows of a match CSV
+    #[serde(default)]
+    matches: Option<Vec<MatchEntry>>,
+    /// Registered pairs, read like the rows of a pair CSV
+    #[serde(default)]
+    pairs: Option<Vec<PairEntry>>,
+}
+
+/// A match between two pairs
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MatchEntry {
+    /// Match ID, e.g. `A-2-1`
+    #[serde(default)]
+    id: Option<Scalar>,
+    /// Name of the pool
+    #[serde(default)]
+    pool: Option<Scalar>,
+    /// Round within the pool
+    #[serde(default)]
+    round: Option<Scalar>,
+    /// Division whose rules apply to the match
+    #[serde(default)]
+    division: Option<Scalar>,
+    /// The two pairs playing the match
+    pairs: Vec<PairEntry>,
+    /// Further columns, such as `Court` or `Time`
+    #[serde(default)]
+    fields: BTreeMap<String, Scalar>,
+}
+
+/// A pair, or a team of two players with an optional substitute
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PairEntry {
+    /// Pair number printed on the score sheet
+    #[serde(default)]
+    pair_no: Option<Scalar>,
+    /// Name of the team
+    #[serde(default)]
+    team: Option<Scalar>,
+    /// One player for singles, two for doubles
+    players: Vec<PlayerEntry>,
+    /// Substitute who may replace either player
+    #[serde(default)]
+    sub: Option<PlayerEntry>,
+    /// Further columns of a pair document
+    #[serde(default)]
+    fields: BTreeMap<String, Scalar>,
+}
+
+/// A player, given by name alone or with the reading of the name
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PlayerEntry {
+    /// Name of the player
+    Name(String),
+    /// Name of the player with its reading
+    Detailed(PlayerDetails),
+}
+
+/// Name of a player with its reading
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlayerDetails {
+    /// Name of the player
+    name: String,
+    /// Reading of the name in kana
+    #[serde(default)]
+    reading: Option<String>,
+}
+
+/// A value written to a single column: text, number or boolean
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    /// Text, written as is
+    Text(String),
+    /// Whole number, such as a pair number or a round
+    Integer(i64),
+    /// Decimal number
+    Float(f64),
+    /// Boolean, written as `true` or `false`
+    Bool(bool),
+}
+
+/// A row under construction, keeping its columns in order
+#[derive(Default)]
+struct RowBuilder {
+    /// Columns and values in the order they were added
+    fields: Vec<(String, String)>,
+    /// Columns filled from the keys of the entry, whether or not the entry gives them a value
+    generated: Vec<String>,
+}
+
+/// Returns the format of a document from the extension of its path
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+///
+/// # Returns
+///
+/// * `Option<Format>` - The format for `.json`, `.yaml` and `.yml` files, None otherwise
+pub fn format_of(path: &str) -> Option<Format> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "json" => Some(Format::Json),
+        "yaml" | "yml" => Some(Format::Yaml),
+        _ => None,
+    }
+}
+
+/// Guesses the format of a document read from stdin from its first character
+///
+/// # Arguments
+///
+/// * `text` - Contents of the input
+///
+/// # Returns
+///
+/// * `Option<Format>` - JSON for text starting with `{`, None for anything else, which is read as CSV
+pub fn sniff(text: &str) -> Option<Format> {
+    text.trim_start().starts_with('{').then_some(Format::Json)
+}
+
+/// Converts a JSON or YAML document of matches or pairs to CSV
+///
+/// Pair `i` (0 or 1) of a match fills `Pair No{i+1}`, `Team{i+1}`, two `Player` columns
+/// numbered as in a match CSV (`Player3`, `Player4` for the second pair) and `Sub{i+1}`;
+/// a pair document fills `Pair No`, `Team`, `Player1`, `Player2` and `Sub`. Readings go
+/// to the `Kana` column of each player. Columns no entry uses are left out, and `fields`
+/// may not name a column filled from the other keys.
+///
+/// # Arguments
+///
+/// * `text` - Contents of the document
+/// * `format` - Format of the document
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the CSV bytes if successful, Err otherwise
+pub fn to_csv(text: &str, format: Format) -> Result<Vec<u8>> {
+    let document: InputDocument = match format {
+        Format::Json => serde_json::from_str(text).context("Invalid JSON document")?,
+        Format::Yaml => serde_yaml::from_str(text).context("Invalid YAML document")?,
+    };
+
+    let rows = match (document.matches, document.pairs) {
+        (Some(matches), None) => matches
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| match_row(entry, index))
+            .collect::<Result<Vec<_>>>()?,
+        (None, Some(pairs)) => pairs
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| pair_row(entry, &format!("pairs[{}]", index)))
+            .collect::<Result<Vec<_>>>()?,
+        (Some(_), Some(_)) => bail!("A document has either `matches` or `pairs`, not both"),
+        (None, None) => bail!("A document needs `matches` or `pairs`"),
+    };
+
+    write_csv(&rows)
+}
+
+/// Builds the CSV row of a match
+///
+/// # Arguments
+///
+/// * `entry` - The match
+/// * `index` - Position of the match in the document, for errors
+///
+/// # Returns
+///
+/// * `Result<RowBuilder>` - Ok with the row if the match has two pairs, Err otherwise
+fn match_row(entry: &MatchEntry, index: usize) -> Result<RowBuilder> {
+    if entry.pairs.len() != 2 {
+        bail!(
+            "matches[{}]: expected 2 pairs, found {}",
+            index,
+            entry.pairs.len()
+        );
+    }
+
+    let mut row = RowBuilder::default();
+    row.push_optional("Match ID", entry.id.as_ref());
+    row.push_optional("Pool", entry.pool.as_ref());
+    row.push_optional("Round", entry.round.as_ref());
+    row.push_optional("Division", entry.division.as_ref());
+    for (side, pair) in entry.pairs.iter().enumerate() {
+        let location = format!("matches[{}].pairs[{}]", index, side);
+        if !pair.fields.is_empty() {
+            bail!("{}: put the `fields` of a match on the match", location);
+        }
+        let first_player = side * 2 + 1;
+        row.push_pair(
+            pair,
+            &format!("Pair No{}", side + 1),
+            &format!("Team{}", side + 1),
+            [
+                format!("Player{}", first_player),
+                format!("Player{}", first_player + 1),
+            ],
+            &format!("Sub{}", side + 1),
+            &location,
+        )?;
+    }
+    row.push_fields(&entry.fields, &format!("matches[{}]", index))?;
+
+    Ok(row)
+}
+
+/// Builds the CSV row of a registered pair
+///
+/// # Arguments
+///
+/// * `entry` - The pair
+/// * `location` - Position of the pair in the document, for errors
+///
+/// # Returns
+///
+/// * `Result<RowBuilder>` - Ok with the row if the pair has one or two players, Err otherwise
+fn pair_row(entry: &PairEntry, location: &str) -> Result<RowBuilder> {
+    let mut row = RowBuilder::default();
+    row.push_pair(
+        entry,
+        "Pair No",
+        "Team",
+        ["Player1".to_string(), "Player2".to_string()],
+        "Sub",
+        location,
+    )?;
+    row.push_fields(&entry.fields, location)?;
+
+    Ok(row)
+}
+
+/// Writes rows as CSV, with every column used by any row
+///
+/// # Arguments
+///
+/// * `rows` - Rows of the document
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>>` - Ok with the CSV bytes if successful, Err otherwise
+fn write_csv(rows: &[RowBuilder]) -> Result<Vec<u8>> {
+    let mut columns: Vec<&str> = Vec::new();
+    for row in rows {
+        for (column, _) in &row.fields {
+            if !columns.contains(&column.as_str()) {
+                columns.push(column);
+            }
+        }
+    }
+
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(&columns)?;
+    for row in rows {
+        writer.write_record(columns.iter().map(|column| {
+            row.fields
+                .iter()
+                .find(|(name, _)| name == column)
+                .map_or("", |(_, value)| value.as_str())
+        }))?;
+    }
+
+    writer
+        .into_inner()
+        .context("Failed to convert document to CSV")
+}
+
+impl RowBuilder {
+    /// Adds a column if the value is given
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - Name of the column
+    /// * `value` - Value of the column, if any
+    fn push_optional(&mut self, column: &str, value: Option<&Scalar>) {
+        self.generated.push(column.to_string());
+        if let Some(value) = value {
+            self.fields.push((column.to_string(), value.text()));
+        }
+    }
+
+    /// Adds the pair number, team, players and substitute of a pair
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - The pair
+    /// * `pair_no_column` - Column of the pair number
+    /// * `team_column` - Column of the team name
+    /// * `player_columns` - Columns of the two players
+    /// * `sub_column` - Column of the substitute
+    /// * `location` - Position of the pair in the document, for errors
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the pair has one or two players, Err otherwise
+    fn push_pair(
+        &mut self,
+        pair: &PairEntry,
+        pair_no_column: &str,
+        team_column: &str,
+        player_columns: [String; 2],
+        sub_column: &str,
+        location: &str,
+    ) -> Result<()> {
+        if pair.players.is_empty() || pair.players.len() > player_columns.len() {
+            bail!(
+                "{}: expected 1 or 2 players, found {}; put a substitute in `sub`",
+                location,
+                pair.players.len()
+            );
+        }
+
+        self.generated.push(pair_no_column.to_string());
+        self.fields.push((
+            pair_no_column.to_string(),
+            pair.pair_no.as_ref().map(Scalar::text).unwrap_or_default(),
+        ));
+        self.push_optional(team_column, pair.team.as_ref());
+        for (index, column) in player_columns.iter().enumerate() {
+            self.push_player(column, pair.players.get(index));
+        }
+        self.push_player(sub_column, pair.sub.as_ref());
+
+        Ok(())
+    }
+
+    /// Adds a player column, and its reading column if the reading is given
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - Column of the player
+    /// * `player` - The player, if any
+    fn push_player(&mut self, column: &str, player: Option<&PlayerEntry>) {
+        self.generated.push(column.to_string());
+        self.generated.push(reading_column(column));
+        match player {
+            None => {}
+            Some(PlayerEntry::Name(name)) => self.fields.push((column.to_string(), name.clone())),
+            Some(PlayerEntry::Detailed(details)) => {
+                self.fields.push((column.to_string(), details.name.clone()));
+                if let Some(reading) = &details.reading {
+                    self.fields.push((reading_column(column), reading.clone()));
+                }
+            }
+        }
+    }
+
+    /// Adds free-form columns
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Values keyed by column name
+    /// * `location` - Position of the entry in the document, for errors
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if no column is filled from the other keys of the entry, Err otherwise
+    fn push_fields(&mut self, fields: &BTreeMap<String, Scalar>, location: &str) -> Result<()> {
+        for (column, value) in fields {
+            if self.generated.contains(column) {
+                bail!(
+                    "{}.fields: `{}` is filled from the other keys of the entry; set it there instead",
+                    location,
+                    column
+                );
+            }
+            self.fields.push((column.clone(), value.text()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Scalar {
+    /// Returns the value as the text of a CSV field
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The text, number or boolean as written in the document
+    fn text(&self) -> String {
+        match self {
+            Scalar::Text(text) => text.clone(),
+            Scalar::Integer(number) => number.to_string(),
+            Scalar::Float(number) => number.to_string(),
+            Scalar::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a JSON document to CSV text
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The document
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - Ok with the CSV text if successful, Err otherwise
+    fn csv(json: &str) -> Result<String> {
+        Ok(String::from_utf8(to_csv(json, Format::Json)?)?)
+    }
+
+    #[test]
+    fn players_with_readings() {
+        let json =
+            r#"{"pairs": [{"pair_no": 1, "players": ["Al", {"name": "Bo", "reading": "ぼー"}]}]}"#;
+        assert_eq!(
+            csv(json).unwrap(),
+            "Pair No,Player1,Player2,Player2Kana\n1,Al,Bo,ぼー\n"
+        );
+    }
+
+    #[test]
+    fn misspelled_player_keys_are_rejected() {
+        let json = r#"{"pairs": [{"players": [{"name": "Al", "readng": "x"}]}]}"#;
+        assert!(csv(json).is_err());
+    }
+
+    #[test]
+    fn fields_cannot_shadow_generated_columns() {
+        let cases = [
+            (
+                r#"{"matches": [{"pairs": [{"players": ["A"]}, {"players": ["B"]}], "fields": {"Player1": "X"}}]}"#,
+                "matches[0].fields: `Player1`",
+            ),
+            // Columns the entry could fill are reserved even when it leaves them out
+            (
+                r#"{"matches": [{"pairs": [{"players": ["A"]}, {"players": ["B"]}], "fields": {"Player4Kana": "X"}}]}"#,
+                "matches[0].fields: `Player4Kana`",
+            ),
+            (
+                r#"{"pairs": [{"players": ["A"], "fields": {"Team": "X"}}]}"#,
+                "pairs[0].fields: `Team`",
+            ),
+        ];
+        for (json, message) in cases {
+            let err = csv(json).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}", err);
+        }
+
+        let json = r#"{"matches": [{"pairs": [{"players": ["A"]}, {"players": ["B"]}], "fields": {"Court": 3}}]}"#;
+        assert_eq!(
+            csv(json).unwrap(),
+            "Pair No1,Player1,Pair No2,Player3,Court\n,A,,B,3\n"
+        );
+    }
+}
@@ -0,0 +1,933 @@
+use crate::fit::{Fit, TextFitter, TextStyle, LINE_HEIGHT};
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use svg2pdf::usvg::roxmltree;
+
+/// SVG template whose `<text>` elements are filled by element ID
+pub struct Template {
+    /// Source of the SVG template
+    svg: String,
+    /// Fillable text elements keyed by their `id` attribute
+    slots: HashMap<String, TextSlot>,
+    /// Values of the `font-family` attribute of every text element, fillable or not
+    font_families: BTreeSet<String>,
+}
+
+/// Location of a fillable `<text>` element inside the template source
+struct TextSlot {
+    /// Byte range of the element's content, from its first child to its last child
+    content: Range<usize>,
+    /// Start tag of the first `<tspan>`, reused so the filled text keeps its position
+    tspan_start_tag: Option<String>,
+    /// Text of the element in the template, which may contain `{{Column}}` placeholders
+    text: String,
+    /// Font of the element
+    style: TextStyle,
+    /// Width the filled text must fit in, from `data-max-width` or the manifest
+    max_width: Option<f32>,
+    /// Attributes of the first `<tspan>`, by qualified name, with their unescaped values
+    tspan_attributes: Vec<(String, String)>,
+    /// `x` attribute of the first `<tspan>`
+    tspan_x: Option<f32>,
+    /// `y` attribute of the first `<tspan>`
+    tspan_y: Option<f32>,
+    /// `text-anchor` of the element: `start`, `middle` or `end`
+    anchor: String,
+}
+
+/// Font size of ruby, as a fraction of the font size of the text it is printed above
+const RUBY_SCALE: f32 = 0.5;
+
+/// Height of the ruby baseline above the baseline of its text, as a fraction of the font size of the text
+const RUBY_RAISE: f32 = 0.95;
+
+impl Template {
+    /// Parses an SVG template and indexes its `<text>` elements by ID
+    ///
+    /// # Arguments
+    ///
+    /// * `svg_str` - String containing the SVG template
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Template>` - Ok with the parsed template if successful, Err otherwise
+    pub fn parse(svg_str: &str) -> Result<Template> {
+        let document =
+            roxmltree::Document::parse(svg_str).context("Failed to parse SVG template")?;
+
+        let mut slots = HashMap::new();
+        let mut font_families = BTreeSet::new();
+        for node in document.descendants() {
+            if node.has_tag_name("text") || node.has_tag_name("tspan") {
+                let font_family = node
+                    .ancestors()
+                    .find_map(|ancestor| ancestor.attribute("font-family"))
+                    .unwrap_or("serif");
+                font_families.insert(font_family.to_string());
+            }
+            if !node.has_tag_name("text") {
+                continue;
+            }
+            let text = element_text(node);
+            let Some(id) = node.attribute("id") else {
+                if text.contains("{{") {
+                    anyhow::bail!(
+                        "Text element `{}` in SVG template has placeholders but no ID",
+                        text
+                    );
+                }
+                continue;
+            };
+            let (Some(first), Some(last)) = (node.first_child(), node.last_child()) else {
+                anyhow::bail!("Text element `{}` in SVG template has no content", id);
+            };
+
+            let tspan = node.children().find(|child| child.has_tag_name("tspan"));
+            let tspan_start_tag = tspan.map(|tspan| {
+                let end = tspan
+                    .first_child()
+                    .map_or(tspan.range().end, |child| child.range().start);
+                let tag = &svg_str[tspan.range().start..end];
+                // A self-closing `<tspan .../>` is reopened so text can be put inside it
+                match tag.strip_suffix("/>") {
+                    Some(tag) => format!("{}>", tag.trim_end()),
+                    None => tag.to_string(),
+                }
+            });
+
+            let inherited = |name: &str| {
+                tspan.and_then(|tspan| tspan.attribute(name)).or_else(|| {
+                    node.ancestors()
+                        .find_map(|ancestor| ancestor.attribute(name))
+                })
+            };
+            let style = TextStyle {
+                font_family: inherited("font-family").unwrap_or("serif").to_string(),
+                font_size: inherited("font-size")
+                    .and_then(parse_length)
+                    .unwrap_or(12.0),
+            };
+            let max_width = node
+                .attribute("data-max-width")
+                .map(|width| {
+                    parse_length(width).with_context(|| {
+                        format!(
+                            "Invalid data-max-width `{}` on text element `{}`",
+                            width, id
+                        )
+                    })
+                })
+                .transpose()?;
+
+            let slot = TextSlot {
+                content: first.range().start..last.range().end,
+                tspan_start_tag,
+                text,
+                style,
+                max_width,
+                tspan_attributes: tspan.map_or_else(Vec::new, |tspan| {
+                    tspan
+                        .attributes()
+                        .map(|attribute| {
+                            let name = &svg_str[attribute.range_qname()];
+                            (name.to_string(), attribute.value().to_string())
+                        })
+                        .collect()
+                }),
+                tspan_x: tspan
+                    .and_then(|tspan| tspan.attribute("x"))
+                    .and_then(parse_length),
+                tspan_y: tspan
+                    .and_then(|tspan| tspan.attribute("y"))
+                    .and_then(parse_length),
+                anchor: inherited("text-anchor").unwrap_or("start").to_string(),
+            };
+            if slots.insert(id.to_string(), slot).is_some() {
+                anyhow::bail!("Duplicate text element ID `{}` in SVG template", id);
+            }
+        }
+
+        Ok(Template {
+            svg: svg_str.to_string(),
+            slots,
+            font_families,
+        })
+    }
+
+    /// Returns the `font-family` values that the text of the template is printed with
+    ///
+    /// # Returns
+    ///
+    /// * `impl Iterator<Item = &str>` - Each value once, such as `Inter` or `Inter, sans-serif`
+    pub fn font_families(&self) -> impl Iterator<Item = &str> {
+        self.font_families.iter().map(String::as_str)
+    }
+
+    /// Returns the `font-family` value that a fillable text element is printed with
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Element ID
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The value, or None if no fillable element has the ID
+    pub fn font_family(&self, id: &str) -> Option<&str> {
+        self.slots
+            .get(id)
+            .map(|slot| slot.style.font_family.as_str())
+    }
+
+    /// Returns the text elements that contain `{{Column}}` placeholders
+    ///
+    /// # Returns
+    ///
+    /// * `impl Iterator<Item = (&str, &str)>` - ID and template text of each element
+    pub fn placeholder_slots(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.slots
+            .iter()
+            .filter(|(_, slot)| slot.text.contains("{{"))
+            .map(|(id, slot)| (id.as_str(), slot.text.as_str()))
+    }
+
+    /// Returns the CSV columns used by `{{Column}}` placeholders, sorted and deduplicated
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - Names of the columns
+    pub fn placeholder_columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = self
+            .placeholder_slots()
+            .flat_map(|(_, text)| placeholder_names(text))
+            .map(str::to_string)
+            .collect();
+        columns.sort_unstable();
+        columns.dedup();
+        columns
+    }
+
+    /// Sets the width that the text of an element must fit in
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Element ID
+    /// * `max_width` - Maximum width in user units
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the element exists, Err otherwise
+    pub fn set_max_width(&mut self, id: &str, max_width: f32) -> Result<()> {
+        let slot = self
+            .slots
+            .get_mut(id)
+            .with_context(|| format!("SVG template has no text element with ID: {}", id))?;
+        slot.max_width = Some(max_width);
+
+        Ok(())
+    }
+
+    /// Fills text elements with the given values
+    ///
+    /// The whole content of each element is replaced, so text that an editor split
+    /// across several `<tspan>` elements is still replaced as one. Ruby is centered
+    /// above the first line of its element, at half its font size.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - Text for each element, keyed by element ID
+    /// * `ruby` - Ruby printed above the text of elements, keyed by element ID; ignored without a fitter
+    /// * `fitter` - Fits text into elements that have a maximum width, if given
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - Ok with the filled SVG string if successful, Err if an ID is not in the template
+    pub fn fill(
+        &self,
+        values: &HashMap<String, String>,
+        ruby: &HashMap<String, String>,
+        fitter: Option<&TextFitter>,
+    ) -> Result<String> {
+        let mut unknown_ids: Vec<&str> = values
+            .keys()
+            .filter(|id| !self.slots.contains_key(*id))
+            .map(String::as_str)
+            .collect();
+        if !unknown_ids.is_empty() {
+            unknown_ids.sort_unstable();
+            anyhow::bail!(
+                "SVG template has no text element with ID: {}",
+                unknown_ids.join(", ")
+            );
+        }
+
+        let mut replacements: Vec<(&str, &TextSlot, &String)> = values
+            .iter()
+            .map(|(id, value)| (id.as_str(), &self.slots[id], value))
+            .collect();
+        // Replace from the end so earlier byte ranges stay valid
+        replacements.sort_unstable_by_key(|(_, slot, _)| std::cmp::Reverse(slot.content.start));
+
+        let mut svg_str = self.svg.clone();
+        for (id, slot, value) in replacements {
+            let fit = match (fitter, slot.max_width) {
+                (Some(fitter), Some(max_width)) => fitter.fit(value, &slot.style, max_width),
+                _ => None,
+            };
+            let reading = ruby.get(id).filter(|reading| !reading.is_empty());
+            let ruby_content = match (fitter, reading) {
+                (Some(fitter), Some(reading)) => {
+                    ruby_content(slot, value, fit.as_ref(), reading, fitter)
+                }
+                _ => String::new(),
+            };
+            let content = match (fit, &slot.tspan_start_tag) {
+                (Some(fit), _) => fitted_content(slot, &fit),
+                (None, Some(start_tag)) => format!("{}{}</tspan>", start_tag, escape_xml(value)),
+                (None, None) => escape_xml(value),
+            };
+            svg_str.replace_range(slot.content.clone(), &(content + &ruby_content));
+        }
+
+        Ok(svg_str)
+    }
+}
+
+/// Builds the content of a text element whose text was shrunk, condensed or wrapped
+///
+/// Wrapped lines keep the baseline of the last line on the original baseline, so the
+/// text stays above the line it is written on. Each line keeps the attributes of the
+/// original `<tspan>`, such as its class or style, except those the layout replaces.
+///
+/// # Arguments
+///
+/// * `slot` - Text element to fill
+/// * `fit` - Layout of the text
+///
+/// # Returns
+///
+/// * `String` - `<tspan>` elements for each line
+fn fitted_content(slot: &TextSlot, fit: &Fit) -> String {
+    let mut content = String::new();
+
+    for (line_index, line) in fit.lines.iter().enumerate() {
+        let mut replaced = vec!["font-size"];
+        if fit.letter_spacing != 0.0 {
+            replaced.push("letter-spacing");
+        }
+        if slot.tspan_y.is_some() || line_index > 0 {
+            replaced.extend(["y", "dy"]);
+        }
+
+        content.push_str("<tspan");
+        for (name, value) in &slot.tspan_attributes {
+            let value = if name == "style" {
+                // Declarations in `style` would win over the attributes set below
+                value
+                    .split(';')
+                    .filter(|declaration| {
+                        let property = declaration.split(':').next().unwrap_or_default();
+                        !declaration.trim().is_empty() && !replaced.contains(&property.trim())
+                    })
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            } else if replaced.contains(&name.as_str()) {
+                continue;
+            } else {
+                value.clone()
+            };
+            if value.is_empty() && name == "style" {
+                continue;
+            }
+            content.push_str(&format!(" {}=\"{}\"", name, escape_xml(&value)));
+        }
+        if let Some(y) = slot.tspan_y {
+            let lines_below = (fit.lines.len() - 1 - line_index) as f32;
+            let y = y - lines_below * fit.font_size * LINE_HEIGHT;
+            content.push_str(&format!(" y=\"{}\"", y));
+        } else if line_index > 0 {
+            content.push_str(&format!(" dy=\"{}\"", fit.font_size * LINE_HEIGHT));
+        }
+        content.push_str(&format!(" font-size=\"{}\"", fit.font_size));
+        if fit.letter_spacing != 0.0 {
+            content.push_str(&format!(" letter-spacing=\"{}\"", fit.letter_spacing));
+        }
+        content.push('>');
+        content.push_str(&escape_xml(line));
+        content.push_str("</tspan>");
+    }
+
+    content
+}
+
+/// Builds a `<tspan>` printing ruby centered above the first line of a text element
+///
+/// # Arguments
+///
+/// * `slot` - Text element the ruby belongs to
+/// * `value` - Text of the element
+/// * `fit` - Layout of the text, if it was shrunk, condensed or wrapped
+/// * `reading` - Ruby to print
+/// * `fitter` - Measures the text and the ruby
+///
+/// # Returns
+///
+/// * `String` - The `<tspan>`, or an empty string if the element has no numeric position
+fn ruby_content(
+    slot: &TextSlot,
+    value: &str,
+    fit: Option<&Fit>,
+    reading: &str,
+    fitter: &TextFitter,
+) -> String {
+    let (Some(x), Some(y)) = (slot.tspan_x, slot.tspan_y) else {
+        return String::new();
+    };
+
+    let (first_line, font_size, letter_spacing, lines) = match fit {
+        Some(fit) => (
+            fit.lines[0].as_str(),
+            fit.font_size,
+            fit.letter_spacing,
+            fit.lines.len(),
+        ),
+        None => (value, slot.style.font_size, 0.0, 1),
+    };
+    let style = TextStyle {
+        font_family: slot.style.font_family.clone(),
+        font_size,
+    };
+    let gaps = first_line.chars().count().saturating_sub(1) as f32;
+    let width = fitter.width(first_line, &style) + letter_spacing * gaps;
+    let center = match slot.anchor.as_str() {
+        "middle" => x,
+        "end" => x - width / 2.0,
+        _ => x + width / 2.0,
+    };
+    let first_y = y - (lines - 1) as f32 * font_size * LINE_HEIGHT;
+
+    // Ruby may be as wide as the text box, or as the text if there is none
+    let ruby_style = TextStyle {
+        font_family: slot.style.font_family.clone(),
+        font_size: font_size * RUBY_SCALE,
+    };
+    let ruby_width = fitter.width(reading, &ruby_style);
+    let max_width = slot.max_width.unwrap_or(width).max(width);
+    let ruby_size = if ruby_width > max_width {
+        ruby_style.font_size * max_width / ruby_width
+    } else {
+        ruby_style.font_size
+    };
+
+    format!(
+        r#"<tspan x="{}" y="{}" font-size="{}" letter-spacing="0" text-anchor="middle">{}</tspan>"#,
+        center,
+        first_y - font_size * RUBY_RAISE,
+        ruby_size,
+        escape_xml(reading)
+    )
+}
+
+/// Parses an SVG length in user units, such as `12` or `12px`
+///
+/// # Arguments
+///
+/// * `value` - Attribute value
+///
+/// # Returns
+///
+/// * `Option<f32>` - The length, or None if it is not a number
+fn parse_length(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches("px").parse().ok()
+}
+
+/// Returns the ID of a text element on the given card
+///
+/// Cards follow the naming of design tools such as Figma: the first card uses the
+/// bare name (`PLAYER1`) and the following ones add a suffix (`PLAYER1_2`, `PLAYER1_3`, ...).
+///
+/// # Arguments
+///
+/// * `name` - Base ID of the element
+/// * `card_index` - Zero-based index of the card on the page
+///
+/// # Returns
+///
+/// * `String` - ID of the element on that card
+pub fn card_slot_id(name: &str, card_index: usize) -> String {
+    if card_index == 0 {
+        name.to_string()
+    } else {
+        format!("{}_{}", name, card_index + 1)
+    }
+}
+
+/// Returns the index of the card an element belongs to, the inverse of [`card_slot_id`]
+///
+/// # Arguments
+///
+/// * `id` - ID of the element
+/// * `cards_per_page` - Number of cards on each page
+///
+/// # Returns
+///
+/// * `usize` - Zero-based index of the card; 0 if the ID has no card suffix
+pub fn card_index_of(id: &str, cards_per_page: usize) -> usize {
+    id.rsplit_once('_')
+        .and_then(|(_, suffix)| suffix.parse::<usize>().ok())
+        .filter(|card_num| (2..=cards_per_page).contains(card_num))
+        .map_or(0, |card_num| card_num - 1)
+}
+
+/// Replaces `{{Column}}` placeholders in text with the values of a row
+///
+/// # Arguments
+///
+/// * `text` - Text containing placeholders
+/// * `row` - Values of the row keyed by column name, or None to blank every placeholder
+///
+/// # Returns
+///
+/// * `Result<String>` - Ok with the expanded text if successful, Err if a placeholder has no column
+pub fn expand_placeholders(text: &str, row: Option<&HashMap<String, String>>) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+
+        let column = rest[start + 2..start + end].trim();
+        if let Some(row) = row {
+            let value = row.get(column).with_context(|| {
+                let mut columns: Vec<&str> = row.keys().map(String::as_str).collect();
+                columns.sort_unstable();
+                format!(
+                    "Unknown template variable `{{{{{}}}}}`; available columns: {}",
+                    column,
+                    columns.join(", ")
+                )
+            })?;
+            expanded.push_str(value);
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Returns the column names of the `{{Column}}` placeholders in text
+///
+/// # Arguments
+///
+/// * `text` - Text containing placeholders
+///
+/// # Returns
+///
+/// * `Vec<&str>` - Column names in order of appearance
+fn placeholder_names(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + end].trim());
+        rest = &rest[start + end + 2..];
+    }
+
+    names
+}
+
+/// Returns the text of a `<text>` element
+///
+/// When the element has `<tspan>` children, whitespace between them is ignored.
+///
+/// # Arguments
+///
+/// * `node` - The `<text>` element
+///
+/// # Returns
+///
+/// * `String` - Concatenated text of the element
+fn element_text(node: roxmltree::Node) -> String {
+    let has_tspan = node.children().any(|child| child.has_tag_name("tspan"));
+
+    node.descendants()
+        .filter(|child| child.is_text())
+        .filter(|child| !(has_tspan && child.parent() == Some(node)))
+        .filter_map(|child| child.text())
+        .collect()
+}
+
+/// Escapes text for use as XML character data
+///
+/// # Arguments
+///
+/// * `text` - Text to escape
+///
+/// # Returns
+///
+/// * `String` - Escaped text
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps text elements in an SVG document
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - Elements of the document
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The SVG document
+    fn svg(body: &str) -> String {
+        format!(
+            r#"<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">{}</svg>"#,
+            body
+        )
+    }
+
+    /// Builds the values of text elements from ID and text pairs
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - ID and text of each element
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<String, String>` - Text keyed by element ID
+    fn values(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(id, value)| (id.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn text_split_across_tspans_is_replaced_as_one() {
+        // Figma splits text into one `<tspan>` per run of characters
+        let template = Template::parse(&svg(
+            r#"<text id="NAME" font-family="Inter"><tspan x="10" y="20">KINTO </tspan><tspan x="52" y="20">CUP</tspan></text>"#,
+        ))
+        .unwrap();
+
+        let filled = template
+            .fill(&values(&[("NAME", "福岡 OPEN")]), &HashMap::new(), None)
+            .unwrap();
+        assert_eq!(
+            filled,
+            svg(
+                r#"<text id="NAME" font-family="Inter"><tspan x="10" y="20">福岡 OPEN</tspan></text>"#
+            )
+        );
+    }
+
+    #[test]
+    fn self_closing_tspan_is_reopened() {
+        let template = Template::parse(&svg(
+            r#"<text id="PLAYER1"><tspan x="10" y="20" /></text><text id="PLAYER2">Player</text>"#,
+        ))
+        .unwrap();
+
+        let filled = template
+            .fill(
+                &values(&[("PLAYER1", "Ann"), ("PLAYER2", "Ben")]),
+                &HashMap::new(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            filled,
+            svg(
+                r#"<text id="PLAYER1"><tspan x="10" y="20">Ann</tspan></text><text id="PLAYER2">Ben</text>"#
+            )
+        );
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        let template =
+            Template::parse(&svg(r#"<text id="PLAYER1"><tspan>Player</tspan></text>"#)).unwrap();
+
+        let filled = template
+            .fill(
+                &values(&[("PLAYER1", r#"Tom & "Jerry" <TJ>"#)]),
+                &HashMap::new(),
+                None,
+            )
+            .unwrap();
+        assert!(filled.contains("<tspan>Tom &amp; &quot;Jerry&quot; &lt;TJ&gt;</tspan>"));
+        roxmltree::Document::parse(&filled).unwrap();
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let template = Template::parse(&svg(r#"<text id="PLAYER1">Player</text>"#)).unwrap();
+
+        let error = template
+            .fill(
+                &values(&[("PLAYER9", "Ann"), ("PLAYER1", "Ben"), ("NAME", "Cup")]),
+                &HashMap::new(),
+                None,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SVG template has no text element with ID: NAME, PLAYER9"
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let error = Template::parse(&svg(
+            r#"<text id="PLAYER1">Player</text><g><text id="PLAYER1">Player</text></g>"#,
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Duplicate text element ID `PLAYER1` in SVG template"
+        );
+    }
+
+    #[test]
+    fn elements_left_out_keep_their_text() {
+        let template = Template::parse(&svg(
+            r#"<text id="PLAYER1">Player</text><text id="RULES">Best of 3</text>"#,
+        ))
+        .unwrap();
+
+        let filled = template
+            .fill(&values(&[("PLAYER1", "Ann")]), &HashMap::new(), None)
+            .unwrap();
+        assert_eq!(
+            filled,
+            svg(r#"<text id="PLAYER1">Ann</text><text id="RULES">Best of 3</text>"#)
+        );
+    }
+
+    #[test]
+    fn placeholders_are_filled_from_the_row() {
+        let row = values(&[("Court", "3"), ("Round", "2")]);
+
+        assert_eq!(
+            expand_placeholders("Court {{Court}} / R{{ Round }}", Some(&row)).unwrap(),
+            "Court 3 / R2"
+        );
+        // Without a row, as on a card left blank, every placeholder is blanked
+        assert_eq!(
+            expand_placeholders("Court {{Court}}", None).unwrap(),
+            "Court "
+        );
+        // An unclosed placeholder is kept as text
+        assert_eq!(
+            expand_placeholders("{{Court}} {{Round", Some(&row)).unwrap(),
+            "3 {{Round"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_list_the_columns() {
+        let row = values(&[("Round", "2"), ("Court", "3")]);
+
+        let error = expand_placeholders("{{Time}}", Some(&row)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown template variable `{{Time}}`; available columns: Court, Round"
+        );
+    }
+
+    #[test]
+    fn expanded_values_are_escaped_when_filled() {
+        let template = Template::parse(&svg(r#"<text id="COURT">Court {{Court}}</text>"#)).unwrap();
+        let row = values(&[("Court", "A&B <center>")]);
+
+        let text = expand_placeholders(template.placeholder_slots().next().unwrap().1, Some(&row))
+            .unwrap();
+        let filled = template
+            .fill(&values(&[("COURT", &text)]), &HashMap::new(), None)
+            .unwrap();
+        assert!(filled.contains("Court A&amp;B &lt;center&gt;"));
+    }
+
+    #[test]
+    fn placeholder_columns_are_sorted_and_deduplicated() {
+        let template = Template::parse(&svg(
+            r#"<text id="COURT">{{Court}} {{Round}}</text><text id="COURT_2">{{Court}}</text><text id="LABEL">Court</text>"#,
+        ))
+        .unwrap();
+
+        assert_eq!(template.placeholder_columns(), ["Court", "Round"]);
+        assert_eq!(template.placeholder_slots().count(), 2);
+    }
+
+    #[test]
+    fn card_suffixes_map_to_cards() {
+        assert_eq!(card_slot_id("PLAYER1", 0), "PLAYER1");
+        assert_eq!(card_slot_id("PLAYER1", 3), "PLAYER1_4");
+
+        for card_index in 0..4 {
+            assert_eq!(
+                card_index_of(&card_slot_id("Court No", card_index), 4),
+                card_index
+            );
+        }
+        // Suffixes beyond the cards of the page, and `_1`, belong to the first card
+        assert_eq!(card_index_of("COURT_5", 4), 0);
+        assert_eq!(card_index_of("COURT_1", 4), 0);
+        assert_eq!(card_index_of("Court_No", 4), 0);
+    }
+
+    /// Returns a fitter measuring with the bundled test font, with a minimum font size of 8
+    ///
+    /// # Returns
+    ///
+    /// * `TextFitter` - The fitter
+    fn test_fitter() -> TextFitter {
+        let mut fontdb = svg2pdf::usvg::fontdb::Database::new();
+        fontdb.load_font_file("test/fonts/Tuffy.ttf").unwrap();
+        TextFitter::new(std::sync::Arc::new(fontdb), HashMap::new(), 8.0)
+    }
+
+    #[test]
+    fn fitted_lines_keep_the_attributes_of_the_tspan() {
+        let template = Template::parse(&svg(
+            r#"<text id="PLAYER1" font-family="Tuffy" font-size="12" data-max-width="40"><tspan class="name" x="10" y="50" fill="red" style="font-size: 12px; font-weight: bold">P</tspan></text>"#,
+        ))
+        .unwrap();
+
+        let filled = template
+            .fill(
+                &values(&[("PLAYER1", "Alexander Hamilton")]),
+                &HashMap::new(),
+                Some(&test_fitter()),
+            )
+            .unwrap();
+        let document = roxmltree::Document::parse(&filled).unwrap();
+        let lines: Vec<roxmltree::Node> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("tspan"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(line.attribute("class"), Some("name"));
+            assert_eq!(line.attribute("x"), Some("10"));
+            assert_eq!(line.attribute("fill"), Some("red"));
+            // The font size set by fitting is not overridden by the style
+            assert_eq!(line.attribute("style"), Some("font-weight: bold"));
+            assert!(line.attribute("font-size").unwrap().parse::<f32>().unwrap() < 12.0);
+        }
+        assert_eq!(lines[0].text(), Some("Alexander"));
+        assert_eq!(lines[1].text(), Some("Hamilton"));
+        // The last line stays on the original baseline
+        assert_eq!(lines[1].attribute("y"), Some("50"));
+    }
+
+    /// Returns the tspans of a filled SVG document as their attributes and text
+    ///
+    /// # Arguments
+    ///
+    /// * `filled` - The SVG document
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(HashMap<String, String>, String)>` - Attributes and text of each tspan, in document order
+    fn tspans(filled: &str) -> Vec<(HashMap<String, String>, String)> {
+        let document = roxmltree::Document::parse(filled).unwrap();
+        document
+            .descendants()
+            .filter(|node| node.has_tag_name("tspan"))
+            .map(|node| {
+                let attributes = node
+                    .attributes()
+                    .map(|attribute| (attribute.name().to_string(), attribute.value().to_string()))
+                    .collect();
+                (attributes, node.text().unwrap_or_default().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ruby_is_centered_above_the_first_line() {
+        let fitter = test_fitter();
+        let style = TextStyle {
+            font_family: "Tuffy".to_string(),
+            font_size: 20.0,
+        };
+        let half_width = fitter.width("Ann Lee", &style) / 2.0;
+
+        for (anchor, center) in [
+            ("start", 10.0 + half_width),
+            ("middle", 10.0),
+            ("end", 10.0 - half_width),
+        ] {
+            let template = Template::parse(&svg(&format!(
+                r#"<text id="PLAYER1" font-family="Tuffy" font-size="20" text-anchor="{}"><tspan x="10" y="50">P</tspan></text>"#,
+                anchor
+            )))
+            .unwrap();
+            let filled = template
+                .fill(
+                    &values(&[("PLAYER1", "Ann Lee")]),
+                    &values(&[("PLAYER1", "an ri")]),
+                    Some(&fitter),
+                )
+                .unwrap();
+
+            let tspans = tspans(&filled);
+            assert_eq!(tspans.len(), 2, "{}", anchor);
+            assert_eq!(tspans[0].1, "Ann Lee");
+            let (ruby, reading) = &tspans[1];
+            assert_eq!(reading, "an ri");
+            let number = |name: &str| ruby[name].parse::<f32>().unwrap();
+            assert!((number("x") - center).abs() < 0.01, "{}", anchor);
+            assert!((number("y") - (50.0 - 20.0 * RUBY_RAISE)).abs() < 0.01);
+            assert_eq!(number("font-size"), 20.0 * RUBY_SCALE);
+            assert_eq!(ruby["text-anchor"], "middle");
+        }
+    }
+
+    #[test]
+    fn ruby_is_left_out_without_a_reading_or_a_position() {
+        let template = Template::parse(&svg(
+            r#"<text id="PLAYER1" font-family="Tuffy" font-size="20"><tspan x="10" y="50">P</tspan></text><text id="PLAYER2" font-family="Tuffy" font-size="20"><tspan x="10%" y="80">P</tspan></text>"#,
+        ))
+        .unwrap();
+        let names = values(&[("PLAYER1", "Ann"), ("PLAYER2", "Lee")]);
+        let fitter = test_fitter();
+
+        for (ruby, fitter) in [
+            (values(&[]), Some(&fitter)),
+            (values(&[("PLAYER1", ""), ("PLAYER2", "ri")]), Some(&fitter)),
+            (values(&[("PLAYER1", "an"), ("PLAYER2", "ri")]), None),
+        ] {
+            let texts: Vec<String> = tspans(&template.fill(&names, &ruby, fitter).unwrap())
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            assert_eq!(texts, ["Ann", "Lee"], "{:?}", ruby);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Time spent in each stage of processing, shared across rayon workers
+#[derive(Default)]
+pub struct Timings {
+    /// Stages in order of first use, with their total duration and number of calls
+    stages: Mutex<Vec<(&'static str, Duration, usize)>>,
+}
+
+impl Timings {
+    /// Runs a closure and adds its duration to a stage
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - Name of the stage
+    /// * `f` - Closure to run
+    ///
+    /// # Returns
+    ///
+    /// * `T` - Return value of the closure
+    pub fn measure<T>(&self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        let mut stages = self.stages.lock().unwrap_or_else(|err| err.into_inner());
+        match stages.iter_mut().find(|(name, _, _)| *name == stage) {
+            Some((_, duration, calls)) => {
+                *duration += elapsed;
+                *calls += 1;
+            }
+            None => stages.push((stage, elapsed, 1)),
+        }
+
+        result
+    }
+
+    /// Returns how many times a stage has run
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - Name of the stage
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Number of calls, 0 if the stage never ran
+    pub fn calls(&self, stage: &str) -> usize {
+        let stages = self.stages.lock().unwrap_or_else(|err| err.into_inner());
+        stages
+            .iter()
+            .find(|(name, _, _)| *name == stage)
+            .map_or(0, |(_, _, calls)| *calls)
+    }
+
+    /// Prints the time spent in each stage to stderr
+    ///
+    /// Stages run once per page are summed over all rayon workers, so their total can
+    /// exceed the wall-clock time of the run.
+    pub fn report(&self) {
+        let stages = self.stages.lock().unwrap_or_else(|err| err.into_inner());
+        let width = stages
+            .iter()
+            .map(|(name, _, _)| name.len())
+            .max()
+            .unwrap_or(0);
+
+        for (name, duration, calls) in stages.iter() {
+            if *calls == 1 {
+                eprintln!("{:width$}  {:>10.1} ms", name, ms(*duration));
+            } else {
+                eprintln!(
+                    "{:width$}  {:>10.1} ms  ({} calls, {:.1} ms each)",
+                    name,
+                    ms(*duration),
+                    calls,
+                    ms(*duration) / *calls as f64
+                );
+            }
+        }
+    }
+}
+
+/// Converts a duration to fractional milliseconds
+///
+/// # Arguments
+///
+/// * `duration` - Duration to convert
+///
+/// # Returns
+///
+/// * `f64` - Duration in milliseconds
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
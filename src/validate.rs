@@ -0,0 +1,427 @@
+use crate::model::PAIR_COLUMNS;
+use anyhow::{Context, Result};
+use csv::{Reader, StringRecord};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Options of the CSV validation step
+pub struct ValidateOptions<'a> {
+    /// Columns that the template reads from every row
+    pub required_columns: &'a [String],
+    /// CSV columns renamed to the template field they fill
+    pub rename_columns: &'a [(String, String)],
+    /// Pad short rows with blank fields and report blank names as warnings instead of errors
+    pub tolerant: bool,
+    /// Recognizes a player name standing for a pair decided by an earlier match, such as
+    /// `Winner of W1-2`, whose pair number and partner stay blank until that match is played
+    pub is_pending_pair: Option<fn(&str) -> bool>,
+}
+
+/// Rows of a valid CSV file, with the problems that were tolerated
+#[derive(Debug, Default)]
+pub struct Validated {
+    /// Rows keyed by (renamed) column
+    pub rows: Vec<HashMap<String, String>>,
+    /// Line of each row in the file, counting the lines inside quoted fields
+    pub lines: Vec<u64>,
+    /// Problems that did not reject the file, each starting with its line number
+    pub warnings: Vec<String>,
+}
+
+/// Reads and validates every row of a CSV file
+///
+/// All problems are collected before reporting, each with the line number it was found on:
+///
+/// * columns required by the template that are missing from the header
+/// * rows with fewer or more fields than the header
+/// * blank values in required columns, except the pair number and second player of a
+///   pair recognized by `is_pending_pair`
+/// * the same name appearing twice in one match, in the `Player` columns
+///
+/// In tolerant mode, short rows and blank values are returned as warnings for the
+/// caller to report.
+///
+/// # Arguments
+///
+/// * `reader` - CSV reader
+/// * `options` - Validation options
+///
+/// # Returns
+///
+/// * `Result<Validated>` - Ok with the rows and warnings if valid, Err listing every problem otherwise
+pub fn read_rows<R: Read>(reader: &mut Reader<R>, options: &ValidateOptions) -> Result<Validated> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    let header: Vec<String> = reader
+        .headers()
+        .context("Failed to read CSV header")?
+        .iter()
+        .map(|column| {
+            let column = column.trim();
+            options
+                .rename_columns
+                .iter()
+                .find(|(from, _)| from == column)
+                .map_or(column, |(_, to)| to.as_str())
+                .to_string()
+        })
+        .collect();
+
+    let missing_columns: Vec<&str> = options
+        .required_columns
+        .iter()
+        .filter(|column| !header.contains(column))
+        .map(String::as_str)
+        .collect();
+    if !missing_columns.is_empty() {
+        errors.push(format!(
+            "line 1: header is missing required columns: {}",
+            missing_columns.join(", ")
+        ));
+    }
+
+    let mut rows = Vec::new();
+    let mut lines = Vec::new();
+    let mut record = StringRecord::new();
+    loop {
+        let has_record = match reader.read_record(&mut record) {
+            Ok(has_record) => has_record,
+            Err(err) => {
+                errors.push(err.to_string());
+                break;
+            }
+        };
+        if !has_record {
+            break;
+        }
+        let line = record.position().map_or(0, |position| position.line());
+
+        if record.len() > header.len() {
+            errors.push(format!(
+                "line {}: expected {} fields, found {}",
+                line,
+                header.len(),
+                record.len()
+            ));
+            continue;
+        }
+        if record.len() < header.len() {
+            let message = format!(
+                "line {}: expected {} fields, found {}",
+                line,
+                header.len(),
+                record.len()
+            );
+            if options.tolerant {
+                warnings.push(format!("{}; padded with blank fields", message));
+            } else {
+                errors.push(message);
+                continue;
+            }
+        }
+
+        let row: HashMap<String, String> = header
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                let value = record.get(index).unwrap_or_default().trim();
+                (column.clone(), value.to_string())
+            })
+            .collect();
+
+        // A pair decided by an earlier match has only its label until that match is played
+        let pending_columns: Vec<&str> = PAIR_COLUMNS
+            .iter()
+            .filter(|[_, player, _]| {
+                options
+                    .is_pending_pair
+                    .zip(row.get(*player))
+                    .is_some_and(|(is_pending_pair, name)| is_pending_pair(name))
+            })
+            .flat_map(|[pair_no, _, partner]| [*pair_no, *partner])
+            .collect();
+
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for column in options.required_columns {
+            let Some(value) = row.get(column) else {
+                continue;
+            };
+            if value.is_empty() {
+                if pending_columns.contains(&column.as_str()) {
+                    continue;
+                }
+                let message = format!("line {}: blank `{}`", line, column);
+                if options.tolerant {
+                    warnings.push(message);
+                } else {
+                    errors.push(message);
+                }
+                continue;
+            }
+            if !is_player_column(column) {
+                continue;
+            }
+            if let Some(other_column) = seen.insert(value, column) {
+                errors.push(format!(
+                    "line {}: `{}` appears in both `{}` and `{}`",
+                    line, value, other_column, column
+                ));
+            }
+        }
+
+        rows.push(row);
+        lines.push(line);
+    }
+
+    if !errors.is_empty() {
+        errors.extend(
+            warnings
+                .iter()
+                .map(|warning| format!("warning: {}", warning)),
+        );
+        anyhow::bail!("Invalid CSV file:\n{}", errors.join("\n"));
+    }
+
+    Ok(Validated {
+        rows,
+        lines,
+        warnings,
+    })
+}
+
+/// Returns whether a column holds a player, such as `Player1` or `Player4`
+///
+/// # Arguments
+///
+/// * `column` - Name of the column
+///
+/// # Returns
+///
+/// * `bool` - true for `Player` followed by a number
+pub fn is_player_column(column: &str) -> bool {
+    column
+        .strip_prefix("Player")
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::ReaderBuilder;
+
+    /// Reads a CSV string with the given required columns
+    ///
+    /// # Arguments
+    ///
+    /// * `csv` - Contents of the CSV file
+    /// * `required_columns` - Columns that the template reads
+    /// * `tolerant` - Whether to report short rows and blank values as warnings
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Validated>` - Result of [`read_rows`]
+    fn read(csv: &str, required_columns: &[&str], tolerant: bool) -> Result<Validated> {
+        let required_columns: Vec<String> = required_columns
+            .iter()
+            .map(|column| column.to_string())
+            .collect();
+        read_rows(
+            &mut ReaderBuilder::new()
+                .flexible(true)
+                .from_reader(csv.as_bytes()),
+            &ValidateOptions {
+                required_columns: &required_columns,
+                rename_columns: &[],
+                tolerant,
+                is_pending_pair: None,
+            },
+        )
+    }
+
+    #[test]
+    fn same_number_in_pair_and_placeholder_columns_is_valid() {
+        let csv = "Pair No1,Player1,Pair No2,Player3,Court,Round\n1,Ann,2,Ben,1,2\n";
+        let columns = [
+            "Pair No1", "Player1", "Pair No2", "Player3", "Court", "Round",
+        ];
+
+        assert_eq!(read(csv, &columns, false).unwrap().rows.len(), 1);
+    }
+
+    #[test]
+    fn same_player_twice_in_a_match_is_rejected() {
+        let csv = "Player1,Player2,Player3\nAnn,Ben,Ann\n";
+        let error = read(csv, &["Player1", "Player2", "Player3"], false).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("line 2: `Ann` appears in both `Player1` and `Player3`"));
+    }
+
+    #[test]
+    fn tolerated_problems_are_returned_as_warnings() {
+        let csv = "Player1,Player2\nAnn,\nBen\n";
+        let columns = ["Player1", "Player2"];
+
+        let validated = read(csv, &columns, true).unwrap();
+        assert_eq!(validated.rows.len(), 2);
+        assert_eq!(
+            validated.warnings,
+            [
+                "line 2: blank `Player2`",
+                "line 3: expected 2 fields, found 1; padded with blank fields",
+                "line 3: blank `Player2`",
+            ]
+        );
+
+        let error = read(csv, &columns, false).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid CSV file:\nline 2: blank `Player2`\nline 3: expected 2 fields, found 1"
+        );
+    }
+
+    /// Columns that `test/sample.svg` reads from every row
+    const SAMPLE_COLUMNS: [&str; 6] = [
+        "Pair No1", "Player1", "Player2", "Pair No2", "Player3", "Player4",
+    ];
+
+    #[test]
+    fn pair_numbers_and_players_of_the_fixture_are_valid() {
+        let csv = std::fs::read_to_string("test/matches.csv").unwrap();
+
+        let validated = read(&csv, &SAMPLE_COLUMNS, false).unwrap();
+        assert_eq!(validated.rows.len(), 343);
+        assert!(validated.warnings.is_empty());
+        assert_eq!(validated.rows[0]["Pair No1"], "1");
+        assert_eq!(validated.rows[0]["Player4"], "サンプル 四郎");
+    }
+
+    #[test]
+    fn rows_without_pair_numbers_are_short() {
+        // The original fixture has the pair number columns in its header only
+        let csv = std::fs::read_to_string("test/data.csv").unwrap();
+
+        let error = read(&csv, &SAMPLE_COLUMNS, false).unwrap_err().to_string();
+        assert!(error.starts_with("Invalid CSV file:\nline 2: expected 6 fields, found 4\n"));
+
+        let validated = read(&csv, &SAMPLE_COLUMNS, true).unwrap();
+        assert_eq!(validated.rows.len(), 343);
+        assert_eq!(
+            validated.warnings[..3],
+            [
+                "line 2: expected 6 fields, found 4; padded with blank fields",
+                "line 2: blank `Player3`",
+                "line 2: blank `Player4`",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_columns_and_long_rows_are_always_rejected() {
+        let csv = "Pair No1,Player1\n1,Ann,extra\n2,Ben\n";
+
+        let error = read(csv, &["Pair No1", "Player1", "Pair No2"], true)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Invalid CSV file:\nline 1: header is missing required columns: Pair No2\nline 2: expected 2 fields, found 3"
+        );
+    }
+
+    #[test]
+    fn blank_pair_numbers_are_reported_like_names() {
+        let csv = "Pair No1,Player1,Pair No2,Player3\n,Ann,2,Ben\n";
+        let columns = ["Pair No1", "Player1", "Pair No2", "Player3"];
+
+        let error = read(csv, &columns, false).unwrap_err().to_string();
+        assert_eq!(error, "Invalid CSV file:\nline 2: blank `Pair No1`");
+    }
+
+    #[test]
+    fn renamed_columns_fill_their_field() {
+        let csv = "Court No,Player1\n3,Ann\n";
+        let required_columns = ["Court".to_string(), "Player1".to_string()];
+        let rename_columns = [("Court No".to_string(), "Court".to_string())];
+
+        let validated = read_rows(
+            &mut ReaderBuilder::new().from_reader(csv.as_bytes()),
+            &ValidateOptions {
+                required_columns: &required_columns,
+                rename_columns: &rename_columns,
+                tolerant: false,
+                is_pending_pair: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(validated.rows[0]["Court"], "3");
+    }
+
+    #[test]
+    fn line_numbers_count_newlines_inside_quoted_fields() {
+        let csv = "Player1,Player2\n\"Ann\nLee\",Ben\nCat,\n";
+
+        let error = read(csv, &["Player1", "Player2"], false)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "Invalid CSV file:\nline 4: blank `Player2`");
+    }
+
+    #[test]
+    fn rows_keep_the_line_they_start_on() {
+        let csv = "Match ID,Player1\nA-1-1,\"Ann\nLee\"\nA-1-2,Ben\n";
+
+        let validated = read(csv, &["Match ID"], false).unwrap();
+        assert_eq!(validated.lines, [2, 4]);
+    }
+
+    #[test]
+    fn pending_pairs_may_be_blank() {
+        let csv = "Match ID,Pair No1,Player1,Player2,Pair No2,Player3,Player4\n\
+                   W2-1,1,Ann,Ben,,Winner of W1-1,\n\
+                   L1-1,,Loser of W1-1,,,Loser of W1-2,\n\
+                   W2-2,,Winner of W1-2,,4,Cat,\n";
+        let required_columns: Vec<String> = [
+            "Pair No1", "Player1", "Player2", "Pair No2", "Player3", "Player4",
+        ]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+        let read_pending = |is_pending_pair: Option<fn(&str) -> bool>| {
+            read_rows(
+                &mut ReaderBuilder::new().from_reader(csv.as_bytes()),
+                &ValidateOptions {
+                    required_columns: &required_columns,
+                    rename_columns: &[],
+                    tolerant: false,
+                    is_pending_pair,
+                },
+            )
+        };
+
+        let error = read_pending(Some(|name| {
+            name.ends_with(" of W1-1") || name.ends_with(" of W1-2")
+        }))
+        .unwrap_err()
+        .to_string();
+        assert_eq!(error, "Invalid CSV file:\nline 4: blank `Player4`");
+
+        let error = read_pending(None).unwrap_err().to_string();
+        assert_eq!(error.matches("blank `Pair No").count(), 4);
+    }
+
+    #[test]
+    fn player_columns() {
+        for (column, player) in [
+            ("Player1", true),
+            ("Player12", true),
+            ("Player", false),
+            ("Player1Kana", false),
+            ("Pair No1", false),
+        ] {
+            assert_eq!(is_player_column(column), player, "{}", column);
+        }
+    }
+}